@@ -1,28 +1,104 @@
+pub mod adventure;
+pub mod arcade;
+pub mod ascii;
+pub mod assembly;
+pub mod asynchronous;
+pub mod backend;
+pub mod cached;
 pub mod debugger;
+pub mod decompiler;
+pub mod device;
+pub mod differential;
+pub mod droid;
+pub mod extension;
+mod fault;
+pub mod fuzzer;
 pub mod helpers;
 mod instruction;
-mod input;
-mod output;
+pub mod input;
+pub mod memory;
+pub mod modification;
+pub mod optimizer;
+pub mod output;
+pub mod patch;
+mod profile;
+pub mod program;
+pub mod robot;
+pub mod scheduler;
+pub mod search;
+pub mod session;
+pub mod springdroid;
+pub mod symbolic;
+pub mod topology;
 mod value;
-
+pub mod wide;
+mod word;
+
+pub use self::backend::IntcodeBackend;
+pub use self::cached::IntcodeCachedMachine;
+pub use self::device::IntcodeDevice;
+pub use self::extension::{IntcodeEffect, IntcodeExtension, IntcodeExtensions};
+pub use self::fault::IntcodeFault;
 pub use self::instruction::IntcodeInstruction;
-pub use self::input::{IntcodeInput, IntcodeConsoleInput, IntcodePresetInput, IntcodeBlockingInput};
+pub use self::memory::IntcodeAccessCounts;
+pub use self::input::{IntcodeInput, IntcodeConsoleInput, IntcodePresetInput, IntcodeQueueInput, IntcodeBlockingInput};
 pub use self::output::{IntcodeOutput, IntcodeConsoleOutput, IntcodeHistoryOutput};
+pub use self::profile::IntcodeProfile;
+pub use self::program::IntcodeProgram;
+pub use self::session::{IntcodeSession, IntcodeSessionEvent};
 pub use self::value::IntcodeValue;
+pub use self::word::IntcodeWord;
+
+use std::ops::Range;
+
+// Memory starts at 1024 cells and grows when the program writes past the end, up to this limit
+pub(crate) const INITIAL_MEMORY: usize = 1024;
+pub(crate) const MAX_MEMORY: usize = 1 << 16;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum IntcodeState {
     Initialized,
     Running,
     Suspended,
     Halted,
+    Faulted(IntcodeFault),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntcodeSnapshot {
+    state: IntcodeState,
+    instruction_pointer: usize,
+    relative_base: usize,
+    instruction_count: usize,
+    memory: Vec<i64>,
+}
+
+impl IntcodeSnapshot {
+    pub fn state(&self) -> &IntcodeState {
+        &self.state
+    }
+
+    pub fn instruction_count(&self) -> usize {
+        self.instruction_count
+    }
+
+    pub fn memory(&self) -> &[i64] {
+        &self.memory
+    }
 }
 
 pub struct IntcodeMachine<I, O> {
     state: IntcodeState,
     instruction_pointer: usize,
     relative_base: usize,
+    instruction_count: usize,
     memory: Vec<i64>,
+    devices: Vec<(Range<usize>, Box<dyn IntcodeDevice>)>,
+    extensions: IntcodeExtensions,
+    profile: IntcodeProfile,
+    exit_code: Option<i64>,
+    recording: Option<IntcodeSession>,
+    tracking: Option<IntcodeAccessCounts>,
     input_handler: I,
     output_handler: O,
 }
@@ -32,7 +108,7 @@ where I: IntcodeInput,
       O: IntcodeOutput,
 {
     pub fn new(machine_code: &[i64], input_handler: I, output_handler: O) -> Self {
-        let mut memory = vec![0; 1024];
+        let mut memory = vec![0; machine_code.len().max(INITIAL_MEMORY)];
         for i in 0..machine_code.len() {
             memory[i] = machine_code[i];
         }
@@ -41,16 +117,37 @@ where I: IntcodeInput,
             state: IntcodeState::Initialized,
             instruction_pointer: 0,
             relative_base: 0,
+            instruction_count: 0,
             memory,
+            devices: Vec::new(),
+            extensions: IntcodeExtensions::new(),
+            profile: IntcodeProfile::default(),
+            exit_code: None,
+            recording: None,
+            tracking: None,
             input_handler,
             output_handler,
         }
     }
 
     pub fn run(&mut self) {
+        if let Err(fault) = self.try_run() {
+            panic!("{}", fault);
+        }
+    }
+
+    pub fn try_run(&mut self) -> Result<(), IntcodeFault> {
         self.state = IntcodeState::Running;
         while self.state == IntcodeState::Running {
-            self.run_next_instruction();
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    pub fn step(&mut self) -> Result<(), IntcodeFault> {
+        match self.try_decode_instruction(self.instruction_pointer) {
+            Ok(instruction) => self.execute(instruction),
+            Err(fault) => self.raise_fault(fault),
         }
     }
 
@@ -71,10 +168,26 @@ where I: IntcodeInput,
         &self.state
     }
 
+    pub fn instruction_pointer(&self) -> usize {
+        self.instruction_pointer
+    }
+
+    pub fn relative_base(&self) -> usize {
+        self.relative_base
+    }
+
+    pub fn instruction_count(&self) -> usize {
+        self.instruction_count
+    }
+
     pub fn memory(&self) -> &[i64] {
         &self.memory
     }
 
+    pub fn exit_code(&self) -> Option<i64> {
+        self.exit_code
+    }
+
     pub fn read_memory_position(&self, position: usize) -> i64 {
         self.memory[position]
     }
@@ -83,10 +196,34 @@ where I: IntcodeInput,
         self.memory[position] = value;
     }
 
+    pub fn snapshot(&self) -> IntcodeSnapshot {
+        IntcodeSnapshot {
+            state: self.state.clone(),
+            instruction_pointer: self.instruction_pointer,
+            relative_base: self.relative_base,
+            instruction_count: self.instruction_count,
+            memory: self.memory.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &IntcodeSnapshot) {
+        self.state = snapshot.state.clone();
+        self.instruction_pointer = snapshot.instruction_pointer;
+        self.relative_base = snapshot.relative_base;
+        self.instruction_count = snapshot.instruction_count;
+        self.memory = snapshot.memory.clone();
+    }
+
+    // Completes an input instruction the machine is suspended on
     pub fn input(&mut self, value: i64) {
-        let position = self.memory[self.instruction_pointer + 1] as usize;
-        self.write_memory(position, value);
-        self.instruction_pointer += 2;
+        if let IntcodeInstruction::Input{position} = self.decode_instruction(self.instruction_pointer) {
+            self.record(IntcodeSessionEvent::Input{count: self.instruction_count, value});
+            if let Err(fault) = self.write(&position, value) {
+                panic!("{}", fault);
+            }
+            self.instruction_pointer += 2;
+            self.finish_instruction();
+        }
     }
 
     pub fn process_input(&mut self) -> Option<i64> {
@@ -106,106 +243,284 @@ where I: IntcodeInput,
     }
 
     pub fn run_next_instruction(&mut self) {
-        if self.instruction_pointer >= self.memory.len() {
-            panic!("Instruction pointer out of range")
+        if let Err(fault) = self.step() {
+            panic!("{}", fault);
+        }
+    }
+
+    pub fn decode_instruction(&self, position: usize) -> IntcodeInstruction {
+        self.try_decode_instruction(position).unwrap_or_else(|fault| panic!("{}", fault))
+    }
+
+    pub fn try_decode_instruction(&self, position: usize) -> Result<IntcodeInstruction, IntcodeFault> {
+        if position >= self.memory.len() {
+            Err(IntcodeFault::InstructionPointerOutOfRange(position))
         } else {
-            let ptr = self.instruction_pointer;
-            let opcode = self.memory[ptr];
-            let instruction = IntcodeInstruction::new(opcode, &self.memory[ptr+1..]);
-            self.operate(instruction);
+            let opcode = self.memory[position];
+            self.profile.check(opcode)?;
+            IntcodeInstruction::try_decode(opcode, &self.memory[position+1..], &self.extensions)
         }
     }
 
+    pub fn execute(&mut self, instruction: IntcodeInstruction) -> Result<(), IntcodeFault> {
+        self.state = IntcodeState::Running;
+        match self.operate(instruction) {
+            Ok(()) => Ok(()),
+            Err(fault) => self.raise_fault(fault),
+        }
+    }
+
+    pub fn raise_fault(&mut self, fault: IntcodeFault) -> Result<(), IntcodeFault> {
+        self.state = IntcodeState::Faulted(fault.clone());
+        Err(fault)
+    }
+
     fn debug_next_instruction(&mut self) -> IntcodeInstruction {
-        if self.instruction_pointer >= self.memory.len() {
-            panic!("Instruction pointer out of range")
-        } else {
-            let ptr = self.instruction_pointer;
-            let opcode = self.memory[ptr];
-            let instruction = IntcodeInstruction::new(opcode, &self.memory[ptr+1..]);
-            // dbg!(&self);
-            // dbg!(&instruction);
-            // let input = crate::utils::input::read_input_with_prompt("").unwrap();
-            // if input != "" {
-            //     panic!("Aborting execution");
-            // }
-            self.operate(instruction.clone());
-            
-            instruction
+        let instruction = self.decode_instruction(self.instruction_pointer);
+        // dbg!(&self);
+        // dbg!(&instruction);
+        // let input = crate::utils::input::read_input_with_prompt("").unwrap();
+        // if input != "" {
+        //     panic!("Aborting execution");
+        // }
+        if let Err(fault) = self.execute(instruction.clone()) {
+            panic!("{}", fault);
+        }
+
+        instruction
+    }
+
+    pub fn attach_device<D>(&mut self, range: Range<usize>, device: D) -> anyhow::Result<()>
+    where D: IntcodeDevice + 'static
+    {
+        if range.start >= range.end {
+            anyhow::bail!("Empty device range: {:?}", range);
+        }
+        if let Some((existing, _)) = self.devices.iter().find(|(existing, _)| existing.start < range.end && range.start < existing.end) {
+            anyhow::bail!("Device range {:?} overlaps {:?}", range, existing);
+        }
+
+        self.devices.push((range, Box::new(device)));
+        Ok(())
+    }
+
+    pub fn register_extension(&mut self, extension: IntcodeExtension) -> anyhow::Result<()> {
+        self.extensions.register(extension)
+    }
+
+    pub fn extensions(&self) -> &IntcodeExtensions {
+        &self.extensions
+    }
+
+    pub fn set_profile(&mut self, profile: IntcodeProfile) {
+        self.profile = profile;
+    }
+
+    pub fn profile(&self) -> IntcodeProfile {
+        self.profile
+    }
+
+    pub fn start_recording(&mut self) {
+        self.recording = Some(IntcodeSession::new());
+    }
+
+    pub fn stop_recording(&mut self) -> Option<IntcodeSession> {
+        self.recording.take()
+    }
+
+    pub fn recording(&self) -> Option<&IntcodeSession> {
+        self.recording.as_ref()
+    }
+
+    // Counts reads and writes of each memory address until tracking is stopped
+    pub fn start_tracking(&mut self) {
+        self.tracking = Some(IntcodeAccessCounts::new(self.memory.len()));
+    }
+
+    pub fn stop_tracking(&mut self) -> Option<IntcodeAccessCounts> {
+        self.tracking.take()
+    }
+
+    pub fn access_counts(&self) -> Option<&IntcodeAccessCounts> {
+        self.tracking.as_ref()
+    }
+
+    fn record(&mut self, event: IntcodeSessionEvent) {
+        if let Some(recording) = self.recording.as_mut() {
+            recording.push(event);
         }
     }
 
-    fn operate(&mut self, instruction: IntcodeInstruction) {
+    fn device_at(&mut self, address: usize) -> Option<(usize, &mut Box<dyn IntcodeDevice>)> {
+        self.devices.iter_mut()
+            .find(|(range, _)| range.contains(&address))
+            .map(|(range, device)| (address - range.start, device))
+    }
+
+    fn read(&mut self, value: &IntcodeValue) -> Result<i64, IntcodeFault> {
+        if let Some(address) = value.address(self.relative_base)? {
+            if let Some(tracking) = self.tracking.as_mut() {
+                tracking.record_read(address);
+            }
+            if let Some((offset, device)) = self.device_at(address as usize) {
+                return Ok(device.read(offset));
+            }
+            if address as usize >= self.memory.len() && (address as usize) < MAX_MEMORY {
+                return Ok(0);
+            }
+        }
+        value.try_evaluate(&self.memory, self.relative_base)
+    }
+
+    fn write(&mut self, target: &IntcodeValue, value: i64) -> Result<(), IntcodeFault> {
+        let address = self.target_address(target)?;
+        if let Some(tracking) = self.tracking.as_mut() {
+            tracking.record_write(address as i64);
+        }
+        if let Some((offset, device)) = self.device_at(address) {
+            device.write(offset, value);
+            return Ok(());
+        }
+        if address >= self.memory.len() && address < MAX_MEMORY {
+            self.memory.resize(address + 1, 0);
+        }
+
+        match self.memory.get_mut(address) {
+            Some(cell) => {
+                *cell = value;
+                Ok(())
+            },
+            None => Err(IntcodeFault::MemoryOutOfRange(address as i64)),
+        }
+    }
+
+    fn target_address(&self, target: &IntcodeValue) -> Result<usize, IntcodeFault> {
+        match target.address(self.relative_base)? {
+            Some(address) if address >= 0 => Ok(address as usize),
+            Some(address) => Err(IntcodeFault::MemoryOutOfRange(address)),
+            None => Err(IntcodeFault::InvalidParameterMode(1)),
+        }
+    }
+
+    fn operate(&mut self, instruction: IntcodeInstruction) -> Result<(), IntcodeFault> {
         use IntcodeInstruction::*;
         
         match instruction {
             Add{x, y, position} => {
-                let x = x.evaluate(&self.memory, self.relative_base);
-                let y = y.evaluate(&self.memory, self.relative_base);
-                self.write_memory(position, x + y);
+                let x = self.read(&x)?;
+                let y = self.read(&y)?;
+                self.write(&position, x.checked_add(y).ok_or(IntcodeFault::Overflow)?)?;
                 self.instruction_pointer += 4;
             },
             Multiply{x, y, position} => {
-                let x = x.evaluate(&self.memory, self.relative_base);
-                let y = y.evaluate(&self.memory, self.relative_base);
-                self.write_memory(position, x * y);
+                let x = self.read(&x)?;
+                let y = self.read(&y)?;
+                self.write(&position, x.checked_mul(y).ok_or(IntcodeFault::Overflow)?)?;
                 self.instruction_pointer += 4;
             },
             Input{position} => {
+                let address = self.target_address(&position)?;
+                if address >= MAX_MEMORY && self.device_at(address).is_none() {
+                    return Err(IntcodeFault::MemoryOutOfRange(address as i64));
+                }
                 match self.process_input() {
                     Some(input) => { 
-                        self.write_memory(position, input);
+                        self.record(IntcodeSessionEvent::Input{count: self.instruction_count, value: input});
+                        self.write(&position, input)?;
                         self.instruction_pointer += 2;
                     },
+                    None if self.input_handler.is_exhausted() => return Err(IntcodeFault::InputExhausted),
                     None => self.state = IntcodeState::Suspended,
                 }
             },
             Output{value} => {
-                self.process_output(value.evaluate(&self.memory, self.relative_base));
+                let value = self.read(&value)?;
+                self.record(IntcodeSessionEvent::Output{count: self.instruction_count, value});
+                self.process_output(value);
                 self.instruction_pointer += 2;
             },
             JumpIfTrue{test_position, jump_position} => {
-                let test_value = test_position.evaluate(&self.memory, self.relative_base);
-                if test_value > 0 {
-                    self.instruction_pointer = jump_position.evaluate(&self.memory, self.relative_base) as usize;
+                let test_value = self.read(&test_position)?;
+                if test_value != 0 {
+                    self.instruction_pointer = self.read(&jump_position)? as usize;
                 } else {
                     self.instruction_pointer += 3;
                 }
             },
             JumpIfFalse{test_position, jump_position} => {
-                let test_value = test_position.evaluate(&self.memory, self.relative_base);
+                let test_value = self.read(&test_position)?;
                 if test_value == 0 {
-                    self.instruction_pointer = jump_position.evaluate(&self.memory, self.relative_base) as usize;
+                    self.instruction_pointer = self.read(&jump_position)? as usize;
                 } else {
                     self.instruction_pointer += 3;
                 }
             },
             IsLessThan{x, y, position} => {
-                let x = x.evaluate(&self.memory, self.relative_base);
-                let y = y.evaluate(&self.memory, self.relative_base);
+                let x = self.read(&x)?;
+                let y = self.read(&y)?;
                 if x < y {
-                    self.write_memory(position, 1);
+                    self.write(&position, 1)?;
                 } else {
-                    self.write_memory(position, 0);
+                    self.write(&position, 0)?;
                 }
                 self.instruction_pointer += 4;
             },
             IsEquals{x, y, position} => {
-                let x = x.evaluate(&self.memory, self.relative_base);
-                let y = y.evaluate(&self.memory, self.relative_base);
+                let x = self.read(&x)?;
+                let y = self.read(&y)?;
                 if x == y {
-                    self.write_memory(position, 1);
+                    self.write(&position, 1)?;
                 } else {
-                    self.write_memory(position, 0);
+                    self.write(&position, 0)?;
                 }
                 self.instruction_pointer += 4;
             }, 
             SetRelativeBase{offset} => {
-                self.relative_base += offset.evaluate(&self.memory, self.relative_base) as usize;
+                let offset = self.read(&offset)?;
+                let relative_base = (self.relative_base as i64).checked_add(offset).ok_or(IntcodeFault::Overflow)?;
+                if relative_base < 0 {
+                    return Err(IntcodeFault::InvalidRelativeBase(relative_base));
+                }
+                self.relative_base = relative_base as usize;
                 self.instruction_pointer += 2;
             },
             Halt => self.state = IntcodeState::Halted,
-        }     
+            Extension{opcode, ref params, ref position} => {
+                let mut args = Vec::with_capacity(params.len());
+                for param in params {
+                    args.push(self.read(param)?);
+                }
+                let extension = self.extensions.get(opcode).ok_or(IntcodeFault::InvalidInstruction(opcode))?;
+                match extension.execute(&args)? {
+                    IntcodeEffect::Write(value) => {
+                        match position {
+                            Some(position) => self.write(position, value)?,
+                            None => return Err(IntcodeFault::InvalidInstruction(opcode)),
+                        }
+                        self.instruction_pointer += instruction.size();
+                    },
+                    IntcodeEffect::Continue => self.instruction_pointer += instruction.size(),
+                    IntcodeEffect::Jump(target) => self.instruction_pointer = target as usize,
+                    IntcodeEffect::Exit(code) => {
+                        self.exit_code = Some(code);
+                        self.state = IntcodeState::Halted;
+                    },
+                }
+            },
+        }
+
+        if self.state != IntcodeState::Suspended {
+            self.finish_instruction();
+        }
+
+        Ok(())
+    }
+
+    fn finish_instruction(&mut self) {
+        self.instruction_count += 1;
+        for (_, device) in self.devices.iter_mut() {
+            device.tick();
+        }
     }
 }
 
@@ -269,6 +584,72 @@ mod tests {
         assert_eq!(test_program(&[1101,100,-1,4,0]), vec![1101,100,-1,4,99]);
     }
 
+    #[test]
+    fn test_overflow_faults() {
+        let mut machine = IntcodeMachine::new_automated_machine(&[1102,4294967296,4294967296,7,99,0,0,0], &[]);
+        assert_eq!(machine.try_run(), Err(IntcodeFault::Overflow));
+        assert_eq!(machine.state(), &IntcodeState::Faulted(IntcodeFault::Overflow));
+    }
+
+    #[test]
+    fn test_jump_if_true_on_negative() {
+        assert_eq!(test_program(&[1105,-1,7,1101,0,0,0,99]), vec![1105,-1,7,1101,0,0,0,99]);
+    }
+
+    #[test]
+    fn test_relative_writes() {
+        assert_eq!(test_program(&[109,3,21101,2,3,5,99,0,0]), vec![109,3,21101,2,3,5,99,0,5]);
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let program = vec![3,9,8,9,10,9,4,9,99,-1,8];
+        let mut machine = IntcodeMachine::new_blocking_machine(&program);
+        machine.run();
+        let snapshot = machine.snapshot();
+
+        machine.input(8);
+        machine.run();
+        assert_eq!(machine.output_handler().last_output().unwrap(), "1");
+
+        machine.restore(&snapshot);
+        assert_eq!(machine.state(), &IntcodeState::Suspended);
+        machine.input(7);
+        machine.run();
+        assert_eq!(machine.output_handler().last_output().unwrap(), "0");
+    }
+
+    #[test]
+    fn test_memory_grows_on_write() {
+        let mut machine = IntcodeMachine::new_automated_machine(&[1001,5000,7,3000,4,3000,99], &[]);
+        machine.run();
+
+        assert_eq!(machine.output_handler().last_output().unwrap(), "7");
+        assert_eq!(machine.memory().len(), 3001);
+    }
+
+    #[test]
+    fn test_relative_base_overflow_faults() {
+        let mut machine = IntcodeMachine::new_automated_machine(&[109,1,109,9223372036854775807,99], &[]);
+        assert_eq!(machine.try_run(), Err(IntcodeFault::Overflow));
+        assert_eq!(machine.relative_base(), 1);
+    }
+
+    #[test]
+    fn test_relative_address_overflow_faults() {
+        let mut machine = IntcodeMachine::new_automated_machine(&[109,1,21101,0,0,9223372036854775807,99], &[]);
+        assert_eq!(machine.try_run(), Err(IntcodeFault::Overflow));
+        assert_eq!(machine.instruction_pointer(), 2);
+    }
+
+    #[test]
+    fn test_fault_stops_machine() {
+        let mut machine = IntcodeMachine::new_automated_machine(&[1,0,0,0,42], &[]);
+        assert_eq!(machine.try_run(), Err(IntcodeFault::InvalidInstruction(42)));
+        assert_eq!(machine.instruction_pointer(), 4);
+        assert_eq!(machine.instruction_count(), 1);
+    }
+
     #[test]
     fn test_chaining() {
         use std::str::FromStr;
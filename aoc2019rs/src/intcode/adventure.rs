@@ -0,0 +1,560 @@
+use anyhow::{anyhow, bail, Result};
+
+use std::collections::{HashMap, VecDeque};
+use std::collections::hash_map::Entry;
+
+use crate::intcode::IntcodeProgram;
+use crate::intcode::ascii::IntcodeAsciiMachine;
+use crate::utils::graph::Graph;
+use crate::utils::grid::{Direction, Grid, Point};
+use crate::utils::input;
+
+// Enough for any one command; items that trap the program in a loop run into it
+const MAX_STEPS: usize = 5_000_000;
+
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Right, Direction::Left];
+
+pub fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Up => "north",
+        Direction::Down => "south",
+        Direction::Right => "east",
+        Direction::Left => "west",
+    }
+}
+
+pub fn parse_direction(name: &str) -> Option<Direction> {
+    DIRECTIONS.iter().copied().find(|direction| direction_name(*direction) == name)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntcodeRoom {
+    pub name: String,
+    pub description: String,
+    pub doors: Vec<Direction>,
+    pub items: Vec<String>,
+}
+
+// Every room described in the text, in order. Rooms look like
+//
+//   == Hull Breach ==
+//   You got in through a hole in the floor here.
+//
+//   Doors here lead:
+//   - north
+//
+//   Items here:
+//   - mouse
+pub fn parse_rooms(text: &str) -> Vec<IntcodeRoom> {
+    let mut rooms: Vec<IntcodeRoom> = Vec::new();
+    let mut list: Option<&str> = None;
+    for line in text.lines() {
+        if let Some(name) = line.strip_prefix("== ").and_then(|line| line.strip_suffix(" ==")) {
+            rooms.push(IntcodeRoom { name: String::from(name), description: String::new(), doors: Vec::new(), items: Vec::new() });
+            list = None;
+            continue;
+        }
+        let room = match rooms.last_mut() {
+            Some(room) => room,
+            None => continue,
+        };
+
+        match (line, line.strip_prefix("- "), list) {
+            ("Doors here lead:", ..) => list = Some("doors"),
+            ("Items here:", ..) => list = Some("items"),
+            (_, Some(door), Some("doors")) => room.doors.extend(parse_direction(door)),
+            (_, Some(item), Some("items")) => room.items.push(String::from(item)),
+            ("", ..) => list = None,
+            (_, _, None) if room.description.is_empty() => room.description = String::from(line),
+            _ => {},
+        }
+    }
+
+    rooms
+}
+
+// The airlock password from the message shown once past the pressure plate
+pub fn password(text: &str) -> Option<i64> {
+    let start = text.find("typing ")? + "typing ".len();
+    text[start..].split_whitespace().next()?.parse().ok()
+}
+
+// Rooms by the order they were found in, and the doors known to lead between them
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IntcodeAdventureMap {
+    rooms: Vec<IntcodeRoom>,
+    connections: HashMap<(usize, Direction), usize>,
+}
+
+impl IntcodeAdventureMap {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn rooms(&self) -> &[IntcodeRoom] {
+        &self.rooms
+    }
+
+    pub fn room_index(&self, name: &str) -> Option<usize> {
+        self.rooms.iter().position(|room| room.name == name)
+    }
+
+    // Rooms are told apart by name; seeing one again refreshes what's in it
+    pub fn add_room(&mut self, room: IntcodeRoom) -> usize {
+        match self.room_index(&room.name) {
+            Some(index) => {
+                self.rooms[index] = room;
+                index
+            },
+            None => {
+                self.rooms.push(room);
+                self.rooms.len() - 1
+            },
+        }
+    }
+
+    pub fn connect(&mut self, from: usize, direction: Direction, to: usize) {
+        self.connections.insert((from, direction), to);
+        self.connections.insert((to, direction.reverse()), from);
+    }
+
+    pub fn neighbour(&self, room: usize, direction: Direction) -> Option<usize> {
+        self.connections.get(&(room, direction)).copied()
+    }
+
+    pub fn unexplored(&self, room: usize) -> Vec<Direction> {
+        self.rooms[room].doors.iter().copied().filter(|door| self.neighbour(room, *door).is_none()).collect()
+    }
+
+    pub fn path(&self, from: usize, to: usize) -> Option<Vec<Direction>> {
+        let mut graph = Graph::new();
+        for room in 0..self.rooms.len() {
+            let neighbours = DIRECTIONS.iter().filter_map(|direction| self.neighbour(room, *direction)).collect();
+            graph.add_node_with_adjacency_list(room, neighbours);
+        }
+
+        let rooms = graph.shortest_path(from, to)?;
+        rooms.windows(2)
+            .map(|pair| DIRECTIONS.iter().copied().find(|direction| self.neighbour(pair[0], *direction) == Some(pair[1])))
+            .collect()
+    }
+
+    // Lays rooms out by the doors between them, starting from the first room found
+    fn positions(&self) -> HashMap<usize, Point> {
+        let mut positions = HashMap::new();
+        let mut queue = VecDeque::new();
+        if !self.rooms.is_empty() {
+            positions.insert(0, (0, 0));
+            queue.push_back(0);
+        }
+        while let Some(room) = queue.pop_front() {
+            for direction in DIRECTIONS {
+                let position = direction.step(positions[&room]);
+                if let Some(neighbour) = self.neighbour(room, direction) {
+                    if let Entry::Vacant(entry) = positions.entry(neighbour) {
+                        entry.insert(position);
+                        queue.push_back(neighbour);
+                    }
+                }
+            }
+        }
+
+        positions
+    }
+
+    // Rooms as `o` (or `@` for the current one) joined by corridors, followed by a legend
+    pub fn render(&self, current: usize) -> String {
+        let positions = self.positions();
+        let mut grid = Grid::new();
+        for (room, (x, y)) in &positions {
+            grid.set((2 * x, 2 * y), if *room == current { '@' } else { 'o' });
+            for direction in DIRECTIONS {
+                if self.neighbour(*room, direction).is_some() {
+                    let corridor = if matches!(direction, Direction::Up | Direction::Down) { '|' } else { '-' };
+                    grid.set(direction.step((2 * x, 2 * y)), corridor);
+                }
+            }
+        }
+
+        let mut text = grid.render(|_, cell| cell.copied().unwrap_or(' '));
+        let mut rooms: Vec<(&usize, &Point)> = positions.iter().collect();
+        rooms.sort_by_key(|(_, (x, y))| (*y, *x));
+        for (room, (x, y)) in rooms {
+            let room = &self.rooms[*room];
+            text.push_str(&format!("\n{:>3},{:<3} {}", x, y, room.name));
+            if !room.items.is_empty() {
+                text.push_str(&format!(" [{}]", room.items.join(", ")));
+            }
+        }
+
+        text
+    }
+}
+
+// Plays a text adventure through its ASCII terminal, keeping track of where it has been
+pub struct IntcodeAdventure {
+    machine: IntcodeAsciiMachine,
+    map: IntcodeAdventureMap,
+    current: usize,
+    inventory: Vec<String>,
+    dangerous: Vec<String>,
+    // The room before the pressure plate and the door to it
+    checkpoint: Option<(usize, Direction)>,
+    output: String,
+}
+
+impl IntcodeAdventure {
+    pub fn new(program: &[i64]) -> Result<Self> {
+        let mut machine = IntcodeAsciiMachine::new(program).with_step_limit(MAX_STEPS);
+        let output = machine.run()?;
+        let room = parse_rooms(&output).pop().ok_or(anyhow!("The program didn't describe a starting room"))?;
+
+        let mut map = IntcodeAdventureMap::new();
+        let current = map.add_room(room);
+        Ok(Self { machine, map, current, inventory: Vec::new(), dangerous: Vec::new(), checkpoint: None, output })
+    }
+
+    pub fn map(&self) -> &IntcodeAdventureMap {
+        &self.map
+    }
+
+    pub fn current(&self) -> &IntcodeRoom {
+        &self.map.rooms[self.current]
+    }
+
+    pub fn inventory(&self) -> &[String] {
+        &self.inventory
+    }
+
+    pub fn dangerous(&self) -> &[String] {
+        &self.dangerous
+    }
+
+    // What the program printed after the last command
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    pub fn command(&mut self, command: &str) -> Result<String> {
+        let text = self.machine.send_line(command)?;
+        let rooms = parse_rooms(&text);
+
+        if let (Some(direction), Some(first), Some(last)) = (parse_direction(command), rooms.first(), rooms.last()) {
+            let entered = self.map.add_room(first.clone());
+            self.map.connect(self.current, direction, entered);
+            if rooms.len() > 1 {
+                // Turned back by the pressure plate
+                self.checkpoint = Some((self.current, direction));
+            }
+            self.current = self.map.add_room(last.clone());
+        }
+        if let (Some(item), true) = (command.strip_prefix("take "), text.contains("You take")) {
+            self.inventory.push(String::from(item));
+            self.map.rooms[self.current].items.retain(|here| here != item);
+        }
+        if let (Some(item), true) = (command.strip_prefix("drop "), text.contains("You drop")) {
+            self.inventory.retain(|held| held != item);
+            self.map.rooms[self.current].items.push(String::from(item));
+        }
+
+        self.output = text.clone();
+        Ok(text)
+    }
+
+    // Visits every room depth first, picking up everything that turns out to be safe
+    pub fn explore(&mut self) -> Result<()> {
+        self.explore_from(None)
+    }
+
+    fn explore_from(&mut self, entered: Option<Direction>) -> Result<()> {
+        let room = self.current;
+        let way_back = entered.map(|direction| direction.reverse()).or_else(|| self.map.rooms[room].doors.first().copied());
+        for item in self.map.rooms[room].items.clone() {
+            if !self.dangerous.contains(&item) {
+                self.take_safely(&item, way_back)?;
+            }
+        }
+
+        for direction in self.map.unexplored(room) {
+            if self.map.neighbour(room, direction).is_some() {
+                continue;
+            }
+            let known = self.map.rooms.len();
+            self.command(direction_name(direction))?;
+            if self.current == room {
+                continue;
+            }
+            if self.map.rooms.len() > known {
+                self.explore_from(Some(direction))?;
+            }
+            self.command(direction_name(direction.reverse()))?;
+            if self.current != room {
+                bail!("Couldn't find the way back to {}", self.map.rooms[room].name);
+            }
+        }
+
+        Ok(())
+    }
+
+    // An item is dangerous if taking it ends the game, never returns, or stops the droid moving.
+    // The game is rolled back to before taking it in that case.
+    fn take_safely(&mut self, item: &str, test_move: Option<Direction>) -> Result<bool> {
+        let snapshot = self.machine.snapshot();
+        let saved = (self.map.clone(), self.current, self.inventory.clone(), self.checkpoint);
+
+        let safe = self.try_item(item, test_move).unwrap_or(false);
+        if !safe {
+            self.machine.restore(&snapshot);
+            self.map = saved.0;
+            self.current = saved.1;
+            self.inventory = saved.2;
+            self.checkpoint = saved.3;
+            self.dangerous.push(String::from(item));
+        }
+        Ok(safe)
+    }
+
+    fn try_item(&mut self, item: &str, test_move: Option<Direction>) -> Result<bool> {
+        let room = self.current;
+        if !self.command(&format!("take {}", item))?.contains("You take") || self.machine.is_halted() {
+            return Ok(false);
+        }
+        if let Some(direction) = test_move {
+            // The test move only checks the droid can still walk, so the map is left as if it
+            // never happened and whatever is through that door still gets explored
+            let saved = (self.map.clone(), self.checkpoint);
+            self.command(direction_name(direction))?;
+            if self.current == room || self.machine.is_halted() {
+                return Ok(false);
+            }
+            self.command(direction_name(direction.reverse()))?;
+            self.map = saved.0;
+            self.checkpoint = saved.1;
+        }
+        Ok(self.current == room && !self.machine.is_halted())
+    }
+
+    // Walks to the checkpoint and tries every set of held items on the pressure plate, changing
+    // one item at a time (in Gray code order). Returns what the game said on success.
+    pub fn pass_checkpoint(&mut self) -> Result<String> {
+        let (checkpoint, plate) = self.checkpoint.ok_or(anyhow!("The pressure plate hasn't been found"))?;
+        let path = self.map.path(self.current, checkpoint).ok_or(anyhow!("No known way to the checkpoint"))?;
+        for direction in path {
+            self.command(direction_name(direction))?;
+        }
+
+        let items = self.inventory.clone();
+        for step in 0..1usize << items.len() {
+            let set = step ^ (step >> 1);
+            for (index, item) in items.iter().enumerate() {
+                match (set & (1 << index) != 0, self.inventory.contains(item)) {
+                    (true, false) => self.command(&format!("take {}", item))?,
+                    (false, true) => self.command(&format!("drop {}", item))?,
+                    _ => continue,
+                };
+            }
+
+            let text = self.command(direction_name(plate))?;
+            if self.current != checkpoint {
+                return Ok(text);
+            }
+        }
+
+        bail!("No combination of {:?} gets past the pressure plate", items)
+    }
+
+    // Reads commands with line editing; `map` shows what has been found so far and `quit` leaves
+    pub fn manual(&mut self) -> Result<()> {
+        print!("{}", self.output);
+        while !self.machine.is_halted() {
+            let line = input::read_input_with_prompt("> ")?;
+            match line.trim() {
+                "quit" => break,
+                "map" => println!("{}", self.map.render(self.current)),
+                command => print!("{}", self.command(command)?),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Usage: adventure <file in input/> [--manual]
+pub fn run_command(args: &[String]) -> Result<String> {
+    let program = IntcodeProgram::load_from_args(args)?.code;
+    let mut adventure = IntcodeAdventure::new(&program)?;
+
+    if args.get(1).map(|arg| arg == "--manual").unwrap_or(false) {
+        adventure.manual()?;
+        return Ok(adventure.map.render(adventure.current));
+    }
+
+    adventure.explore()?;
+    let text = adventure.pass_checkpoint()?;
+    let map = adventure.map.render(adventure.current);
+    match password(&text) {
+        Some(password) => Ok(format!("{}\nDangerous items: {:?}\nPassword: {}", map, adventure.dangerous, password)),
+        None => Ok(format!("{}\n{}", map, text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: &str = "
+== Hull Breach ==
+You got in through a hole in the floor here. To keep your ship from also freezing, the hole has been sealed.
+
+Doors here lead:
+- north
+- east
+
+Items here:
+- mouse
+- giant electromagnet
+
+Command?
+";
+
+    const EJECTED: &str = "
+== Pressure-Sensitive Floor ==
+Analyzing...
+
+Doors here lead:
+- south
+
+A loud, robotic voice says \"Alert! Droids on this ship are heavier than the detected value!\" and you are ejected back to the checkpoint.
+
+
+
+== Security Checkpoint ==
+In the next room, a pressure-sensitive floor will verify your identity.
+
+Doors here lead:
+- north
+- west
+
+Command?
+";
+
+    fn room(name: &str, doors: &[Direction]) -> IntcodeRoom {
+        IntcodeRoom { name: String::from(name), description: String::new(), doors: doors.to_vec(), items: Vec::new() }
+    }
+
+    #[test]
+    fn test_parse_room() {
+        let rooms = parse_rooms(START);
+
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(rooms[0].name, "Hull Breach");
+        assert!(rooms[0].description.starts_with("You got in through a hole"));
+        assert_eq!(rooms[0].doors, vec![Direction::Up, Direction::Right]);
+        assert_eq!(rooms[0].items, vec!["mouse", "giant electromagnet"]);
+    }
+
+    #[test]
+    fn test_parse_ejection() {
+        let rooms = parse_rooms(EJECTED);
+
+        assert_eq!(rooms.iter().map(|room| room.name.as_str()).collect::<Vec<&str>>(), vec!["Pressure-Sensitive Floor", "Security Checkpoint"]);
+        assert_eq!(rooms[1].doors, vec![Direction::Up, Direction::Left]);
+        assert!(rooms[1].items.is_empty());
+        assert_eq!(password(EJECTED), None);
+        assert_eq!(password("\"Oh, hello! You should be able to get in by typing 2424308736 on the keypad at the main airlock.\""), Some(2424308736));
+    }
+
+    // Any command moves between the two rooms, so taking the mouse sends the droid away
+    fn two_room_game() -> Vec<i64> {
+        let print = |text: &str| crate::intcode::ascii::encode(text).into_iter().flat_map(|code| vec![104, code]).collect::<Vec<i64>>();
+        let read_line = |start: usize| vec![3,1000,1008,1000,10,1001,1006,1001,start as i64];
+
+        let mut program = print("== Hull Breach ==\nCold.\n\nDoors here lead:\n- north\n\nCommand?\n");
+        program.extend(read_line(program.len()));
+        program.extend(print("== Lab ==\nWarm.\n\nDoors here lead:\n- south\n\nItems here:\n- mouse\n\nCommand?\n"));
+        program.extend(read_line(program.len()));
+        program.extend(vec![1105,1,0]);
+        program
+    }
+
+    #[test]
+    fn test_explore() {
+        let mut adventure = IntcodeAdventure::new(&two_room_game()).unwrap();
+        assert_eq!(adventure.current().name, "Hull Breach");
+
+        adventure.explore().unwrap();
+        assert_eq!(adventure.current().name, "Hull Breach");
+        assert_eq!(adventure.map().rooms().len(), 2);
+        assert_eq!(adventure.dangerous(), &["mouse"]);
+        assert!(adventure.inventory().is_empty());
+        assert_eq!(adventure.map().path(0, 1), Some(vec![Direction::Up]));
+        assert!(adventure.pass_checkpoint().is_err());
+    }
+
+    // Three rooms in a line going north, with a key in the first one. Only the first letter of a
+    // command counts.
+    fn corridor_game() -> Vec<i64> {
+        const ROOM: i64 = 1000;
+        const COMMAND: i64 = 1001;
+        const CHARACTER: i64 = 1002;
+        const TEST: i64 = 1003;
+        // Jump targets are written as -label until every block is placed
+        let label = |index: usize| -(index as i64) - 10_000;
+        let print = |text: &str| crate::intcode::ascii::encode(text).into_iter().flat_map(|code| vec![104, code]).collect::<Vec<i64>>();
+        let then_read = |mut block: Vec<i64>| { block.extend(vec![1105,1,label(3)]); block };
+        let blocks = vec![
+            then_read(print("== Hull Breach ==\nCold.\n\nDoors here lead:\n- north\n\nItems here:\n- key\n\nCommand?\n")),
+            then_read(print("== Lab ==\nWarm.\n\nDoors here lead:\n- north\n- south\n\nCommand?\n")),
+            then_read(print("== Galley ==\nHot.\n\nDoors here lead:\n- south\n\nCommand?\n")),
+            // Reads a line, keeping its first character
+            vec![3,COMMAND],
+            vec![3,CHARACTER, 1008,CHARACTER,10,TEST, 1006,TEST,label(4)],
+            vec![1008,COMMAND,110,TEST, 1005,TEST,label(6), 1008,COMMAND,115,TEST, 1005,TEST,label(7),
+                 1008,COMMAND,116,TEST, 1005,TEST,label(9), 1105,1,label(10)],
+            // North and south, then showing the room
+            vec![1008,ROOM,2,TEST, 1005,TEST,label(10), 1001,ROOM,1,ROOM, 1105,1,label(8)],
+            vec![1008,ROOM,0,TEST, 1005,TEST,label(10), 1001,ROOM,-1,ROOM, 1105,1,label(8)],
+            vec![1008,ROOM,0,TEST, 1005,TEST,label(0), 1008,ROOM,1,TEST, 1005,TEST,label(1), 1105,1,label(2)],
+            then_read(print("\nYou take the key.\n\nCommand?\n")),
+            then_read(print("\nYou can't go that way.\n\nCommand?\n")),
+        ];
+
+        let mut starts = Vec::new();
+        let mut program: Vec<i64> = Vec::new();
+        for block in &blocks {
+            starts.push(program.len() as i64);
+            program.extend(block);
+        }
+        program.into_iter().map(|word| if word <= -10_000 { starts[(-word - 10_000) as usize] } else { word }).collect()
+    }
+
+    #[test]
+    fn test_explore_past_the_door_used_to_test_items() {
+        let mut adventure = IntcodeAdventure::new(&corridor_game()).unwrap();
+
+        adventure.explore().unwrap();
+        assert_eq!(adventure.inventory(), &["key"]);
+        assert!(adventure.dangerous().is_empty());
+        assert_eq!(adventure.current().name, "Hull Breach");
+        assert_eq!(adventure.map().rooms().len(), 3);
+        assert_eq!(adventure.map().path(0, 2), Some(vec![Direction::Up, Direction::Up]));
+    }
+
+    #[test]
+    fn test_map() {
+        let mut map = IntcodeAdventureMap::new();
+        let breach = map.add_room(room("Hull Breach", &[Direction::Up, Direction::Right]));
+        let lab = map.add_room(room("Lab", &[Direction::Down, Direction::Right]));
+        let galley = map.add_room(room("Galley", &[Direction::Left, Direction::Up]));
+        map.connect(breach, Direction::Up, lab);
+        map.connect(lab, Direction::Right, galley);
+
+        assert_eq!(map.path(breach, galley), Some(vec![Direction::Up, Direction::Right]));
+        assert_eq!(map.path(galley, breach), Some(vec![Direction::Left, Direction::Down]));
+        assert_eq!(map.unexplored(breach), vec![Direction::Right]);
+        assert_eq!(map.unexplored(galley), vec![Direction::Up]);
+        assert_eq!(map.add_room(room("Lab", &[])), lab);
+
+        assert_eq!(map.render(galley), "o-@\n|  \no  \n  0,-1  Lab\n  1,-1  Galley\n  0,0   Hull Breach");
+    }
+}
@@ -0,0 +1,324 @@
+use anyhow::{anyhow, bail, Result};
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::intcode::{IntcodeMachine, IntcodeOutput, IntcodeProgram, IntcodeState};
+use crate::intcode::patch::IntcodePatchSet;
+use crate::utils::grid::Grid;
+use crate::utils::input;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntcodeTile {
+    Empty,
+    Wall,
+    Block,
+    Paddle,
+    Ball,
+}
+
+impl IntcodeTile {
+    pub fn from_id(id: i64) -> Result<Self> {
+        use IntcodeTile::*;
+
+        match id {
+            0 => Ok(Empty),
+            1 => Ok(Wall),
+            2 => Ok(Block),
+            3 => Ok(Paddle),
+            4 => Ok(Ball),
+            _ => bail!("Invalid tile id: {}", id),
+        }
+    }
+
+    fn symbol(&self) -> char {
+        use IntcodeTile::*;
+
+        match self {
+            Empty => ' ',
+            Wall => '#',
+            Block => '+',
+            Paddle => '=',
+            Ball => 'o',
+        }
+    }
+
+    fn color(&self) -> [u8; 3] {
+        use IntcodeTile::*;
+
+        match self {
+            Empty => [0, 0, 0],
+            Wall => [128, 128, 128],
+            Block => [200, 80, 40],
+            Paddle => [40, 120, 220],
+            Ball => [255, 255, 255],
+        }
+    }
+}
+
+// What the cabinet has drawn so far. An output triple of (-1, 0, score) sets the score instead of a tile.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IntcodeArcadeScreen {
+    tiles: Grid<IntcodeTile>,
+    score: i64,
+}
+
+impl IntcodeArcadeScreen {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn draw(&mut self, x: i64, y: i64, value: i64) -> Result<()> {
+        match (x, y) {
+            (-1, 0) => self.score = value,
+            (x, y) if x >= 0 && y >= 0 => self.tiles.set((x, y), IntcodeTile::from_id(value)?),
+            _ => bail!("Can't draw at ({}, {})", x, y),
+        }
+        Ok(())
+    }
+
+    pub fn score(&self) -> i64 {
+        self.score
+    }
+
+    pub fn tile(&self, x: i64, y: i64) -> IntcodeTile {
+        self.tiles.get((x, y)).copied().unwrap_or(IntcodeTile::Empty)
+    }
+
+    pub fn count(&self, tile: IntcodeTile) -> usize {
+        self.tiles.iter().filter(|(_, drawn)| **drawn == tile).count()
+    }
+
+    pub fn find(&self, tile: IntcodeTile) -> Option<(i64, i64)> {
+        self.tiles.iter().find(|(_, drawn)| **drawn == tile).map(|(point, _)| *point)
+    }
+
+    // The screen always starts at the origin, whatever the top left tile drawn so far is
+    fn size(&self) -> (i64, i64) {
+        match self.tiles.bounds() {
+            Some((_, (max_x, max_y))) => (max_x + 1, max_y + 1),
+            None => (0, 0),
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let (width, height) = self.size();
+        let mut text = format!("Score: {}\n", self.score);
+        for y in 0..height {
+            text.extend((0..width).map(|x| self.tile(x, y).symbol()));
+            text.push('\n');
+        }
+
+        text
+    }
+
+    // Binary PPM with a square of `scale` pixels per tile
+    pub fn ppm(&self, scale: usize) -> Vec<u8> {
+        let (width, height) = self.size();
+        let mut image = format!("P6\n{} {}\n255\n", width as usize * scale, height as usize * scale).into_bytes();
+        for y in 0..height {
+            for _ in 0..scale {
+                for x in 0..width {
+                    for _ in 0..scale {
+                        image.extend_from_slice(&self.tile(x, y).color());
+                    }
+                }
+            }
+        }
+
+        image
+    }
+}
+
+// Moves the paddle towards the ball
+pub fn autopilot(screen: &IntcodeArcadeScreen) -> Option<i64> {
+    let ball = screen.find(IntcodeTile::Ball)?;
+    let paddle = screen.find(IntcodeTile::Paddle)?;
+    Some((ball.0 - paddle.0).signum())
+}
+
+// `a` or `d` followed by enter moves left or right, `q` quits and anything else keeps still
+pub fn keyboard(_: &IntcodeArcadeScreen) -> Option<i64> {
+    match input::read_input_with_prompt("Joystick (a/d/q): ").ok()?.trim() {
+        "q" => None,
+        "a" => Some(-1),
+        "d" => Some(1),
+        _ => Some(0),
+    }
+}
+
+pub struct IntcodeArcade {
+    program: Vec<i64>,
+    free_play: bool,
+    display: bool,
+    frames: Option<PathBuf>,
+    scale: usize,
+}
+
+impl IntcodeArcade {
+    pub fn new(program: &[i64]) -> Self {
+        Self { program: program.to_vec(), free_play: false, display: false, frames: None, scale: 4 }
+    }
+
+    // Writes 2 to address 0 so the game doesn't need quarters
+    pub fn free_play(mut self) -> Self {
+        self.free_play = true;
+        self
+    }
+
+    // Clears the terminal and prints the screen every time the game waits for the joystick
+    pub fn display(mut self, display: bool) -> Self {
+        self.display = display;
+        self
+    }
+
+    // Saves each of those frames, and the final screen, as frame_NNNNN.ppm in the directory
+    pub fn save_frames(mut self, directory: PathBuf, scale: usize) -> Self {
+        self.frames = Some(directory);
+        self.scale = scale;
+        self
+    }
+
+    // Plays until the game halts or the joystick returns None
+    pub fn run<F>(&self, mut joystick: F) -> Result<IntcodeArcadeScreen>
+    where F: FnMut(&IntcodeArcadeScreen) -> Option<i64>
+    {
+        let mut machine = IntcodeMachine::new_blocking_machine(&self.program);
+        if self.free_play {
+            IntcodePatchSet::new().patch(0, &[2]).apply_to(&mut machine)?;
+        }
+        if let Some(directory) = &self.frames {
+            fs::create_dir_all(directory)?;
+        }
+
+        let mut screen = IntcodeArcadeScreen::new();
+        let mut consumed = 0;
+        let mut frame = 0;
+        loop {
+            machine.try_run()?;
+            let outputs = machine.output_handler().history()[consumed..]
+                .iter()
+                .map(|output| output.parse::<i64>())
+                .collect::<Result<Vec<i64>, _>>()?;
+            for triple in outputs.chunks_exact(3) {
+                screen.draw(triple[0], triple[1], triple[2])?;
+                consumed += 3;
+            }
+
+            if self.display {
+                print!("\x1b[2J\x1b[H{}", screen.render());
+            }
+            if let Some(directory) = &self.frames {
+                fs::write(directory.join(format!("frame_{:05}.ppm", frame)), screen.ppm(self.scale))?;
+            }
+            frame += 1;
+
+            match machine.state() {
+                IntcodeState::Halted => return Ok(screen),
+                IntcodeState::Suspended => match joystick(&screen) {
+                    Some(direction) => machine.input(direction),
+                    None => return Ok(screen),
+                },
+                state => bail!("Arcade program stopped unexpectedly: {:?}", state),
+            }
+        }
+    }
+}
+
+// Usage: arcade <file in input/> [--free-play] [--autopilot] [--frames <directory>]
+pub fn run_command(args: &[String]) -> Result<String> {
+    let program = IntcodeProgram::load_from_args(args)?.code;
+
+    let mut arcade = IntcodeArcade::new(&program);
+    let mut automatic = false;
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--free-play" => arcade = arcade.free_play(),
+            "--autopilot" => automatic = true,
+            "--frames" => {
+                let directory = options.next().ok_or(anyhow!("--frames needs a directory"))?;
+                arcade = arcade.save_frames(PathBuf::from(directory), 4);
+            },
+            _ => bail!("Unknown option: {}", option),
+        }
+    }
+
+    let arcade = arcade.display(!automatic);
+    let screen = if automatic { arcade.run(autopilot)? } else { arcade.run(keyboard)? };
+    Ok(format!("{}Blocks left: {}", screen.render(), screen.count(IntcodeTile::Block)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Draws a wall, a block, the paddle at x = 1 and the ball at x = 3, then shows the joystick
+    // position as the score until it reads 0
+    fn game() -> Vec<i64> {
+        vec![
+            104,0,104,0,104,1,
+            104,1,104,0,104,2,
+            104,1,104,1,104,3,
+            104,3,104,1,104,4,
+            3,100,104,-1,104,0,4,100,1005,100,24,99,
+        ]
+    }
+
+    #[test]
+    fn test_screen() {
+        let mut screen = IntcodeArcadeScreen::new();
+        for triple in game()[..24].chunks(6) {
+            screen.draw(triple[1], triple[3], triple[5]).unwrap();
+        }
+        screen.draw(-1, 0, 42).unwrap();
+
+        assert_eq!(screen.render(), "Score: 42\n#+  \n = o\n");
+        assert_eq!(screen.count(IntcodeTile::Block), 1);
+        assert_eq!(screen.find(IntcodeTile::Ball), Some((3, 1)));
+        assert!(screen.draw(0, 0, 5).is_err());
+
+        let image = screen.ppm(2);
+        let header = b"P6\n8 4\n255\n";
+        assert_eq!(&image[..header.len()], header);
+        assert_eq!(image.len(), header.len() + 8 * 4 * 3);
+        assert_eq!(image[header.len()..header.len() + 3], [128, 128, 128]);
+    }
+
+    #[test]
+    fn test_autopilot() {
+        let mut joystick = Vec::new();
+        let screen = IntcodeArcade::new(&game()).run(|screen| {
+            let direction = autopilot(screen);
+            joystick.push(direction);
+            // Stops the game by keeping still on the second frame
+            if joystick.len() > 1 { Some(0) } else { direction }
+        }).unwrap();
+
+        assert_eq!(joystick, vec![Some(1), Some(1)]);
+        assert_eq!(screen.score(), 0);
+    }
+
+    #[test]
+    fn test_quitting() {
+        let screen = IntcodeArcade::new(&game()).run(|_| None).unwrap();
+        assert_eq!(screen.count(IntcodeTile::Paddle), 1);
+        assert_eq!(screen.score(), 0);
+    }
+
+    #[test]
+    fn test_free_play_and_frames() {
+        // Shows the score 3 * 3 in free play and 3 + 3 otherwise
+        let program = vec![1,12,12,13,104,-1,104,0,4,13,99,0,3,0];
+        let directory = std::env::temp_dir().join("intcode_arcade_frames_test");
+        let _ = fs::remove_dir_all(&directory);
+
+        let screen = IntcodeArcade::new(&program).free_play().save_frames(directory.clone(), 1).run(|_| None).unwrap();
+        assert_eq!(screen.score(), 9);
+        assert_eq!(IntcodeArcade::new(&program).run(|_| None).unwrap().score(), 6);
+
+        let frame = fs::read(directory.join("frame_00000.ppm")).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(frame, b"P6\n0 0\n255\n");
+    }
+}
@@ -0,0 +1,161 @@
+use anyhow::{bail, Result};
+
+use std::convert::TryFrom;
+
+use crate::intcode::{IntcodeBlockingInput, IntcodeHistoryOutput, IntcodeMachine, IntcodeOutput, IntcodeSnapshot, IntcodeState};
+
+pub fn encode(text: &str) -> Vec<i64> {
+    text.chars().map(|c| c as i64).collect()
+}
+
+// Text made of the outputs that are ASCII, followed by the first one that isn't (usually the answer)
+pub fn decode(outputs: &[i64]) -> (String, Option<i64>) {
+    let mut text = String::new();
+    for output in outputs {
+        match u8::try_from(*output) {
+            Ok(byte) if byte.is_ascii() => text.push(byte as char),
+            _ => return (text, Some(*output)),
+        }
+    }
+
+    (text, None)
+}
+
+// Drives a program that talks in lines of ASCII text
+pub struct IntcodeAsciiMachine {
+    machine: IntcodeMachine<IntcodeBlockingInput, IntcodeHistoryOutput>,
+    consumed: usize,
+    value: Option<i64>,
+    max_steps: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntcodeAsciiSnapshot {
+    machine: IntcodeSnapshot,
+    value: Option<i64>,
+}
+
+impl IntcodeAsciiMachine {
+    pub fn new(program: &[i64]) -> Self {
+        Self { machine: IntcodeMachine::new_blocking_machine(program), consumed: 0, value: None, max_steps: None }
+    }
+
+    // Fails a run or an input character that takes longer than this, e.g. a program stuck in a loop
+    pub fn with_step_limit(mut self, max_steps: usize) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    // Runs until the program wants input or halts, returning the text it printed meanwhile
+    pub fn run(&mut self) -> Result<String> {
+        if self.machine.state() != &IntcodeState::Halted {
+            self.resume()?;
+        }
+        Ok(self.take_output())
+    }
+
+    pub fn send_line(&mut self, line: &str) -> Result<String> {
+        let mut text = String::new();
+        for code in encode(line).into_iter().chain(std::iter::once(10)) {
+            if self.machine.state() != &IntcodeState::Suspended {
+                bail!("Program stopped reading before the end of '{}'", line);
+            }
+            self.machine.input(code);
+            self.resume()?;
+            text.push_str(&self.take_output());
+        }
+
+        Ok(text)
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.machine.state() == &IntcodeState::Halted
+    }
+
+    // The first output that wasn't ASCII, if there was one
+    pub fn value(&self) -> Option<i64> {
+        self.value
+    }
+
+    // Output already read isn't replayed after restoring
+    pub fn snapshot(&self) -> IntcodeAsciiSnapshot {
+        IntcodeAsciiSnapshot { machine: self.machine.snapshot(), value: self.value }
+    }
+
+    pub fn restore(&mut self, snapshot: &IntcodeAsciiSnapshot) {
+        self.machine.restore(&snapshot.machine);
+        self.value = snapshot.value;
+        self.consumed = self.machine.output_handler().history().len();
+    }
+
+    fn resume(&mut self) -> Result<()> {
+        let max_steps = match self.max_steps {
+            Some(max_steps) => max_steps,
+            None => return Ok(self.machine.try_run()?),
+        };
+        for _ in 0..max_steps {
+            self.machine.step()?;
+            if self.machine.state() != &IntcodeState::Running {
+                return Ok(());
+            }
+        }
+        bail!("Program didn't stop within {} steps", max_steps)
+    }
+
+    // Values that aren't ASCII are left out of the text
+    fn take_output(&mut self) -> String {
+        let history = &self.machine.output_handler().history()[self.consumed..];
+        self.consumed += history.len();
+
+        let mut text = String::new();
+        for output in history.iter().filter_map(|output| output.parse::<i64>().ok()) {
+            match u8::try_from(output) {
+                Ok(byte) if byte.is_ascii() => text.push(byte as char),
+                _ => {
+                    self.value.get_or_insert(output);
+                },
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        assert_eq!(encode("NOT A J\n"), vec![78,79,84,32,65,32,74,10]);
+        assert_eq!(decode(&[72,105,10]), (String::from("Hi\n"), None));
+        assert_eq!(decode(&[72,105,19358688,10]), (String::from("Hi"), Some(19358688)));
+    }
+
+    #[test]
+    fn test_echo_line() {
+        // Echoes characters up to a newline, then outputs 1000
+        let program = vec![3,100,4,100,1008,100,10,101,1006,101,0,104,1000,99];
+        let mut machine = IntcodeAsciiMachine::new(&program);
+
+        assert_eq!(machine.run().unwrap(), "");
+        assert_eq!(machine.send_line("hi").unwrap(), "hi\n");
+        assert_eq!(machine.value(), Some(1000));
+        assert!(machine.is_halted());
+        assert!(machine.send_line("again").is_err());
+    }
+
+    #[test]
+    fn test_snapshot_and_step_limit() {
+        // Echoes a character, or loops forever on 'x'
+        let program = vec![3,100,4,100,1008,100,120,101,1005,101,8,1105,1,0];
+        let mut machine = IntcodeAsciiMachine::new(&program).with_step_limit(1000);
+        machine.run().unwrap();
+        let snapshot = machine.snapshot();
+
+        assert_eq!(machine.send_line("ab").unwrap(), "ab\n");
+        assert_eq!(machine.send_line("x").unwrap_err().to_string(), "Program didn't stop within 1000 steps");
+
+        machine.restore(&snapshot);
+        assert_eq!(machine.send_line("c").unwrap(), "c\n");
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::{anyhow, bail, Result};
+
+use crate::intcode::{IntcodeInstruction, IntcodeExtensions};
+
+// (mnemonic, opcode, read parameters, has a written parameter)
+const BUILTINS: [(&str, i64, usize, bool); 10] = [
+    ("Add", 1, 2, true),
+    ("Mul", 2, 2, true),
+    ("Inp", 3, 0, true),
+    ("Out", 4, 1, false),
+    ("JmT", 5, 2, false),
+    ("JmF", 6, 2, false),
+    ("Lst", 7, 2, true),
+    ("Eqt", 8, 2, true),
+    ("Srb", 9, 1, false),
+    ("Halt", 99, 0, false),
+];
+
+// Names the assembler reads before looking at extensions, so no extension can use them
+pub fn is_reserved_mnemonic(mnemonic: &str) -> bool {
+    mnemonic.eq_ignore_ascii_case("data") || BUILTINS.iter().any(|(name, ..)| name.eq_ignore_ascii_case(mnemonic))
+}
+
+pub fn format_instruction(instruction: &IntcodeInstruction, extensions: &IntcodeExtensions) -> String {
+    match instruction {
+        IntcodeInstruction::Extension{opcode, ..} => {
+            let text = format!("{:?}", instruction);
+            match extensions.get(*opcode) {
+                Some(extension) => text.replacen(&format!("Op{}", opcode), extension.mnemonic(), 1),
+                None => text,
+            }
+        },
+        _ => format!("{:?}", instruction),
+    }
+}
+
+// Linear sweep; words that don't decode are emitted as data so the listing reassembles to the same program
+pub fn disassemble(program: &[i64], extensions: &IntcodeExtensions) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut position = 0;
+    while position < program.len() {
+        match IntcodeInstruction::try_decode(program[position], &program[position+1..], extensions) {
+            Ok(instruction) => {
+                lines.push(format_instruction(&instruction, extensions));
+                position += instruction.size();
+            },
+            Err(_) => {
+                lines.push(format!("data {}", program[position]));
+                position += 1;
+            },
+        }
+    }
+
+    lines
+}
+
+pub fn assemble(source: &str, extensions: &IntcodeExtensions) -> Result<Vec<i64>> {
+    let mut program = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let line = line.split(';').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        assemble_line(line, extensions, &mut program)
+            .map_err(|error| anyhow!("Line {}: {}", number + 1, error))?;
+    }
+
+    Ok(program)
+}
+
+fn assemble_line(line: &str, extensions: &IntcodeExtensions, program: &mut Vec<i64>) -> Result<()> {
+    let mut tokens = line.split_whitespace();
+    let mnemonic = tokens.next().unwrap_or("");
+    let operands: Vec<&str> = tokens.collect();
+
+    if mnemonic.eq_ignore_ascii_case("data") {
+        for operand in operands {
+            program.push(operand.parse().map_err(|_| anyhow!("Invalid data word: {}", operand))?);
+        }
+        return Ok(());
+    }
+
+    let (opcode, reads, writes) = match BUILTINS.iter().find(|(name, ..)| name.eq_ignore_ascii_case(mnemonic)) {
+        Some((_, opcode, reads, writes)) => (*opcode, *reads, *writes),
+        None => match extensions.by_mnemonic(mnemonic) {
+            Some(extension) => (extension.opcode(), extension.reads(), extension.writes()),
+            None => bail!("Unknown mnemonic: {}", mnemonic),
+        },
+    };
+
+    let expected = reads + writes as usize;
+    if operands.len() != expected {
+        bail!("{} takes {} operands, got {}", mnemonic, expected, operands.len());
+    }
+
+    let mut instruction = opcode;
+    let mut params = Vec::new();
+    for (index, operand) in operands.iter().enumerate() {
+        let (mode, value) = parse_operand(operand, index >= reads)?;
+        instruction += mode * 10i64.pow(index as u32 + 2);
+        params.push(value);
+    }
+
+    program.push(instruction);
+    program.extend(params);
+    Ok(())
+}
+
+fn parse_operand(operand: &str, is_write: bool) -> Result<(i64, i64)> {
+    let (digits, mode) = match operand.chars().last() {
+        Some('p') => (&operand[..operand.len() - 1], 0),
+        Some('i') => (&operand[..operand.len() - 1], 1),
+        Some('r') => (&operand[..operand.len() - 1], 2),
+        _ if is_write => (operand, 0),
+        _ => bail!("Operand {} needs a mode suffix (p, i or r)", operand),
+    };
+    if is_write && mode == 1 {
+        bail!("Written operand {} can't be immediate", operand);
+    }
+
+    let value = digits.parse().map_err(|_| anyhow!("Invalid operand: {}", operand))?;
+    Ok((mode, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::IntcodeExtension;
+
+    #[test]
+    fn test_disassemble() {
+        let program = vec![1002,4,3,4,33,99];
+        assert_eq!(disassemble(&program, &IntcodeExtensions::new()), vec!["Mul 4p 3i 4p", "data 33", "Halt"]);
+    }
+
+    #[test]
+    fn test_assemble() {
+        let source = "inp 13 ; read\nADD 13p 5i 13\nout 13p\nsrb 3i\ninp 6r\nhalt\ndata 0";
+        assert_eq!(assemble(source, &IntcodeExtensions::new()).unwrap(), vec![3,13,1001,13,5,13,4,13,109,3,203,6,99,0]);
+    }
+
+    #[test]
+    fn test_extension_mnemonics_round_trip() {
+        let mut extensions = IntcodeExtensions::new();
+        extensions.register(IntcodeExtension::divide(10)).unwrap();
+        let program = vec![1110,17,5,20,4,20,99];
+
+        let listing = disassemble(&program, &extensions);
+        assert_eq!(listing[0], "Div 17i 5i 20p");
+        assert_eq!(assemble(&listing.join("\n"), &extensions).unwrap(), program);
+    }
+
+    #[test]
+    fn test_assemble_errors_report_line() {
+        let error = assemble("halt\nfoo 1p", &IntcodeExtensions::new()).unwrap_err();
+        assert_eq!(error.to_string(), "Line 2: Unknown mnemonic: foo");
+        assert!(assemble("add 1p 2i 3i", &IntcodeExtensions::new()).is_err());
+    }
+}
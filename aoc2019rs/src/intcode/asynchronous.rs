@@ -0,0 +1,322 @@
+use anyhow::{bail, Result};
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll, Wake, Waker};
+
+use crate::intcode::{IntcodeMachine, IntcodeInput, IntcodeOutput, IntcodeState, IntcodeFault};
+
+// Instructions a machine runs before yielding to the other tasks
+const TIME_SLICE: usize = 1000;
+
+struct ChannelState {
+    values: VecDeque<i64>,
+    wakers: Vec<Waker>,
+}
+
+// Unbounded single-threaded queue of values. A machine reads its inputs from one channel and
+// writes its outputs to another; clones share the queue.
+#[derive(Clone)]
+pub struct IntcodeChannel {
+    state: Rc<RefCell<ChannelState>>,
+    history: Vec<String>,
+}
+
+impl IntcodeChannel {
+    pub fn new() -> Self {
+        Self {
+            state: Rc::new(RefCell::new(ChannelState { values: VecDeque::new(), wakers: Vec::new() })),
+            history: Vec::new(),
+        }
+    }
+
+    pub fn send(&self, value: i64) {
+        let mut state = self.state.borrow_mut();
+        state.values.push_back(value);
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+
+    pub fn try_recv(&self) -> Option<i64> {
+        self.state.borrow_mut().values.pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.borrow().values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn ready(&self) -> ChannelReady {
+        ChannelReady { state: self.state.clone() }
+    }
+}
+
+impl IntcodeInput for IntcodeChannel {
+    fn process(&mut self) -> Option<i64> {
+        self.try_recv()
+    }
+}
+
+impl IntcodeOutput for IntcodeChannel {
+    fn process(&mut self, value: i64) {
+        self.send(value);
+        self.history.push(format!("{}", value));
+    }
+
+    fn history(&self) -> &[String] {
+        &self.history
+    }
+}
+
+pub struct ChannelReady {
+    state: Rc<RefCell<ChannelState>>,
+}
+
+impl Future for ChannelReady {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.state.borrow_mut();
+        if state.values.is_empty() {
+            state.wakers.push(context.waker().clone());
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    }
+}
+
+struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            Poll::Ready(())
+        } else {
+            self.yielded = true;
+            context.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+// Runs like IntcodeMachine::try_run, except that waiting for input awaits the input channel
+// instead of returning with the machine suspended
+pub struct IntcodeAsyncMachine {
+    machine: IntcodeMachine<IntcodeChannel, IntcodeChannel>,
+}
+
+impl IntcodeAsyncMachine {
+    pub fn new(machine_code: &[i64], input: IntcodeChannel, output: IntcodeChannel) -> Self {
+        Self { machine: IntcodeMachine::new(machine_code, input, output) }
+    }
+
+    pub fn machine(&self) -> &IntcodeMachine<IntcodeChannel, IntcodeChannel> {
+        &self.machine
+    }
+
+    pub async fn run(&mut self) -> Result<(), IntcodeFault> {
+        let mut slice = 0;
+        loop {
+            self.machine.step()?;
+            match self.machine.state() {
+                IntcodeState::Halted => return Ok(()),
+                IntcodeState::Suspended => self.machine.input_handler().ready().await,
+                _ => {
+                    slice += 1;
+                    if slice == TIME_SLICE {
+                        slice = 0;
+                        YieldNow { yielded: false }.await;
+                    }
+                },
+            }
+        }
+    }
+}
+
+struct TaskWaker {
+    woken: AtomicBool,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.woken.store(true, Ordering::SeqCst);
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::SeqCst);
+    }
+}
+
+struct Task {
+    future: Pin<Box<dyn Future<Output = ()>>>,
+    waker: Arc<TaskWaker>,
+}
+
+// Polls woken tasks round-robin on the current thread until they all finish
+pub struct IntcodeExecutor {
+    tasks: Vec<Task>,
+}
+
+impl IntcodeExecutor {
+    pub fn new() -> Self {
+        Self { tasks: Vec::new() }
+    }
+
+    pub fn spawn<F>(&mut self, future: F)
+    where F: Future<Output = ()> + 'static
+    {
+        self.tasks.push(Task {
+            future: Box::pin(future),
+            waker: Arc::new(TaskWaker { woken: AtomicBool::new(true) }),
+        });
+    }
+
+    // Fails if every remaining task is waiting on a channel nobody will send to
+    pub fn run(&mut self) -> Result<()> {
+        while !self.tasks.is_empty() {
+            let mut progressed = false;
+            let mut index = 0;
+            while index < self.tasks.len() {
+                let task = &mut self.tasks[index];
+                if !task.waker.woken.swap(false, Ordering::SeqCst) {
+                    index += 1;
+                    continue;
+                }
+
+                progressed = true;
+                let waker = Waker::from(task.waker.clone());
+                let mut context = Context::from_waker(&waker);
+                match task.future.as_mut().poll(&mut context) {
+                    Poll::Ready(()) => {
+                        self.tasks.remove(index);
+                    },
+                    Poll::Pending => index += 1,
+                }
+            }
+
+            if !progressed {
+                bail!("Deadlock: {} tasks are waiting for input", self.tasks.len());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amplifier_ring(program: &[i64], phases: &[i64]) -> i64 {
+        let channels: Vec<IntcodeChannel> = phases.iter()
+            .map(|phase| {
+                let channel = IntcodeChannel::new();
+                channel.send(*phase);
+                channel
+            })
+            .collect();
+        channels[0].send(0);
+
+        let mut executor = IntcodeExecutor::new();
+        for i in 0..channels.len() {
+            let mut amplifier = IntcodeAsyncMachine::new(program, channels[i].clone(), channels[(i + 1) % channels.len()].clone());
+            executor.spawn(async move {
+                amplifier.run().await.unwrap();
+            });
+        }
+        executor.run().unwrap();
+
+        channels[0].try_recv().unwrap()
+    }
+
+    #[test]
+    fn test_amplifier_feedback_loop() {
+        let program = vec![3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5];
+        assert_eq!(amplifier_ring(&program, &[9,8,7,6,5]), 139629729);
+    }
+
+    #[test]
+    fn test_many_machines() {
+        // A chain of 200 machines that each add one to their input
+        let program = vec![3,9,1001,9,1,9,4,9,99,0];
+        let channels: Vec<IntcodeChannel> = (0..201).map(|_| IntcodeChannel::new()).collect();
+        let mut executor = IntcodeExecutor::new();
+        for i in (0..200).rev() {
+            let mut machine = IntcodeAsyncMachine::new(&program, channels[i].clone(), channels[i + 1].clone());
+            executor.spawn(async move {
+                machine.run().await.unwrap();
+            });
+        }
+        channels[0].send(0);
+        executor.run().unwrap();
+
+        assert_eq!(channels[200].try_recv(), Some(200));
+    }
+
+    #[test]
+    fn test_deadlock_is_detected() {
+        let mut machine = IntcodeAsyncMachine::new(&[3,0,99], IntcodeChannel::new(), IntcodeChannel::new());
+        let mut executor = IntcodeExecutor::new();
+        executor.spawn(async move {
+            machine.run().await.unwrap();
+        });
+
+        assert!(executor.run().is_err());
+    }
+
+    // Logs which task is polled before passing the poll on
+    struct Recorded {
+        id: usize,
+        polls: Rc<RefCell<Vec<usize>>>,
+        future: Pin<Box<dyn Future<Output = ()>>>,
+    }
+
+    impl Future for Recorded {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<()> {
+            self.polls.borrow_mut().push(self.id);
+            self.future.as_mut().poll(context)
+        }
+    }
+
+    #[test]
+    fn test_long_running_machines_yield() {
+        // Counts down from 5000 before outputting, so both machines need several time slices
+        let program = vec![1101,5000,0,20,1001,20,-1,20,1005,20,4,104,7,99];
+        let outputs: Vec<IntcodeChannel> = (0..2).map(|_| IntcodeChannel::new()).collect();
+        let polls = Rc::new(RefCell::new(Vec::new()));
+        let mut executor = IntcodeExecutor::new();
+        for (id, output) in outputs.iter().enumerate() {
+            let mut machine = IntcodeAsyncMachine::new(&program, IntcodeChannel::new(), output.clone());
+            executor.spawn(Recorded {
+                id,
+                polls: polls.clone(),
+                future: Box::pin(async move {
+                    machine.run().await.unwrap();
+                }),
+            });
+        }
+        executor.run().unwrap();
+
+        assert!(outputs.iter().all(|output| output.try_recv() == Some(7)));
+        let polls = polls.borrow();
+        // About 10000 instructions each, so at least ten time slices apiece
+        assert!(polls.len() >= 20);
+        assert!(polls.iter().enumerate().all(|(index, id)| *id == index % 2));
+    }
+}
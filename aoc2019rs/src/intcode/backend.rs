@@ -0,0 +1,51 @@
+use super::{IntcodeMachine, IntcodeInput, IntcodeOutput, IntcodeState, IntcodeInstruction, IntcodeFault};
+
+pub trait IntcodeBackend {
+    fn name(&self) -> &'static str;
+    fn step(&mut self) -> Result<(), IntcodeFault>;
+    fn state(&self) -> &IntcodeState;
+    fn instruction_pointer(&self) -> usize;
+    fn instruction_count(&self) -> usize;
+    fn memory(&self) -> &[i64];
+    fn outputs(&self) -> &[String];
+
+    fn next_instruction(&self) -> Option<IntcodeInstruction> {
+        let memory = self.memory();
+        let ptr = self.instruction_pointer();
+        let opcode = *memory.get(ptr)?;
+        IntcodeInstruction::try_new(opcode, &memory[ptr+1..]).ok()
+    }
+}
+
+impl<I, O> IntcodeBackend for IntcodeMachine<I, O>
+where I: IntcodeInput,
+      O: IntcodeOutput,
+{
+    fn name(&self) -> &'static str {
+        "interpreter"
+    }
+
+    fn step(&mut self) -> Result<(), IntcodeFault> {
+        IntcodeMachine::step(self)
+    }
+
+    fn state(&self) -> &IntcodeState {
+        IntcodeMachine::state(self)
+    }
+
+    fn instruction_pointer(&self) -> usize {
+        IntcodeMachine::instruction_pointer(self)
+    }
+
+    fn instruction_count(&self) -> usize {
+        IntcodeMachine::instruction_count(self)
+    }
+
+    fn memory(&self) -> &[i64] {
+        IntcodeMachine::memory(self)
+    }
+
+    fn outputs(&self) -> &[String] {
+        self.output_handler().history()
+    }
+}
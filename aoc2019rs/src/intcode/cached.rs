@@ -0,0 +1,183 @@
+use super::{IntcodeMachine, IntcodeInput, IntcodeOutput, IntcodeState, IntcodeInstruction, IntcodeBackend, IntcodeFault};
+use super::{IntcodeSnapshot, IntcodePresetInput, IntcodeHistoryOutput};
+
+const MAX_INSTRUCTION_SIZE: usize = 4;
+
+// Decodes each address once and reuses the decoded instruction until something writes over it.
+pub struct IntcodeCachedMachine<I, O> {
+    machine: IntcodeMachine<I, O>,
+    cache: Vec<Option<IntcodeInstruction>>,
+}
+
+impl<I, O> IntcodeCachedMachine<I, O>
+where I: IntcodeInput,
+      O: IntcodeOutput,
+{
+    pub fn new(machine_code: &[i64], input_handler: I, output_handler: O) -> Self {
+        let machine = IntcodeMachine::new(machine_code, input_handler, output_handler);
+        let cache = vec![None; machine.memory().len()];
+
+        Self { machine, cache }
+    }
+
+    pub fn run(&mut self) {
+        if let Err(fault) = self.try_run() {
+            panic!("{}", fault);
+        }
+    }
+
+    pub fn try_run(&mut self) -> Result<(), IntcodeFault> {
+        self.step()?;
+        while self.machine.state() == &IntcodeState::Running {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    pub fn step(&mut self) -> Result<(), IntcodeFault> {
+        let ptr = self.machine.instruction_pointer();
+        self.grow_cache();
+        let instruction = match self.cache.get(ptr) {
+            Some(Some(instruction)) => instruction.clone(),
+            _ => match self.machine.try_decode_instruction(ptr) {
+                Ok(instruction) => {
+                    self.cache[ptr] = Some(instruction.clone());
+                    instruction
+                },
+                Err(fault) => return self.machine.raise_fault(fault),
+            },
+        };
+
+        if let Some(position) = instruction.written_position(self.machine.relative_base()) {
+            self.invalidate(position.max(0) as usize);
+        }
+
+        self.machine.execute(instruction)
+    }
+
+    pub fn snapshot(&self) -> IntcodeSnapshot {
+        self.machine.snapshot()
+    }
+
+    pub fn restore(&mut self, snapshot: &IntcodeSnapshot) {
+        self.machine.restore(snapshot);
+        self.cache = vec![None; self.machine.memory().len()];
+    }
+
+    pub fn write_memory(&mut self, position: usize, value: i64) {
+        self.invalidate(position);
+        self.machine.write_memory(position, value);
+    }
+
+    pub fn input(&mut self, value: i64) {
+        let instruction = self.machine.decode_instruction(self.machine.instruction_pointer());
+        if let Some(position) = instruction.written_position(self.machine.relative_base()) {
+            self.invalidate(position.max(0) as usize);
+        }
+        self.machine.input(value);
+    }
+
+    pub fn machine(&self) -> &IntcodeMachine<I, O> {
+        &self.machine
+    }
+
+    pub fn cached_instruction_count(&self) -> usize {
+        self.cache.iter().filter(|entry| entry.is_some()).count()
+    }
+
+    // Memory grows when the program writes past the end, and the cache has to follow it
+    fn grow_cache(&mut self) {
+        let size = self.machine.memory().len();
+        if self.cache.len() < size {
+            self.cache.resize(size, None);
+        }
+    }
+
+    fn invalidate(&mut self, position: usize) {
+        let end = position.saturating_add(1).min(self.cache.len());
+        let start = position.saturating_sub(MAX_INSTRUCTION_SIZE - 1).min(end);
+        for entry in &mut self.cache[start..end] {
+            *entry = None;
+        }
+    }
+}
+
+impl<I, O> IntcodeBackend for IntcodeCachedMachine<I, O>
+where I: IntcodeInput,
+      O: IntcodeOutput,
+{
+    fn name(&self) -> &'static str {
+        "decoded cache"
+    }
+
+    fn step(&mut self) -> Result<(), IntcodeFault> {
+        IntcodeCachedMachine::step(self)
+    }
+
+    fn state(&self) -> &IntcodeState {
+        self.machine.state()
+    }
+
+    fn instruction_pointer(&self) -> usize {
+        self.machine.instruction_pointer()
+    }
+
+    fn instruction_count(&self) -> usize {
+        self.machine.instruction_count()
+    }
+
+    fn memory(&self) -> &[i64] {
+        self.machine.memory()
+    }
+
+    fn outputs(&self) -> &[String] {
+        self.machine.output_handler().history()
+    }
+}
+
+impl IntcodeCachedMachine<IntcodePresetInput, IntcodeHistoryOutput> {
+    pub fn new_automated_machine(machine_code: &[i64], inputs: &[i64]) -> IntcodeCachedMachine<IntcodePresetInput, IntcodeHistoryOutput> {
+        IntcodeCachedMachine::new(machine_code, IntcodePresetInput::new(inputs), IntcodeHistoryOutput::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_matches_interpreter() {
+        let program = vec![1,1,1,4,99,5,6,0,99];
+        let mut machine = IntcodeCachedMachine::new_automated_machine(&program, &[]);
+        machine.run();
+        assert_eq!(&machine.machine().memory()[0..program.len()], &[30,1,1,4,2,5,6,0,99]);
+    }
+
+    #[test]
+    fn test_self_modifying_invalidates_cache() {
+        let program = vec![1002,4,3,4,33];
+        let mut machine = IntcodeCachedMachine::new_automated_machine(&program, &[]);
+        machine.run();
+        assert_eq!(&machine.machine().memory()[0..program.len()], &[1002,4,3,4,99]);
+        assert_eq!(machine.machine().state(), &IntcodeState::Halted);
+    }
+
+    #[test]
+    fn test_cache_fills_on_execution() {
+        let program = vec![3,9,8,9,10,9,4,9,99,-1,8];
+        let mut machine = IntcodeCachedMachine::new_automated_machine(&program, &[8]);
+        machine.run();
+        assert_eq!(machine.machine().output_handler().last_output().unwrap(), "1");
+        assert_eq!(machine.cached_instruction_count(), 4);
+    }
+
+    #[test]
+    fn test_runs_code_written_past_initial_memory() {
+        // Writes a halt at 2000 and jumps to it
+        let program = vec![1101,99,0,2000,1105,1,2000];
+        let mut machine = IntcodeCachedMachine::new_automated_machine(&program, &[]);
+        machine.run();
+        assert_eq!(machine.machine().state(), &IntcodeState::Halted);
+        assert_eq!(machine.machine().instruction_pointer(), 2000);
+    }
+}
@@ -0,0 +1,690 @@
+use anyhow::Result;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::intcode::{IntcodeInstruction, IntcodeValue, IntcodeExtensions, IntcodeProgram};
+
+// How an instruction leaves the straight-line flow
+#[derive(Clone, Debug, PartialEq)]
+enum Control {
+    Next,
+    Halt,
+    Goto(usize),
+    Call(usize),
+    Return,
+    Indirect(IntcodeValue),
+    Branch{test: IntcodeValue, if_zero: bool, target: usize},
+    IndirectBranch{test: IntcodeValue, if_zero: bool, target: IntcodeValue},
+}
+
+// The compiled calling convention stores the return address in a relative slot and then jumps:
+//   Add 0i <return>i <slot>r
+//   JmT 1i <function>i
+// and a function returns by jumping to the slot, e.g. JmF 0i 0r.
+fn control(pc: usize, instruction: &IntcodeInstruction, previous: Option<&IntcodeInstruction>) -> Control {
+    use IntcodeInstruction::*;
+
+    let (test, target, if_zero) = match instruction {
+        Halt => return Control::Halt,
+        JumpIfTrue{test_position, jump_position} => (test_position, jump_position, false),
+        JumpIfFalse{test_position, jump_position} => (test_position, jump_position, true),
+        _ => return Control::Next,
+    };
+
+    let immediate_target = match target {
+        IntcodeValue::Immediate(target) if *target >= 0 => Some(*target as usize),
+        _ => None,
+    };
+
+    let test = match test {
+        IntcodeValue::Immediate(value) if (*value == 0) != if_zero => return Control::Next,
+        IntcodeValue::Immediate(_) => None,
+        test => Some(test.clone()),
+    };
+
+    match (test, immediate_target) {
+        (Some(test), Some(target)) => Control::Branch{test, if_zero, target},
+        (Some(test), None) => Control::IndirectBranch{test, if_zero, target: target.clone()},
+        (None, Some(target)) => {
+            let return_address = (pc + instruction.size()) as i64;
+            if previous.and_then(pushed_constant) == Some(return_address) {
+                Control::Call(target)
+            } else {
+                Control::Goto(target)
+            }
+        },
+        (None, None) => match target {
+            IntcodeValue::Relative(_) => Control::Return,
+            target => Control::Indirect(target.clone()),
+        },
+    }
+}
+
+fn pushed_constant(instruction: &IntcodeInstruction) -> Option<i64> {
+    use IntcodeInstruction::*;
+    use IntcodeValue::{Immediate, Relative};
+
+    match instruction {
+        Add{x: Immediate(x), y: Immediate(y), position: Relative(_)} => x.checked_add(*y),
+        Multiply{x: Immediate(x), y: Immediate(y), position: Relative(_)} => x.checked_mul(*y),
+        _ => None,
+    }
+}
+
+fn function_name(entry: usize) -> String {
+    match entry {
+        0 => String::from("main"),
+        entry => format!("func_{}", entry),
+    }
+}
+
+fn negate(condition: &str) -> String {
+    if let Some(expression) = condition.strip_suffix(" != 0") {
+        format!("{} == 0", expression)
+    } else if let Some(expression) = condition.strip_suffix(" == 0") {
+        format!("{} != 0", expression)
+    } else {
+        format!("!({})", condition)
+    }
+}
+
+struct Block {
+    start: usize,
+    end: usize,
+    instructions: Vec<(usize, IntcodeInstruction)>,
+    control: Control,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Exit {
+    Fall,
+    Goto(usize),
+    Branch(String, usize),
+    Return,
+    Halt,
+    Indirect(String),
+}
+
+impl Exit {
+    fn target(&self) -> Option<usize> {
+        match self {
+            Exit::Goto(target) | Exit::Branch(_, target) => Some(*target),
+            _ => None,
+        }
+    }
+}
+
+struct RenderedBlock {
+    start: usize,
+    end: usize,
+    statements: Vec<String>,
+    exit: Exit,
+}
+
+// Lifts a program to pseudocode: code is found by following jumps from address 0, split into
+// basic blocks and grouped into functions by the calling convention above. Relative slots are
+// named after their offset from the relative base at function entry, as long as every change to
+// the base is a constant. Control flow that doesn't fit if/else or loops is left as gotos.
+pub fn decompile(program: &[i64], extensions: &IntcodeExtensions) -> Vec<String> {
+    Decompiler::new(program, extensions).render()
+}
+
+// Usage: decompile <file in input/>
+pub fn run_command(args: &[String]) -> Result<String> {
+    let program = IntcodeProgram::load_from_args(args)?;
+    Ok(decompile(&program.code, &IntcodeExtensions::new()).join("\n"))
+}
+
+// The relative base as an offset from its value at function entry, which is known to be zero in main
+#[derive(Clone, Copy)]
+struct Frame {
+    base: Option<i64>,
+    absolute: bool,
+}
+
+struct Decompiler<'a> {
+    extensions: &'a IntcodeExtensions,
+    blocks: BTreeMap<usize, Block>,
+    entries: BTreeSet<usize>,
+}
+
+impl<'a> Decompiler<'a> {
+    fn new(program: &[i64], extensions: &'a IntcodeExtensions) -> Self {
+        let mut instructions = BTreeMap::new();
+        let mut leaders = BTreeSet::from([0]);
+        let mut entries = BTreeSet::from([0]);
+        let mut pending = vec![0];
+
+        while let Some(start) = pending.pop() {
+            let mut pc = start;
+            let mut previous = None;
+            while pc < program.len() && !instructions.contains_key(&pc) {
+                let instruction = match IntcodeInstruction::try_decode(program[pc], &program[pc + 1..], extensions) {
+                    Ok(instruction) => instruction,
+                    Err(_) => break,
+                };
+                let next = pc + instruction.size();
+
+                let stop = match control(pc, &instruction, previous.as_ref()) {
+                    Control::Next | Control::IndirectBranch{..} => false,
+                    Control::Branch{target, ..} => {
+                        leaders.extend([target, next]);
+                        pending.push(target);
+                        false
+                    },
+                    Control::Goto(target) => {
+                        leaders.insert(target);
+                        pending.push(target);
+                        true
+                    },
+                    Control::Call(target) => {
+                        entries.insert(target);
+                        leaders.extend([target, next]);
+                        pending.extend([target, next]);
+                        true
+                    },
+                    Control::Halt | Control::Return | Control::Indirect(_) => true,
+                };
+
+                instructions.insert(pc, instruction.clone());
+                if stop {
+                    break;
+                }
+                previous = Some(instruction);
+                pc = next;
+            }
+        }
+
+        let mut blocks = BTreeMap::new();
+        for &start in leaders.iter().filter(|leader| instructions.contains_key(leader)) {
+            let mut block = Block { start, end: start, instructions: Vec::new(), control: Control::Next };
+            while let Some(instruction) = instructions.get(&block.end) {
+                let previous = block.instructions.last().map(|(_, previous)| previous);
+                block.control = control(block.end, instruction, previous);
+                block.instructions.push((block.end, instruction.clone()));
+                block.end += instruction.size();
+
+                let ends_block = !matches!(block.control, Control::Next | Control::IndirectBranch{..});
+                if ends_block || leaders.contains(&block.end) {
+                    break;
+                }
+            }
+            blocks.insert(start, block);
+        }
+
+        Self { extensions, blocks, entries }
+    }
+
+    fn render(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for &entry in self.entries.iter().filter(|entry| self.blocks.contains_key(entry)) {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.extend(self.render_function(entry));
+        }
+
+        lines
+    }
+
+    fn render_function(&self, entry: usize) -> Vec<String> {
+        let mut blocks = self.function_blocks(entry);
+        for index in 0..blocks.len() {
+            let end = blocks[index].end;
+            if blocks[index].exit == Exit::Fall && blocks.get(index + 1).map(|next| next.start) != Some(end) {
+                if blocks.iter().any(|block| block.start == end) {
+                    blocks[index].exit = Exit::Goto(end);
+                } else {
+                    blocks[index].statements.push(format!("// undecodable instruction at {}", end));
+                }
+            }
+        }
+
+        let mut lines = vec![format!("fn {}() {{", function_name(entry))];
+        if blocks[0].start != entry {
+            lines.push(format!("    goto L{};", entry));
+        }
+        let structurer = Structurer { blocks };
+        let region = Region { from: 0, to: structurer.blocks.len(), follow: None, innermost: None, header_done: false };
+        structurer.structure(region, 1, &mut lines);
+        lines.push(String::from("}"));
+
+        // Only keep the labels something still jumps to
+        let targets: BTreeSet<String> = lines.iter()
+            .filter_map(|line| line.split("goto L").nth(1))
+            .map(|label| String::from(label.trim_end_matches(';')))
+            .collect();
+        lines.retain(|line| match line.trim().strip_prefix('L').and_then(|label| label.strip_suffix(':')) {
+            Some(label) => targets.contains(label),
+            None => true,
+        });
+
+        lines
+    }
+
+    // Blocks reachable from the entry without following calls, in address order
+    fn function_blocks(&self, entry: usize) -> Vec<RenderedBlock> {
+        let mut rendered = BTreeMap::new();
+        let mut pending = vec![(entry, Frame { base: Some(0), absolute: entry == 0 })];
+        while let Some((start, frame)) = pending.pop() {
+            let block = match self.blocks.get(&start) {
+                Some(block) if !rendered.contains_key(&start) => block,
+                _ => continue,
+            };
+
+            let (rendered_block, frame) = self.render_block(block, frame);
+            match block.control {
+                Control::Halt | Control::Return | Control::Indirect(_) => {},
+                Control::Goto(target) => pending.push((target, frame)),
+                Control::Branch{target, ..} => pending.extend([(target, frame), (block.end, frame)]),
+                _ => pending.push((block.end, frame)),
+            }
+            rendered.insert(start, rendered_block);
+        }
+
+        rendered.into_values().collect()
+    }
+
+    fn render_block(&self, block: &Block, mut frame: Frame) -> (RenderedBlock, Frame) {
+        let mut statements = Vec::new();
+        let mut exit = Exit::Fall;
+        let mut previous = None;
+        for (pc, instruction) in &block.instructions {
+            match control(*pc, instruction, previous) {
+                Control::Next => statements.extend(self.statement(instruction, &mut frame)),
+                Control::Halt => exit = Exit::Halt,
+                Control::Goto(target) => exit = Exit::Goto(target),
+                Control::Call(target) => {
+                    // Drop the return address push
+                    statements.pop();
+                    statements.push(format!("{}();", function_name(target)));
+                },
+                Control::Return => exit = Exit::Return,
+                Control::Indirect(target) => exit = Exit::Indirect(self.operand(&target, frame)),
+                Control::Branch{test, if_zero, target} => exit = Exit::Branch(self.condition(&test, if_zero, frame), target),
+                Control::IndirectBranch{test, if_zero, target} => {
+                    statements.push(format!("if ({}) goto *{};", self.condition(&test, if_zero, frame), self.operand(&target, frame)));
+                },
+            }
+            previous = Some(instruction);
+        }
+
+        let rendered = RenderedBlock { start: block.start, end: block.end, statements, exit };
+        (rendered, frame)
+    }
+
+    fn statement(&self, instruction: &IntcodeInstruction, frame: &mut Frame) -> Option<String> {
+        use IntcodeInstruction::*;
+
+        let statement = match instruction {
+            Add{x, y, position} => self.assign(position, self.binary("+", x, y, *frame), *frame),
+            Multiply{x, y, position} => self.assign(position, self.binary("*", x, y, *frame), *frame),
+            IsLessThan{x, y, position} => self.assign(position, self.binary("<", x, y, *frame), *frame),
+            IsEquals{x, y, position} => self.assign(position, self.binary("==", x, y, *frame), *frame),
+            Input{position} => self.assign(position, String::from("input()"), *frame),
+            Output{value} => format!("output({});", self.operand(value, *frame)),
+            SetRelativeBase{offset: change} => {
+                if let (IntcodeValue::Immediate(change), Some(base)) = (change, frame.base) {
+                    if let Some(base) = base.checked_add(*change) {
+                        frame.base = Some(base);
+                        return None;
+                    }
+                }
+                let statement = format!("rb += {};", self.operand(change, *frame));
+                frame.base = None;
+                statement
+            },
+            Extension{opcode, params, position} => {
+                let name = self.extensions.get(*opcode)
+                    .map(|extension| extension.mnemonic().to_lowercase())
+                    .unwrap_or(format!("op{}", opcode));
+                let arguments: Vec<String> = params.iter().map(|param| self.operand(param, *frame)).collect();
+                let call = format!("{}({})", name, arguments.join(", "));
+                match position {
+                    Some(position) => self.assign(position, call, *frame),
+                    None => format!("{};", call),
+                }
+            },
+            JumpIfTrue{..} | JumpIfFalse{..} | Halt => return None,
+        };
+
+        Some(statement)
+    }
+
+    fn assign(&self, target: &IntcodeValue, expression: String, frame: Frame) -> String {
+        format!("{} = {};", self.operand(target, frame), expression)
+    }
+
+    fn condition(&self, test: &IntcodeValue, if_zero: bool, frame: Frame) -> String {
+        format!("{} {} 0", self.operand(test, frame), if if_zero { "==" } else { "!=" })
+    }
+
+    fn binary(&self, operator: &str, x: &IntcodeValue, y: &IntcodeValue, frame: Frame) -> String {
+        use IntcodeValue::Immediate;
+
+        if let (Immediate(x), Immediate(y)) = (x, y) {
+            let folded = match operator {
+                "+" => x.checked_add(*y),
+                "*" => x.checked_mul(*y),
+                "<" => Some((x < y) as i64),
+                _ => Some((x == y) as i64),
+            };
+            if let Some(value) = folded {
+                return value.to_string();
+            }
+        }
+
+        let (left, right) = (self.operand(x, frame), self.operand(y, frame));
+        match (operator, x, y) {
+            ("+", Immediate(0), _) | ("*", Immediate(1), _) => right,
+            ("+", _, Immediate(0)) | ("*", _, Immediate(1)) => left,
+            ("*", Immediate(0), _) | ("*", _, Immediate(0)) => String::from("0"),
+            ("+", _, Immediate(value)) if *value < 0 => format!("{} - {}", left, value.unsigned_abs()),
+            ("<", ..) if x == y => String::from("0"),
+            ("==", ..) if x == y => String::from("1"),
+            _ => format!("{} {} {}", left, operator, right),
+        }
+    }
+
+    fn operand(&self, value: &IntcodeValue, frame: Frame) -> String {
+        // A slot whose address would overflow is left relative to rb, where running it faults
+        let address = match value {
+            IntcodeValue::Relative(slot) => frame.base.and_then(|base| base.checked_add(*slot)),
+            _ => None,
+        };
+        match (value, address) {
+            (IntcodeValue::Immediate(value), _) => value.to_string(),
+            (IntcodeValue::Position(position), _) => format!("mem[{}]", position),
+            (IntcodeValue::Relative(_), Some(address)) if frame.absolute => format!("mem[{}]", address),
+            (IntcodeValue::Relative(_), Some(address)) if address < 0 => format!("local_m{}", address.unsigned_abs()),
+            (IntcodeValue::Relative(_), Some(address)) => format!("local{}", address),
+            (IntcodeValue::Relative(slot), None) => format!("mem[rb{:+}]", slot),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Region {
+    from: usize,
+    to: usize,
+    // Where control goes after the region, so a jump there from its last block can be left out
+    follow: Option<usize>,
+    // Header and exit address of the innermost loop
+    innermost: Option<(usize, usize)>,
+    header_done: bool,
+}
+
+struct Structurer {
+    blocks: Vec<RenderedBlock>,
+}
+
+impl Structurer {
+    fn structure(&self, region: Region, depth: usize, lines: &mut Vec<String>) {
+        let indent = "    ".repeat(depth);
+        let mut index = region.from;
+        while index < region.to {
+            let block = &self.blocks[index];
+            if !(region.header_done && index == region.from) {
+                lines.push(format!("{}L{}:", indent, block.start));
+                let latch = (index..region.to).rev().find(|&latch| self.blocks[latch].exit.target() == Some(block.start));
+                if let Some(latch) = latch {
+                    self.structure_loop(region, index, latch, depth, lines);
+                    index = latch + 1;
+                    continue;
+                }
+            }
+
+            for statement in &block.statements {
+                lines.push(format!("{}{}", indent, statement));
+            }
+
+            match &block.exit {
+                Exit::Fall => {},
+                Exit::Halt => lines.push(format!("{}halt;", indent)),
+                Exit::Return => lines.push(format!("{}return;", indent)),
+                Exit::Indirect(target) => lines.push(format!("{}goto *{};", indent, target)),
+                Exit::Goto(target) => lines.extend(self.jump(*target, index, &region).map(|jump| format!("{}{}", indent, jump))),
+                Exit::Branch(condition, target) => {
+                    if let Some(jump) = self.loop_jump(*target, &region) {
+                        lines.push(format!("{}if ({}) {}", indent, condition, jump));
+                    } else if let Some(join) = self.forward_index(*target, index, &region) {
+                        if join == index + 1 {
+                            index += 1;
+                            continue;
+                        }
+
+                        // An if/else when the then part ends by jumping over the else part
+                        let else_end = match &self.blocks[join - 1].exit {
+                            Exit::Goto(end) if join - 1 > index && end > target => {
+                                self.forward_index(*end, join - 1, &region).map(|end_index| (*end, end_index))
+                            },
+                            _ => None,
+                        };
+
+                        lines.push(format!("{}if ({}) {{", indent, negate(condition)));
+                        let follow = Some(else_end.map(|(end, _)| end).unwrap_or(*target));
+                        self.structure(Region { from: index + 1, to: join, follow, header_done: false, ..region }, depth + 1, lines);
+                        index = join;
+                        if let Some((_, end_index)) = else_end {
+                            lines.push(format!("{}}} else {{", indent));
+                            self.structure(Region { from: join, to: end_index, follow, header_done: false, ..region }, depth + 1, lines);
+                            index = end_index;
+                        }
+                        lines.push(format!("{}}}", indent));
+                        continue;
+                    } else {
+                        lines.push(format!("{}if ({}) goto L{};", indent, condition, target));
+                    }
+                },
+            }
+
+            index += 1;
+        }
+    }
+
+    fn structure_loop(&self, region: Region, header: usize, latch: usize, depth: usize, lines: &mut Vec<String>) {
+        let indent = "    ".repeat(depth);
+        let inner = "    ".repeat(depth + 1);
+        let start = self.blocks[header].start;
+        let exit = if latch + 1 < region.to {
+            Some(self.blocks[latch + 1].start)
+        } else {
+            region.follow.or(self.blocks.get(latch + 1).map(|block| block.start))
+        };
+        let exit = exit.unwrap_or(self.blocks[latch].end);
+
+        let mut body = Vec::new();
+        let body_region = Region { from: header, to: latch + 1, follow: Some(start), innermost: Some((start, exit)), header_done: true };
+        self.structure(body_region, depth + 1, &mut body);
+        if matches!(self.blocks[latch].exit, Exit::Fall | Exit::Branch(..)) {
+            body.push(format!("{}break;", inner));
+        }
+
+        let mut opening = format!("{}loop {{", indent);
+        let mut closing = format!("{}}}", indent);
+        let leading_break = body.first()
+            .and_then(|line| line.strip_prefix(&inner))
+            .and_then(|line| line.strip_prefix("if ("))
+            .and_then(|line| line.strip_suffix(") break;"))
+            .map(String::from);
+        let continues = body.iter().filter(|line| line.ends_with("continue;")).count();
+        let trailing_continue = match body.as_slice() {
+            [.., condition, last] if continues == 1 && *last == format!("{}break;", inner) => condition
+                .strip_prefix(&inner)
+                .and_then(|line| line.strip_prefix("if ("))
+                .and_then(|line| line.strip_suffix(") continue;"))
+                .map(String::from),
+            _ => None,
+        };
+
+        if let Some(condition) = leading_break {
+            opening = format!("{}while ({}) {{", indent, negate(&condition));
+            body.remove(0);
+        } else if let Some(condition) = trailing_continue {
+            opening = format!("{}do {{", indent);
+            closing = format!("{}}} while ({});", indent, condition);
+            body.truncate(body.len() - 2);
+        }
+
+        lines.push(opening);
+        lines.extend(body);
+        lines.push(closing);
+    }
+
+    fn jump(&self, target: usize, index: usize, region: &Region) -> Option<String> {
+        if index + 1 == region.to && Some(target) == region.follow {
+            return None;
+        }
+        if index + 1 < region.to && self.blocks[index + 1].start == target {
+            return None;
+        }
+
+        Some(self.loop_jump(target, region).map(String::from).unwrap_or(format!("goto L{};", target)))
+    }
+
+    fn loop_jump(&self, target: usize, region: &Region) -> Option<&'static str> {
+        match region.innermost {
+            Some((_, exit)) if exit == target => Some("break;"),
+            Some((header, _)) if header == target => Some("continue;"),
+            _ => None,
+        }
+    }
+
+    // Index of the block at a later address in the region, or the region end if that's where it leads
+    fn forward_index(&self, target: usize, after: usize, region: &Region) -> Option<usize> {
+        if target <= self.blocks[after].start {
+            return None;
+        }
+        if let Some(index) = (after + 1..region.to).find(|&index| self.blocks[index].start == target) {
+            return Some(index);
+        }
+
+        let region_end = self.blocks.get(region.to).map(|block| block.start);
+        if Some(target) == region.follow || Some(target) == region_end {
+            Some(region.to)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decompiled(program: &[i64]) -> String {
+        decompile(program, &IntcodeExtensions::new()).join("\n")
+    }
+
+    #[test]
+    fn test_constant_folding_and_relative_slots() {
+        let program = vec![1101,2,3,20,1002,20,1,21,109,5,21101,0,7,1,204,1,99];
+        assert_eq!(decompiled(&program), [
+            "fn main() {",
+            "    mem[20] = 5;",
+            "    mem[21] = mem[20];",
+            "    mem[6] = 7;",
+            "    output(mem[6]);",
+            "    halt;",
+            "}",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_overflowing_relative_slots() {
+        assert_eq!(decompiled(&[109,1,204,9223372036854775807,99]), [
+            "fn main() {",
+            "    output(mem[rb+9223372036854775807]);",
+            "    halt;",
+            "}",
+        ].join("\n"));
+        assert_eq!(decompiled(&[109,9223372036854775807,109,1,204,0,99]), [
+            "fn main() {",
+            "    rb += 1;",
+            "    output(mem[rb+0]);",
+            "    halt;",
+            "}",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_if_else() {
+        let program = vec![3,100,1008,100,5,101,1005,101,14,104,1,1105,1,16,104,2,99];
+        assert_eq!(decompiled(&program), [
+            "fn main() {",
+            "    mem[100] = input();",
+            "    mem[101] = mem[100] == 5;",
+            "    if (mem[101] == 0) {",
+            "        output(1);",
+            "    } else {",
+            "        output(2);",
+            "    }",
+            "    halt;",
+            "}",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_loops() {
+        // Counts down from 3
+        let program = vec![1101,0,3,100,1006,100,16,4,100,1001,100,-1,100,1105,1,4,99];
+        assert_eq!(decompiled(&program), [
+            "fn main() {",
+            "    mem[100] = 3;",
+            "    while (mem[100] != 0) {",
+            "        output(mem[100]);",
+            "        mem[100] = mem[100] - 1;",
+            "    }",
+            "    halt;",
+            "}",
+        ].join("\n"));
+
+        // Echoes inputs until a zero
+        let program = vec![3,100,4,100,1005,100,0,99];
+        assert_eq!(decompiled(&program), [
+            "fn main() {",
+            "    do {",
+            "        mem[100] = input();",
+            "        output(mem[100]);",
+            "    } while (mem[100] != 0);",
+            "    halt;",
+            "}",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_function_calls() {
+        let program = vec![109,100,21101,0,9,0,1105,1,12,204,1,99,109,2,21101,0,42,-1,109,-2,2106,0,0];
+        assert_eq!(decompiled(&program), [
+            "fn main() {",
+            "    func_12();",
+            "    output(mem[101]);",
+            "    halt;",
+            "}",
+            "",
+            "fn func_12() {",
+            "    local1 = 42;",
+            "    return;",
+            "}",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_unstructured_jumps_use_labels() {
+        // The second branch jumps into the middle of the first if
+        let program = vec![3,100,1005,100,9,104,1,104,2,1006,100,7,99];
+        let lines = decompiled(&program);
+
+        assert!(lines.contains("goto L7;"));
+        assert!(lines.contains("L7:"));
+    }
+
+    #[test]
+    fn test_day9_program() {
+        let program = IntcodeProgram::load("input/input9").unwrap().code;
+        let lines = decompile(&program, &IntcodeExtensions::new());
+
+        assert_eq!(lines[0], "fn main() {");
+        assert_eq!(lines[1], "    mem[63] = 1187721666102244;");
+    }
+}
@@ -0,0 +1,230 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use crate::utils::random::Random;
+
+// Reads and writes by the program to an attached address range are routed here instead of memory.
+// Offsets are relative to the start of the range. Instruction fetches always read plain memory.
+pub trait IntcodeDevice {
+    fn read(&mut self, offset: usize) -> i64;
+    fn write(&mut self, offset: usize, value: i64);
+    fn tick(&mut self) {}
+}
+
+// Lets the caller keep a handle to a device after attaching it to a machine
+impl<D> IntcodeDevice for Rc<RefCell<D>>
+where D: IntcodeDevice
+{
+    fn read(&mut self, offset: usize) -> i64 {
+        self.borrow_mut().read(offset)
+    }
+
+    fn write(&mut self, offset: usize, value: i64) {
+        self.borrow_mut().write(offset, value)
+    }
+
+    fn tick(&mut self) {
+        self.borrow_mut().tick()
+    }
+}
+
+pub struct IntcodeFramebufferDevice {
+    width: usize,
+    height: usize,
+    pixels: Vec<i64>,
+}
+
+impl IntcodeFramebufferDevice {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self { width, height, pixels: vec![0; width * height] }
+    }
+
+    pub fn size(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> i64 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn render(&self) -> String {
+        self.pixels.chunks(self.width)
+            .map(|row| row.iter().map(|pixel| if *pixel == 0 { '.' } else { '#' }).collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+impl IntcodeDevice for IntcodeFramebufferDevice {
+    fn read(&mut self, offset: usize) -> i64 {
+        self.pixels.get(offset).copied().unwrap_or(0)
+    }
+
+    fn write(&mut self, offset: usize, value: i64) {
+        if let Some(pixel) = self.pixels.get_mut(offset) {
+            *pixel = value;
+        }
+    }
+}
+
+// Counts executed instructions; writing sets the counter
+pub struct IntcodeClockDevice {
+    ticks: i64,
+}
+
+impl IntcodeClockDevice {
+    pub fn new() -> Self {
+        Self { ticks: 0 }
+    }
+}
+
+impl IntcodeDevice for IntcodeClockDevice {
+    fn read(&mut self, _offset: usize) -> i64 {
+        self.ticks
+    }
+
+    fn write(&mut self, _offset: usize, value: i64) {
+        self.ticks = value;
+    }
+
+    fn tick(&mut self) {
+        self.ticks += 1;
+    }
+}
+
+// Offset 0 yields a random value in [0, bound), offset 1 holds the bound; writing offset 0 reseeds
+pub struct IntcodeRandomDevice {
+    random: Random,
+    bound: i64,
+}
+
+impl IntcodeRandomDevice {
+    pub fn new(seed: u64) -> Self {
+        Self { random: Random::new(seed), bound: i64::MAX }
+    }
+}
+
+impl IntcodeDevice for IntcodeRandomDevice {
+    fn read(&mut self, offset: usize) -> i64 {
+        match offset {
+            0 => (self.random.next_u64() % self.bound as u64) as i64,
+            _ => self.bound,
+        }
+    }
+
+    fn write(&mut self, offset: usize, value: i64) {
+        match offset {
+            0 => self.random = Random::new(value as u64),
+            _ => self.bound = value.max(1),
+        }
+    }
+}
+
+// Writes append a character to the output, reads take the next queued character or 0 when empty
+pub struct IntcodeConsoleDevice {
+    input: VecDeque<i64>,
+    output: String,
+}
+
+impl IntcodeConsoleDevice {
+    pub fn new() -> Self {
+        Self { input: VecDeque::new(), output: String::new() }
+    }
+
+    pub fn push_str(&mut self, text: &str) {
+        self.input.extend(text.chars().map(|c| c as i64));
+    }
+
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+}
+
+impl IntcodeDevice for IntcodeConsoleDevice {
+    fn read(&mut self, _offset: usize) -> i64 {
+        self.input.pop_front().unwrap_or(0)
+    }
+
+    fn write(&mut self, _offset: usize, value: i64) {
+        if let Some(c) = std::char::from_u32(value as u32) {
+            self.output.push(c);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{IntcodeMachine, IntcodeOutput};
+
+    #[test]
+    fn test_console_writes() {
+        let program = vec![1101,72,0,2000,1101,105,0,2000,99];
+        let console = Rc::new(RefCell::new(IntcodeConsoleDevice::new()));
+        let mut machine = IntcodeMachine::new_automated_machine(&program, &[]);
+        machine.attach_device(2000..2001, console.clone()).unwrap();
+        machine.run();
+
+        assert_eq!(console.borrow().output(), "Hi");
+        assert_eq!(machine.memory().len(), 1024);
+    }
+
+    #[test]
+    fn test_console_reads() {
+        let program = vec![4,100,4,100,99];
+        let mut console = IntcodeConsoleDevice::new();
+        console.push_str("ok");
+        let mut machine = IntcodeMachine::new_automated_machine(&program, &[]);
+        machine.attach_device(100..101, console).unwrap();
+        machine.run();
+
+        assert_eq!(machine.output_handler().history(), &["111", "107"]);
+    }
+
+    #[test]
+    fn test_framebuffer() {
+        // Input a pixel index, then light it
+        let program = vec![3,11,1,11,12,9,1101,0,1,0,99,0,500];
+        let framebuffer = Rc::new(RefCell::new(IntcodeFramebufferDevice::new(3, 2)));
+        let mut machine = IntcodeMachine::new_automated_machine(&program, &[4]);
+        machine.attach_device(500..506, framebuffer.clone()).unwrap();
+        machine.run();
+
+        assert_eq!(framebuffer.borrow().pixel(1, 1), 1);
+        assert_eq!(framebuffer.borrow().render(), "...\n.#.");
+    }
+
+    #[test]
+    fn test_clock() {
+        let program = vec![1101,0,0,50,1101,0,0,50,4,900,99];
+        let mut machine = IntcodeMachine::new_automated_machine(&program, &[]);
+        machine.attach_device(900..901, IntcodeClockDevice::new()).unwrap();
+        machine.run();
+
+        assert_eq!(machine.output_handler().last_output().unwrap(), "2");
+    }
+
+    #[test]
+    fn test_random_is_seeded() {
+        let program = vec![1101,6,0,801,4,800,4,800,99];
+        let run = || {
+            let mut machine = IntcodeMachine::new_automated_machine(&program, &[]);
+            machine.attach_device(800..802, IntcodeRandomDevice::new(5)).unwrap();
+            machine.run();
+            machine.output_handler().history().to_vec()
+        };
+
+        let outputs = run();
+        assert_eq!(outputs, run());
+        assert!(outputs.iter().all(|output| output.parse::<i64>().is_ok_and(|value| (0..6).contains(&value))));
+    }
+
+    #[test]
+    fn test_overlapping_devices_are_rejected() {
+        let mut machine = IntcodeMachine::new_automated_machine(&[99], &[]);
+        machine.attach_device(10..20, IntcodeClockDevice::new()).unwrap();
+        assert!(machine.attach_device(15..25, IntcodeClockDevice::new()).is_err());
+        assert!(machine.attach_device(20..25, IntcodeClockDevice::new()).is_ok());
+    }
+}
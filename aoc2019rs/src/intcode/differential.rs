@@ -0,0 +1,205 @@
+use anyhow::{anyhow, Result};
+
+use std::str::FromStr;
+
+use super::{IntcodeMachine, IntcodeProgram, IntcodeCachedMachine, IntcodeBackend, IntcodeInstruction, IntcodeState};
+
+pub const DEFAULT_MAX_STEPS: usize = 10_000_000;
+
+#[derive(Debug)]
+pub struct IntcodeDivergence {
+    pub step: usize,
+    pub instruction_pointer: usize,
+    pub instruction: Option<IntcodeInstruction>,
+    pub reference: &'static str,
+    pub backend: &'static str,
+    pub reason: String,
+}
+
+impl std::fmt::Display for IntcodeDivergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,
+               "Backends diverged at step {} (ip {}: {}): {} vs {}: {}",
+               self.step,
+               self.instruction_pointer,
+               self.instruction.as_ref().map(|i| format!("{:?}", i)).unwrap_or_else(|| String::from("?")),
+               self.reference,
+               self.backend,
+               self.reason)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct IntcodeAgreement {
+    pub steps: usize,
+    pub state: IntcodeState,
+    pub outputs: Vec<String>,
+}
+
+// The plain interpreter as the reference, and the decoded-instruction cache
+pub fn default_backends(program: &[i64], inputs: &[i64]) -> Vec<Box<dyn IntcodeBackend>> {
+    vec![
+        Box::new(IntcodeMachine::new_automated_machine(program, inputs)),
+        Box::new(IntcodeCachedMachine::new_automated_machine(program, inputs)),
+    ]
+}
+
+pub fn compare(program: &[i64], inputs: &[i64]) -> Result<IntcodeAgreement, Box<IntcodeDivergence>> {
+    compare_backends(default_backends(program, inputs), DEFAULT_MAX_STEPS)
+}
+
+// Steps every backend in lockstep and compares each one against the first after every instruction.
+pub fn compare_backends(mut backends: Vec<Box<dyn IntcodeBackend>>, max_steps: usize) -> Result<IntcodeAgreement, Box<IntcodeDivergence>> {
+    let mut steps = 0;
+
+    while steps < max_steps {
+        let instruction_pointer = backends[0].instruction_pointer();
+        let instruction = backends[0].next_instruction();
+
+        let results = backends.iter_mut()
+            .map(|backend| backend.step())
+            .collect::<Vec<_>>();
+        steps += 1;
+
+        let (reference, others) = backends.split_first().unwrap();
+        for (i, backend) in others.iter().enumerate() {
+            let fault_difference = if results[0] != results[i + 1] {
+                Some(format!("result {:?} != {:?}", results[0], results[i + 1]))
+            } else {
+                None
+            };
+
+            if let Some(reason) = fault_difference.or_else(|| find_difference(reference.as_ref(), backend.as_ref())) {
+                return Err(Box::new(IntcodeDivergence {
+                    step: steps,
+                    instruction_pointer,
+                    instruction,
+                    reference: reference.name(),
+                    backend: backend.name(),
+                    reason,
+                }));
+            }
+        }
+
+        if backends[0].state() != &IntcodeState::Running {
+            break;
+        }
+    }
+
+    Ok(IntcodeAgreement {
+        steps,
+        state: backends[0].state().clone(),
+        outputs: backends[0].outputs().to_vec(),
+    })
+}
+
+fn find_difference(reference: &dyn IntcodeBackend, backend: &dyn IntcodeBackend) -> Option<String> {
+    if reference.state() != backend.state() {
+        return Some(format!("state {:?} != {:?}", reference.state(), backend.state()));
+    }
+
+    if reference.instruction_pointer() != backend.instruction_pointer() {
+        return Some(format!("instruction pointer {} != {}", reference.instruction_pointer(), backend.instruction_pointer()));
+    }
+
+    if reference.instruction_count() != backend.instruction_count() {
+        return Some(format!("instruction count {} != {}", reference.instruction_count(), backend.instruction_count()));
+    }
+
+    if reference.outputs() != backend.outputs() {
+        return Some(format!("outputs {:?} != {:?}", reference.outputs(), backend.outputs()));
+    }
+
+    let reference_memory = reference.memory();
+    let backend_memory = backend.memory();
+    if reference_memory.len() != backend_memory.len() {
+        return Some(format!("memory size {} != {}", reference_memory.len(), backend_memory.len()));
+    }
+
+    reference_memory.iter()
+        .zip(backend_memory.iter())
+        .position(|(a, b)| a != b)
+        .map(|position| format!("memory[{}] {} != {}", position, reference_memory[position], backend_memory[position]))
+}
+
+// Usage: diff <file in input/> [inputs...]
+pub fn run_command(args: &[String]) -> Result<String> {
+    let program = IntcodeProgram::load_from_args(args)?.code;
+    let inputs = args[1..].iter()
+        .map(|arg| i64::from_str(arg).map_err(|_| anyhow!("Invalid input value: {}", arg)))
+        .collect::<Result<Vec<i64>>>()?;
+
+    match compare(&program, &inputs) {
+        Ok(agreement) => Ok(format!("All backends agree after {} steps ({:?})\nOutputs: {}",
+                                    agreement.steps,
+                                    agreement.state,
+                                    agreement.outputs.join(","))),
+        Err(divergence) => Ok(format!("{}", divergence)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{IntcodeOutput, IntcodeFault, IntcodePresetInput, IntcodeHistoryOutput};
+
+    struct BrokenMultiplyBackend {
+        machine: IntcodeMachine<IntcodePresetInput, IntcodeHistoryOutput>,
+    }
+
+    impl IntcodeBackend for BrokenMultiplyBackend {
+        fn name(&self) -> &'static str { "broken" }
+        fn state(&self) -> &IntcodeState { self.machine.state() }
+        fn instruction_pointer(&self) -> usize { self.machine.instruction_pointer() }
+        fn instruction_count(&self) -> usize { self.machine.instruction_count() }
+        fn memory(&self) -> &[i64] { self.machine.memory() }
+        fn outputs(&self) -> &[String] { self.machine.output_handler().history() }
+
+        fn step(&mut self) -> Result<(), IntcodeFault> {
+            let instruction = match self.machine.decode_instruction(self.machine.instruction_pointer()) {
+                IntcodeInstruction::Multiply{x, y, position} => IntcodeInstruction::Add{x, y, position},
+                instruction => instruction,
+            };
+            self.machine.execute(instruction)
+        }
+    }
+
+    #[test]
+    fn test_backends_agree() {
+        let program = IntcodeProgram::load(crate::utils::input::input_file_name(5)).unwrap().code;
+        let agreement = compare(&program, &[5]).unwrap();
+        assert_eq!(agreement.state, IntcodeState::Halted);
+        assert_eq!(agreement.outputs, vec!["11981754"]);
+    }
+
+    #[test]
+    fn test_backends_agree_on_self_modifying_code() {
+        let agreement = compare(&[1002,4,3,4,33], &[]).unwrap();
+        assert_eq!(agreement.steps, 2);
+        assert_eq!(agreement.state, IntcodeState::Halted);
+    }
+
+    #[test]
+    fn test_backends_agree_on_code_in_grown_memory() {
+        // Writes `Add 7i 8i 2007p; Out 2007p; Halt` at 2000, past the initial memory, and runs it
+        let program = vec![1101,1101,0,2000,1101,7,0,2001,1101,8,0,2002,1101,2007,0,2003,1101,4,0,2004,1101,2007,0,2005,1101,99,0,2006,1105,1,2000];
+        let agreement = compare(&program, &[]).unwrap();
+        assert_eq!(agreement.state, IntcodeState::Halted);
+        assert_eq!(agreement.outputs, vec!["15"]);
+    }
+
+    #[test]
+    fn test_reports_first_divergence() {
+        let program = vec![1101,2,3,0,1102,2,3,0,99];
+        let backends: Vec<Box<dyn IntcodeBackend>> = vec![
+            Box::new(IntcodeMachine::new_automated_machine(&program, &[])),
+            Box::new(BrokenMultiplyBackend { machine: IntcodeMachine::new_automated_machine(&program, &[]) }),
+        ];
+
+        let divergence = compare_backends(backends, DEFAULT_MAX_STEPS).unwrap_err();
+        assert_eq!(divergence.step, 2);
+        assert_eq!(divergence.instruction_pointer, 4);
+        assert_eq!(divergence.backend, "broken");
+        assert_eq!(divergence.reason, "memory[0] 6 != 5");
+    }
+}
@@ -0,0 +1,247 @@
+use anyhow::{anyhow, bail, Result};
+
+use crate::intcode::{IntcodeBlockingInput, IntcodeHistoryOutput, IntcodeMachine, IntcodeOutput, IntcodeProgram, IntcodeState};
+use crate::utils::graph::Graph;
+use crate::utils::grid::{Direction, Grid, Point};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntcodeDroidStatus {
+    Wall,
+    Moved,
+    Found,
+}
+
+impl IntcodeDroidStatus {
+    pub fn from_code(code: i64) -> Result<Self> {
+        use IntcodeDroidStatus::*;
+
+        match code {
+            0 => Ok(Wall),
+            1 => Ok(Moved),
+            2 => Ok(Found),
+            _ => bail!("Invalid droid status: {}", code),
+        }
+    }
+}
+
+// Movement commands are 1 north, 2 south, 3 west and 4 east
+fn command_code(direction: Direction) -> i64 {
+    match direction {
+        Direction::Up => 1,
+        Direction::Down => 2,
+        Direction::Left => 3,
+        Direction::Right => 4,
+    }
+}
+
+// A droid program answers each movement command with a status
+pub struct IntcodeDroid {
+    machine: IntcodeMachine<IntcodeBlockingInput, IntcodeHistoryOutput>,
+}
+
+impl IntcodeDroid {
+    pub fn new(program: &[i64]) -> Result<Self> {
+        let mut machine = IntcodeMachine::new_blocking_machine(program);
+        machine.try_run()?;
+        Ok(Self { machine })
+    }
+
+    pub fn command(&mut self, direction: Direction) -> Result<IntcodeDroidStatus> {
+        if self.machine.state() != &IntcodeState::Suspended {
+            bail!("Droid program isn't waiting for a command: {:?}", self.machine.state());
+        }
+
+        let outputs = self.machine.output_handler().history().len();
+        self.machine.input(command_code(direction));
+        self.machine.try_run()?;
+        let history = self.machine.output_handler().history();
+        if history.len() != outputs + 1 {
+            bail!("Expected one status after a command, got {}", history.len() - outputs);
+        }
+        IntcodeDroidStatus::from_code(history[outputs].parse()?)
+    }
+
+    pub fn explore(&mut self) -> Result<IntcodeDroidMap> {
+        explore(|direction| self.command(direction))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntcodeDroidTile {
+    Wall,
+    Open,
+    Target,
+}
+
+// Everything the droid found, with the start at the origin
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntcodeDroidMap {
+    tiles: Grid<IntcodeDroidTile>,
+}
+
+impl IntcodeDroidMap {
+    pub fn tile(&self, point: Point) -> Option<IntcodeDroidTile> {
+        self.tiles.get(point).copied()
+    }
+
+    pub fn target(&self) -> Option<Point> {
+        self.tiles.iter().find(|(_, tile)| **tile == IntcodeDroidTile::Target).map(|(point, _)| *point)
+    }
+
+    // Open tiles connected to their open neighbours
+    pub fn graph(&self) -> Graph<Point> {
+        let mut graph = Graph::new();
+        for (point, tile) in self.tiles.iter() {
+            if *tile == IntcodeDroidTile::Wall {
+                continue;
+            }
+            let neighbours = [Direction::Up, Direction::Right, Direction::Down, Direction::Left]
+                .iter()
+                .map(|direction| direction.step(*point))
+                .filter(|neighbour| matches!(self.tile(*neighbour), Some(IntcodeDroidTile::Open) | Some(IntcodeDroidTile::Target)))
+                .collect();
+            graph.add_node_with_adjacency_list(*point, neighbours);
+        }
+
+        graph
+    }
+
+    // None when either end isn't an explored open tile
+    pub fn shortest_path(&self, from: Point, to: Point) -> Option<Vec<Point>> {
+        self.graph().shortest_path(from, to)
+    }
+
+    // Minutes for something spreading one tile a minute from `from` to fill the whole area, or
+    // None when `from` isn't an explored open tile
+    pub fn fill_time(&self, from: Point) -> Option<usize> {
+        self.graph().distances(from).values().max().copied()
+    }
+
+    // `#` walls, `.` open tiles, `O` the target, `D` the start and blanks for unexplored tiles
+    pub fn render(&self) -> String {
+        self.tiles.render(|point, tile| match (point, tile) {
+            (_, Some(IntcodeDroidTile::Target)) => 'O',
+            ((0, 0), _) => 'D',
+            (_, Some(IntcodeDroidTile::Wall)) => '#',
+            (_, Some(IntcodeDroidTile::Open)) => '.',
+            (_, None) => ' ',
+        })
+    }
+}
+
+// Depth first with backtracking: every open tile is visited once and then left the way the droid
+// came in, so the droid ends up back at the start
+pub fn explore<F>(mut step: F) -> Result<IntcodeDroidMap>
+where F: FnMut(Direction) -> Result<IntcodeDroidStatus>
+{
+    let directions = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+    let mut tiles = Grid::new();
+    tiles.set((0, 0), IntcodeDroidTile::Open);
+
+    // Each entry is a tile being explored, the direction that led to it and the next direction to try
+    let mut stack: Vec<(Point, Option<Direction>, usize)> = vec![((0, 0), None, 0)];
+    while let Some((position, entered, next)) = stack.last().copied() {
+        if next == directions.len() {
+            if let Some(entered) = entered {
+                let back = entered.reverse();
+                let expected = match tiles.get(back.step(position)) {
+                    Some(IntcodeDroidTile::Target) => IntcodeDroidStatus::Found,
+                    _ => IntcodeDroidStatus::Moved,
+                };
+                if step(back)? != expected {
+                    bail!("Droid couldn't backtrack from {:?}", position);
+                }
+            }
+            stack.pop();
+            continue;
+        }
+
+        if let Some(top) = stack.last_mut() {
+            top.2 += 1;
+        }
+        let direction = directions[next];
+        let neighbour = direction.step(position);
+        if tiles.get(neighbour).is_some() {
+            continue;
+        }
+
+        match step(direction)? {
+            IntcodeDroidStatus::Wall => tiles.set(neighbour, IntcodeDroidTile::Wall),
+            status => {
+                let tile = if status == IntcodeDroidStatus::Found { IntcodeDroidTile::Target } else { IntcodeDroidTile::Open };
+                tiles.set(neighbour, tile);
+                stack.push((neighbour, Some(direction), 0));
+            },
+        }
+    }
+
+    Ok(IntcodeDroidMap { tiles })
+}
+
+// Usage: droid <file in input/>
+pub fn run_command(args: &[String]) -> Result<String> {
+    let program = IntcodeProgram::load_from_args(args)?.code;
+
+    let map = IntcodeDroid::new(&program)?.explore()?;
+    let target = map.target().ok_or(anyhow!("The droid didn't find anything"))?;
+    let path = map.shortest_path((0, 0), target).ok_or(anyhow!("No path to {:?}", target))?;
+    let fill_time = map.fill_time(target).ok_or(anyhow!("Nothing to fill from {:?}", target))?;
+    Ok(format!("{}\nMoves to {:?}: {}\nFill time from there: {}", map.render(), target, path.len() - 1, fill_time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Answers commands like a droid program would for a maze given as text, starting at `D`
+    fn maze(text: &str) -> impl FnMut(Direction) -> Result<IntcodeDroidStatus> {
+        let rows: Vec<Vec<char>> = text.lines().map(|line| line.chars().collect()).collect();
+        let start = rows.iter().enumerate()
+            .find_map(|(y, row)| row.iter().position(|c| *c == 'D').map(|x| (x as i64, y as i64)))
+            .unwrap();
+        let mut position = start;
+        move |direction| {
+            let (x, y) = direction.step(position);
+            match rows[y as usize][x as usize] {
+                '#' => Ok(IntcodeDroidStatus::Wall),
+                c => {
+                    position = (x, y);
+                    Ok(if c == 'O' { IntcodeDroidStatus::Found } else { IntcodeDroidStatus::Moved })
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn test_explore_maze() {
+        let text = "#####\n#D..#\n#.#.#\n#..O#\n#####";
+        let map = explore(maze(text)).unwrap();
+
+        assert_eq!(map.render(), " ### \n#D..#\n#.#.#\n#..O#\n ### ");
+        assert_eq!(map.target(), Some((2, 2)));
+        assert_eq!(map.shortest_path((0, 0), (2, 2)).unwrap().len() - 1, 4);
+        assert_eq!(map.fill_time((2, 2)), Some(4));
+        assert_eq!(map.fill_time((1, 0)), Some(4));
+    }
+
+    #[test]
+    fn test_missing_start() {
+        let map = explore(maze("#####\n#D..#\n#####")).unwrap();
+
+        assert_eq!(map.shortest_path((5, 5), (1, 0)), None);
+        assert_eq!(map.shortest_path((0, 0), (0, 1)), None);
+        assert_eq!(map.fill_time((0, 1)), None);
+        assert_eq!(map.fill_time((9, 9)), None);
+    }
+
+    #[test]
+    fn test_droid_program() {
+        // Reports a wall whatever the command
+        let program = vec![3,100,104,0,1105,1,0];
+        let map = IntcodeDroid::new(&program).unwrap().explore().unwrap();
+
+        assert_eq!(map.render(), " # \n#D#\n # ");
+        assert_eq!(map.fill_time((0, 0)), Some(0));
+        assert_eq!(map.target(), None);
+    }
+}
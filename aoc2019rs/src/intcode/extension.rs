@@ -0,0 +1,207 @@
+use anyhow::{bail, Result};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::IntcodeFault;
+use super::assembly::is_reserved_mnemonic;
+
+pub enum IntcodeEffect {
+    Continue,
+    Write(i64),
+    Jump(i64),
+    Exit(i64),
+}
+
+type ExtensionFn = dyn Fn(&[i64]) -> Result<IntcodeEffect, IntcodeFault> + Send + Sync;
+
+// A custom opcode: `reads` parameters are evaluated with their modes and handed to `execute`,
+// and if `writes` is set one more position parameter receives the value of `IntcodeEffect::Write`.
+#[derive(Clone)]
+pub struct IntcodeExtension {
+    opcode: i64,
+    mnemonic: String,
+    reads: usize,
+    writes: bool,
+    execute: Arc<ExtensionFn>,
+}
+
+impl IntcodeExtension {
+    pub fn new<F>(opcode: i64, mnemonic: &str, reads: usize, writes: bool, execute: F) -> Self
+    where F: Fn(&[i64]) -> Result<IntcodeEffect, IntcodeFault> + Send + Sync + 'static
+    {
+        Self {
+            opcode,
+            mnemonic: String::from(mnemonic),
+            reads,
+            writes,
+            execute: Arc::new(execute),
+        }
+    }
+
+    pub fn divide(opcode: i64) -> Self {
+        Self::new(opcode, "Div", 2, true, |args| {
+            args[0].checked_div(args[1])
+                .map(IntcodeEffect::Write)
+                .ok_or(if args[1] == 0 { IntcodeFault::DivisionByZero } else { IntcodeFault::Overflow })
+        })
+    }
+
+    pub fn modulo(opcode: i64) -> Self {
+        Self::new(opcode, "Mod", 2, true, |args| {
+            args[0].checked_rem(args[1])
+                .map(IntcodeEffect::Write)
+                .ok_or(if args[1] == 0 { IntcodeFault::DivisionByZero } else { IntcodeFault::Overflow })
+        })
+    }
+
+    pub fn bitwise_and(opcode: i64) -> Self {
+        Self::new(opcode, "And", 2, true, |args| Ok(IntcodeEffect::Write(args[0] & args[1])))
+    }
+
+    pub fn bitwise_or(opcode: i64) -> Self {
+        Self::new(opcode, "Or", 2, true, |args| Ok(IntcodeEffect::Write(args[0] | args[1])))
+    }
+
+    pub fn bitwise_xor(opcode: i64) -> Self {
+        Self::new(opcode, "Xor", 2, true, |args| Ok(IntcodeEffect::Write(args[0] ^ args[1])))
+    }
+
+    pub fn halt_with_code(opcode: i64) -> Self {
+        Self::new(opcode, "Exit", 1, false, |args| Ok(IntcodeEffect::Exit(args[0])))
+    }
+
+    pub fn opcode(&self) -> i64 {
+        self.opcode
+    }
+
+    pub fn mnemonic(&self) -> &str {
+        &self.mnemonic
+    }
+
+    pub fn reads(&self) -> usize {
+        self.reads
+    }
+
+    pub fn writes(&self) -> bool {
+        self.writes
+    }
+
+    pub fn execute(&self, args: &[i64]) -> Result<IntcodeEffect, IntcodeFault> {
+        (self.execute)(args)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct IntcodeExtensions {
+    extensions: HashMap<i64, IntcodeExtension>,
+}
+
+impl IntcodeExtensions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn register(&mut self, extension: IntcodeExtension) -> Result<()> {
+        let opcode = extension.opcode;
+        if !(10..=98).contains(&opcode) {
+            bail!("Extension opcodes must be between 10 and 98, got {}", opcode);
+        }
+        if extension.reads + extension.writes as usize > 3 {
+            bail!("Extension {} has more than three parameters", extension.mnemonic);
+        }
+        if is_reserved_mnemonic(&extension.mnemonic) {
+            bail!("Mnemonic {} is reserved by the assembler", extension.mnemonic);
+        }
+        if let Some(existing) = self.extensions.get(&opcode) {
+            bail!("Opcode {} is already registered as {}", opcode, existing.mnemonic);
+        }
+        if self.by_mnemonic(&extension.mnemonic).is_some() {
+            bail!("Mnemonic {} is already registered", extension.mnemonic);
+        }
+
+        self.extensions.insert(opcode, extension);
+        Ok(())
+    }
+
+    pub fn get(&self, opcode: i64) -> Option<&IntcodeExtension> {
+        self.extensions.get(&opcode)
+    }
+
+    pub fn by_mnemonic(&self, mnemonic: &str) -> Option<&IntcodeExtension> {
+        self.extensions.values().find(|extension| extension.mnemonic.eq_ignore_ascii_case(mnemonic))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.extensions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{IntcodeMachine, IntcodeOutput, IntcodeState};
+
+    #[test]
+    fn test_divide_and_modulo() {
+        // out 17 / 5, out 17 % 5
+        let program = vec![1110,17,5,20,4,20,1111,17,5,20,4,20,99];
+        let mut machine = IntcodeMachine::new_automated_machine(&program, &[]);
+        machine.register_extension(IntcodeExtension::divide(10)).unwrap();
+        machine.register_extension(IntcodeExtension::modulo(11)).unwrap();
+        machine.run();
+
+        assert_eq!(machine.output_handler().history(), &["3", "2"]);
+    }
+
+    #[test]
+    fn test_division_by_zero_faults() {
+        let program = vec![1110,17,0,20,99];
+        let mut machine = IntcodeMachine::new_automated_machine(&program, &[]);
+        machine.register_extension(IntcodeExtension::divide(10)).unwrap();
+
+        assert_eq!(machine.try_run(), Err(IntcodeFault::DivisionByZero));
+    }
+
+    #[test]
+    fn test_halt_with_code() {
+        let program = vec![3,9,1001,9,1,9,42,9,99,0];
+        let mut machine = IntcodeMachine::new_automated_machine(&program, &[41]);
+        machine.register_extension(IntcodeExtension::halt_with_code(42)).unwrap();
+        machine.run();
+
+        assert_eq!(machine.state(), &IntcodeState::Halted);
+        assert_eq!(machine.exit_code(), Some(42));
+    }
+
+    #[test]
+    fn test_custom_jump() {
+        // Jmp 6i skips the first output
+        let program = vec![150,6,104,1,99,0,104,2,99];
+        let mut machine = IntcodeMachine::new_automated_machine(&program, &[]);
+        machine.register_extension(IntcodeExtension::new(50, "Jmp", 1, false, |args| Ok(IntcodeEffect::Jump(args[0])))).unwrap();
+        machine.run();
+
+        assert_eq!(machine.output_handler().history(), &["2"]);
+    }
+
+    #[test]
+    fn test_registration_is_validated() {
+        let mut extensions = IntcodeExtensions::new();
+        assert!(extensions.register(IntcodeExtension::divide(9)).is_err());
+        assert!(extensions.register(IntcodeExtension::divide(99)).is_err());
+        assert!(extensions.register(IntcodeExtension::divide(10)).is_ok());
+        assert!(extensions.register(IntcodeExtension::modulo(10)).is_err());
+        assert!(extensions.register(IntcodeExtension::divide(11)).is_err());
+    }
+
+    #[test]
+    fn test_reserved_mnemonics_are_rejected() {
+        let mut extensions = IntcodeExtensions::new();
+        for mnemonic in ["Add", "out", "HALT", "data", "Data"] {
+            let error = extensions.register(IntcodeExtension::new(20, mnemonic, 1, false, |_| Ok(IntcodeEffect::Continue))).unwrap_err();
+            assert_eq!(error.to_string(), format!("Mnemonic {} is reserved by the assembler", mnemonic));
+        }
+        assert!(extensions.register(IntcodeExtension::new(20, "Adder", 1, false, |_| Ok(IntcodeEffect::Continue))).is_ok());
+    }
+}
@@ -0,0 +1,38 @@
+use crate::intcode::IntcodeProfile;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntcodeFault {
+    InvalidInstruction(i64),
+    InvalidParameterMode(i64),
+    TruncatedInstruction(i64),
+    InstructionPointerOutOfRange(usize),
+    MemoryOutOfRange(i64),
+    InvalidRelativeBase(i64),
+    Overflow,
+    DivisionByZero,
+    InputExhausted,
+    UnsupportedOpcode(IntcodeProfile, i64),
+    UnsupportedParameterMode(IntcodeProfile, i64),
+}
+
+impl std::fmt::Display for IntcodeFault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use IntcodeFault::*;
+
+        match self {
+            InvalidInstruction(opcode) => write!(f, "Invalid instruction: {}", opcode),
+            InvalidParameterMode(mode) => write!(f, "Invalid parameter mode: {}", mode),
+            TruncatedInstruction(opcode) => write!(f, "Instruction {} runs past the end of memory", opcode),
+            InstructionPointerOutOfRange(position) => write!(f, "Instruction pointer out of range: {}", position),
+            MemoryOutOfRange(address) => write!(f, "Memory address out of range: {}", address),
+            InvalidRelativeBase(base) => write!(f, "Invalid relative base: {}", base),
+            Overflow => write!(f, "Arithmetic overflow"),
+            DivisionByZero => write!(f, "Division by zero"),
+            InputExhausted => write!(f, "Ran out of inputs"),
+            UnsupportedOpcode(profile, opcode) => write!(f, "Opcode {} is not part of the {} instruction set", opcode, profile),
+            UnsupportedParameterMode(profile, mode) => write!(f, "Parameter mode {} is not part of the {} instruction set", mode, profile),
+        }
+    }
+}
+
+impl std::error::Error for IntcodeFault {}
@@ -0,0 +1,405 @@
+use anyhow::{anyhow, Result};
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::utils::random::Random;
+use super::{IntcodeMachine, IntcodeCachedMachine, IntcodeBackend, IntcodeQueueInput, IntcodeOutput, IntcodeHistoryOutput};
+use super::{IntcodeState, IntcodeFault, differential};
+
+pub const REGRESSION_DIRECTORY: &str = "fuzz/regressions";
+pub const DEFAULT_BUDGET: usize = 2_000;
+
+// (opcode, parameter count, index of the written parameter)
+const OPCODES: [(i64, usize, Option<usize>); 10] = [
+    (1, 3, Some(2)),
+    (2, 3, Some(2)),
+    (3, 1, Some(0)),
+    (4, 1, None),
+    (5, 2, None),
+    (6, 2, None),
+    (7, 3, Some(2)),
+    (8, 3, Some(2)),
+    (9, 1, None),
+    (99, 0, None),
+];
+
+// Occasional operands at the edges of i64, which small random values never reach
+const EXTREME_OPERANDS: [i64; 4] = [i64::MIN, i64::MIN + 1, i64::MAX - 1, i64::MAX];
+
+#[derive(Clone, Debug, PartialEq, Hash)]
+pub struct IntcodeFuzzCase {
+    pub program: Vec<i64>,
+    pub inputs: Vec<i64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntcodeFuzzFailure {
+    Panic(String),
+    UnreportedFault(String),
+    SnapshotMismatch(String),
+    Divergence(String),
+}
+
+type FuzzMachine = IntcodeMachine<IntcodeQueueInput, IntcodeHistoryOutput>;
+
+pub struct IntcodeFuzzer {
+    random: Random,
+    max_instructions: usize,
+    max_inputs: usize,
+    budget: usize,
+}
+
+impl IntcodeFuzzer {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            random: Random::new(seed),
+            max_instructions: 24,
+            max_inputs: 8,
+            budget: DEFAULT_BUDGET,
+        }
+    }
+
+    pub fn generate(&mut self) -> IntcodeFuzzCase {
+        let instruction_count = 1 + self.random.below(self.max_instructions);
+        let mut layout: Vec<(i64, usize, Option<usize>)> = (0..instruction_count)
+            .map(|_| *self.random.choose(&OPCODES))
+            .collect();
+        layout.push((99, 0, None));
+
+        let mut starts = Vec::with_capacity(layout.len());
+        let mut code_length = 0;
+        for (_, parameter_count, _) in &layout {
+            starts.push(code_length as i64);
+            code_length += parameter_count + 1;
+        }
+        let total_length = code_length + 1 + self.random.below(8);
+
+        let mut program = Vec::with_capacity(total_length);
+        for (opcode, parameter_count, written) in layout {
+            let mut modes = 0;
+            let mut parameters = Vec::with_capacity(parameter_count);
+            for i in 0..parameter_count {
+                let mode = if Some(i) == written { 0 } else { self.random.below(3) as i64 };
+                let is_jump_target = (opcode == 5 || opcode == 6) && i == 1;
+                let parameter = match mode {
+                    0 => self.random.below(total_length) as i64,
+                    1 if is_jump_target => *self.random.choose(&starts),
+                    _ if self.random.chance(1, 16) => *self.random.choose(&EXTREME_OPERANDS),
+                    1 => self.random.range(-10, 10),
+                    _ => self.random.range(-4, 4),
+                };
+                modes += mode * 10_i64.pow(i as u32 + 2);
+                parameters.push(parameter);
+            }
+            program.push(opcode + modes);
+            program.extend(parameters);
+        }
+        while program.len() < total_length {
+            program.push(self.random.range(-10, 10));
+        }
+
+        let input_count = self.random.below(self.max_inputs + 1);
+        let inputs = (0..input_count).map(|_| self.random.range(-10, 10)).collect();
+
+        IntcodeFuzzCase { program, inputs }
+    }
+
+    pub fn run(&mut self, cases: usize) -> Vec<(IntcodeFuzzCase, IntcodeFuzzFailure)> {
+        let mut failures = Vec::new();
+        for _ in 0..cases {
+            let case = self.generate();
+            if let Err(failure) = check_case(&case, self.budget) {
+                let minimized = minimize(&case, &failure, self.budget);
+                let failure = check_case(&minimized, self.budget).err().unwrap_or(failure);
+                failures.push((minimized, failure));
+            }
+        }
+        failures
+    }
+}
+
+pub fn check_case(case: &IntcodeFuzzCase, budget: usize) -> Result<(), IntcodeFuzzFailure> {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        check_fault_reporting(case, budget)?;
+        check_snapshot_restore(case, budget)?;
+        check_backends_agree(case, budget)
+    }));
+
+    match result {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = payload.downcast_ref::<String>().cloned()
+                .or_else(|| payload.downcast_ref::<&str>().map(|message| message.to_string()))
+                .unwrap_or_else(|| String::from("unknown panic"));
+            Err(IntcodeFuzzFailure::Panic(message))
+        },
+    }
+}
+
+fn new_machine(program: &[i64], inputs: &[i64]) -> FuzzMachine {
+    IntcodeMachine::new(program, IntcodeQueueInput::new(inputs), IntcodeHistoryOutput::new())
+}
+
+fn run_for(machine: &mut FuzzMachine, budget: usize) -> Result<(), IntcodeFault> {
+    for _ in 0..budget {
+        machine.step()?;
+        if machine.state() != &IntcodeState::Running {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn check_fault_reporting(case: &IntcodeFuzzCase, budget: usize) -> Result<(), IntcodeFuzzFailure> {
+    let mut machine = new_machine(&case.program, &case.inputs);
+    let result = run_for(&mut machine, budget);
+
+    match (&result, machine.state()) {
+        (Err(fault), IntcodeState::Faulted(state_fault)) if fault == state_fault => Ok(()),
+        (Ok(()), state) if !matches!(state, IntcodeState::Faulted(_)) => Ok(()),
+        (result, state) => Err(IntcodeFuzzFailure::UnreportedFault(format!("step returned {:?} in state {:?}", result, state))),
+    }
+}
+
+fn check_snapshot_restore(case: &IntcodeFuzzCase, budget: usize) -> Result<(), IntcodeFuzzFailure> {
+    let split = budget / 2;
+    let mut original = new_machine(&case.program, &case.inputs);
+    let _ = run_for(&mut original, split);
+
+    let snapshot = original.snapshot();
+    let outputs_at_split = original.output_handler().history().len();
+    let consumed = original.input_handler().consumed();
+
+    let mut restored = new_machine(&[], &case.inputs[consumed..]);
+    restored.restore(&snapshot);
+    if restored.snapshot() != snapshot {
+        return Err(IntcodeFuzzFailure::SnapshotMismatch(String::from("restored machine differs from snapshot")));
+    }
+
+    let _ = run_for(&mut original, budget - split);
+    let _ = run_for(&mut restored, budget - split);
+
+    if original.snapshot() != restored.snapshot() {
+        return Err(IntcodeFuzzFailure::SnapshotMismatch(format!("{:?} != {:?}", original, restored)));
+    }
+
+    let original_outputs = &original.output_handler().history()[outputs_at_split..];
+    let restored_outputs = restored.output_handler().history();
+    if original_outputs != restored_outputs {
+        return Err(IntcodeFuzzFailure::SnapshotMismatch(format!("outputs {:?} != {:?}", original_outputs, restored_outputs)));
+    }
+
+    Ok(())
+}
+
+fn check_backends_agree(case: &IntcodeFuzzCase, budget: usize) -> Result<(), IntcodeFuzzFailure> {
+    let backends: Vec<Box<dyn IntcodeBackend>> = vec![
+        Box::new(new_machine(&case.program, &case.inputs)),
+        Box::new(IntcodeCachedMachine::new(&case.program, IntcodeQueueInput::new(&case.inputs), IntcodeHistoryOutput::new())),
+    ];
+
+    differential::compare_backends(backends, budget)
+        .map(|_| ())
+        .map_err(|divergence| IntcodeFuzzFailure::Divergence(divergence.to_string()))
+}
+
+// Delta debugging over the program words, keeping any reduction that still fails the same way.
+pub fn minimize(case: &IntcodeFuzzCase, failure: &IntcodeFuzzFailure, budget: usize) -> IntcodeFuzzCase {
+    let fails_the_same = |program: &[i64]| {
+        let candidate = IntcodeFuzzCase { program: program.to_vec(), inputs: case.inputs.clone() };
+        match check_case(&candidate, budget) {
+            Err(candidate_failure) => std::mem::discriminant(&candidate_failure) == std::mem::discriminant(failure),
+            Ok(()) => false,
+        }
+    };
+
+    IntcodeFuzzCase { program: minimize_words(&case.program, fails_the_same), inputs: case.inputs.clone() }
+}
+
+fn minimize_words<F>(words: &[i64], fails: F) -> Vec<i64>
+where F: Fn(&[i64]) -> bool
+{
+    let mut program = words.to_vec();
+    let mut granularity = 2;
+    while program.len() >= 2 {
+        let chunk_size = program.len().div_ceil(granularity);
+        let mut reduced = false;
+
+        for start in (0..program.len()).step_by(chunk_size) {
+            let end = (start + chunk_size).min(program.len());
+            let candidate: Vec<i64> = program[..start].iter()
+                .chain(program[end..].iter())
+                .copied()
+                .collect();
+
+            if fails(&candidate) {
+                program = candidate;
+                granularity = (granularity - 1).max(2);
+                reduced = true;
+                break;
+            }
+        }
+
+        if !reduced {
+            if granularity >= program.len() {
+                break;
+            }
+            granularity = (granularity * 2).min(program.len());
+        }
+    }
+
+    program
+}
+
+pub fn save_regression(case: &IntcodeFuzzCase, failure: &IntcodeFuzzFailure, directory: &Path) -> Result<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    case.hash(&mut hasher);
+
+    fs::create_dir_all(directory)?;
+    let path = directory.join(format!("{:016x}.txt", hasher.finish()));
+    let contents = format!("# {:?}\nprogram: {}\ninputs: {}\n", failure, join(&case.program), join(&case.inputs));
+    fs::write(&path, contents)?;
+
+    Ok(path)
+}
+
+pub fn load_regressions(directory: &Path) -> Result<Vec<IntcodeFuzzCase>> {
+    if !directory.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut paths = fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<PathBuf>>>()?;
+    paths.sort();
+
+    paths.into_iter()
+        .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+        .map(|path| parse_regression(&fs::read_to_string(&path)?))
+        .collect()
+}
+
+fn parse_regression(contents: &str) -> Result<IntcodeFuzzCase> {
+    let mut program = None;
+    let mut inputs = Vec::new();
+
+    for line in contents.lines().filter(|line| !line.starts_with('#')) {
+        if let Some(values) = line.strip_prefix("program:") {
+            program = Some(split(values)?);
+        } else if let Some(values) = line.strip_prefix("inputs:") {
+            inputs = split(values)?;
+        }
+    }
+
+    let program = program.ok_or(anyhow!("Regression case has no program"))?;
+    Ok(IntcodeFuzzCase { program, inputs })
+}
+
+fn join(values: &[i64]) -> String {
+    values.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(",")
+}
+
+fn split(values: &str) -> Result<Vec<i64>> {
+    values.split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| i64::from_str(value).map_err(|_| anyhow!("Invalid value in regression case: {}", value)))
+        .collect()
+}
+
+// Usage: fuzz [cases] [seed]
+pub fn run_command(args: &[String]) -> Result<String> {
+    let cases = match args.first() {
+        Some(cases) => usize::from_str(cases)?,
+        None => 1000,
+    };
+    let seed = match args.get(1) {
+        Some(seed) => u64::from_str(seed)?,
+        None => Random::from_time().next_u64(),
+    };
+
+    let failures = IntcodeFuzzer::new(seed).run(cases);
+
+    let mut report = format!("Ran {} cases with seed {}: {} failures", cases, seed, failures.len());
+    for (case, failure) in &failures {
+        let path = save_regression(case, failure, Path::new(REGRESSION_DIRECTORY))?;
+        report.push_str(&format!("\n{:?}\n  saved to {}", failure, path.display()));
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_programs_hold_invariants() {
+        let mut fuzzer = IntcodeFuzzer::new(2019);
+        let failures = fuzzer.run(300);
+        assert_eq!(failures, Vec::new());
+    }
+
+    #[test]
+    fn test_generated_programs_are_well_formed() {
+        let mut fuzzer = IntcodeFuzzer::new(11);
+        for _ in 0..100 {
+            let case = fuzzer.generate();
+            let machine = new_machine(&case.program, &case.inputs);
+            assert!(machine.try_decode_instruction(0).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_generated_programs_reach_extreme_operands() {
+        let mut fuzzer = IntcodeFuzzer::new(5);
+        let found = (0..100).any(|_| fuzzer.generate().program.iter().any(|word| EXTREME_OPERANDS.contains(word)));
+        assert!(found);
+    }
+
+    #[test]
+    fn test_faults_are_reported() {
+        let mut machine = new_machine(&[1,1000000,0,0,99], &[]);
+        assert_eq!(machine.try_run(), Err(IntcodeFault::MemoryOutOfRange(1000000)));
+        assert_eq!(machine.state(), &IntcodeState::Faulted(IntcodeFault::MemoryOutOfRange(1000000)));
+        assert_eq!(machine.instruction_pointer(), 0);
+    }
+
+    #[test]
+    fn test_minimize_words() {
+        let program = vec![1101,1,1,20,104,5,2,1000000,0,0,99];
+        let fails = |words: &[i64]| words.contains(&1000000) && words.contains(&99);
+        assert_eq!(minimize_words(&program, fails), vec![1000000, 99]);
+    }
+
+    #[test]
+    fn test_passing_case_does_not_fail() {
+        let case = IntcodeFuzzCase { program: vec![3,20,1001,20,1,21,4,21,99], inputs: vec![1,2] };
+        assert_eq!(check_case(&case, DEFAULT_BUDGET), Ok(()));
+    }
+
+    #[test]
+    fn test_regression_round_trip() {
+        let case = IntcodeFuzzCase { program: vec![109,-1,99], inputs: vec![3,-4] };
+        let failure = IntcodeFuzzFailure::Panic(String::from("attempt to add with overflow"));
+        let directory = std::env::temp_dir().join("aoc2019rs_fuzz_round_trip");
+
+        let path = save_regression(&case, &failure, &directory).unwrap();
+        let loaded = load_regressions(&directory).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(loaded, vec![case]);
+    }
+
+    #[test]
+    fn test_saved_regressions() {
+        for case in load_regressions(Path::new(REGRESSION_DIRECTORY)).unwrap() {
+            assert_eq!(check_case(&case, DEFAULT_BUDGET), Ok(()), "{:?}", case);
+        }
+    }
+}
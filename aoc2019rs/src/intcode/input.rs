@@ -1,9 +1,45 @@
+use anyhow::{anyhow, Result};
+
+use std::collections::VecDeque;
+use std::path::Path;
 use std::str::FromStr;
 
 use crate::utils::input;
 
 pub trait IntcodeInput {
     fn process(&mut self) -> Option<i64>;
+
+    // When true after `process` returned None, the machine faults instead of suspending
+    fn is_exhausted(&self) -> bool {
+        false
+    }
+
+    // When true after `process` returned None, the source has reached its end rather than
+    // having nothing to give yet
+    fn is_finished(&self) -> bool {
+        false
+    }
+}
+
+// What a source does once it has nothing left to give
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntcodeExhaustion {
+    Suspend,
+    Fault,
+    Default(i64),
+}
+
+impl IntcodeExhaustion {
+    fn apply(&self, exhausted: &mut bool) -> Option<i64> {
+        match self {
+            IntcodeExhaustion::Suspend => None,
+            IntcodeExhaustion::Fault => {
+                *exhausted = true;
+                None
+            },
+            IntcodeExhaustion::Default(value) => Some(*value),
+        }
+    }
 }
 
 pub struct IntcodeConsoleInput;
@@ -31,6 +67,36 @@ impl IntcodeInput for IntcodePresetInput {
     }
 }
 
+#[derive(Clone)]
+pub struct IntcodeQueueInput {
+    inputs: Vec<i64>,
+    consumed: usize,
+}
+
+impl IntcodeQueueInput {
+    pub fn new(inputs: &[i64]) -> Self {
+        Self { inputs: inputs.to_vec(), consumed: 0 }
+    }
+
+    pub fn consumed(&self) -> usize {
+        self.consumed
+    }
+}
+
+impl IntcodeInput for IntcodeQueueInput {
+    fn process(&mut self) -> Option<i64> {
+        let next = self.inputs.get(self.consumed).copied();
+        if next.is_some() {
+            self.consumed += 1;
+        }
+        next
+    }
+
+    fn is_finished(&self) -> bool {
+        self.consumed == self.inputs.len()
+    }
+}
+
 pub struct IntcodeBlockingInput;
 
 impl IntcodeInput for IntcodeBlockingInput {
@@ -38,3 +104,246 @@ impl IntcodeInput for IntcodeBlockingInput {
         None
     }
 }
+
+pub struct IntcodeIteratorInput<T> {
+    inputs: T,
+    exhaustion: IntcodeExhaustion,
+    exhausted: bool,
+    finished: bool,
+}
+
+impl<T> IntcodeIteratorInput<T>
+where T: Iterator<Item=i64>,
+{
+    pub fn new(inputs: T) -> Self {
+        Self { inputs, exhaustion: IntcodeExhaustion::Suspend, exhausted: false, finished: false }
+    }
+
+    pub fn with_exhaustion(mut self, exhaustion: IntcodeExhaustion) -> Self {
+        self.exhaustion = exhaustion;
+        self
+    }
+}
+
+impl IntcodeIteratorInput<std::vec::IntoIter<i64>> {
+    // Numbers separated by commas and/or whitespace
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let inputs = contents.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|word| !word.is_empty())
+            .map(|word| i64::from_str(word).map_err(|_| anyhow!("Invalid input {} in {}", word, path.display())))
+            .collect::<Result<Vec<i64>>>()?;
+
+        Ok(Self::new(inputs.into_iter()))
+    }
+}
+
+impl<T> IntcodeInput for IntcodeIteratorInput<T>
+where T: Iterator<Item=i64>,
+{
+    fn process(&mut self) -> Option<i64> {
+        match self.inputs.next() {
+            Some(input) => Some(input),
+            None => {
+                self.finished = true;
+                self.exhaustion.apply(&mut self.exhausted)
+            },
+        }
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+// Asks the callback for each input, so it can react to what the program has output so far
+pub struct IntcodeFnInput<F> {
+    callback: F,
+    exhaustion: IntcodeExhaustion,
+    exhausted: bool,
+}
+
+impl<F> IntcodeFnInput<F>
+where F: FnMut() -> Option<i64>,
+{
+    pub fn new(callback: F) -> Self {
+        Self { callback, exhaustion: IntcodeExhaustion::Suspend, exhausted: false }
+    }
+
+    pub fn with_exhaustion(mut self, exhaustion: IntcodeExhaustion) -> Self {
+        self.exhaustion = exhaustion;
+        self
+    }
+}
+
+impl<F> IntcodeInput for IntcodeFnInput<F>
+where F: FnMut() -> Option<i64>,
+{
+    fn process(&mut self) -> Option<i64> {
+        match (self.callback)() {
+            Some(input) => Some(input),
+            None => self.exhaustion.apply(&mut self.exhausted),
+        }
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+}
+
+// Drains each source in turn, e.g. a phase setting followed by a feedback loop. A source that
+// has nothing yet is waited on; only finished sources are left behind.
+pub struct IntcodeChainInput {
+    sources: VecDeque<Box<dyn IntcodeInput>>,
+    exhaustion: IntcodeExhaustion,
+    exhausted: bool,
+}
+
+impl IntcodeChainInput {
+    pub fn new() -> Self {
+        Self { sources: VecDeque::new(), exhaustion: IntcodeExhaustion::Suspend, exhausted: false }
+    }
+
+    pub fn then<S>(mut self, source: S) -> Self
+    where S: IntcodeInput + 'static
+    {
+        self.sources.push_back(Box::new(source));
+        self
+    }
+
+    pub fn with_exhaustion(mut self, exhaustion: IntcodeExhaustion) -> Self {
+        self.exhaustion = exhaustion;
+        self
+    }
+}
+
+impl IntcodeInput for IntcodeChainInput {
+    fn process(&mut self) -> Option<i64> {
+        while let Some(source) = self.sources.front_mut() {
+            if let Some(input) = source.process() {
+                return Some(input);
+            }
+            if source.is_exhausted() {
+                self.exhausted = true;
+                return None;
+            }
+            if !source.is_finished() {
+                return None;
+            }
+            self.sources.pop_front();
+        }
+
+        self.exhaustion.apply(&mut self.exhausted)
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    fn is_finished(&self) -> bool {
+        self.sources.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{IntcodeMachine, IntcodeHistoryOutput, IntcodeOutput, IntcodeState, IntcodeFault};
+
+    // Outputs the sum of each pair of inputs, forever
+    fn adder() -> Vec<i64> {
+        vec![3,13,3,14,1,13,14,13,4,13,1105,1,0,0,0]
+    }
+
+    #[test]
+    fn test_iterator_input() {
+        let input = IntcodeIteratorInput::new((1..).take(4));
+        let mut machine = IntcodeMachine::new(&adder(), input, IntcodeHistoryOutput::new());
+        machine.run();
+
+        assert_eq!(machine.output_handler().history(), &["3", "7"]);
+        assert_eq!(machine.state(), &IntcodeState::Suspended);
+    }
+
+    #[test]
+    fn test_exhaustion_policies() {
+        let input = IntcodeIteratorInput::new(vec![1].into_iter()).with_exhaustion(IntcodeExhaustion::Default(10));
+        let mut machine = IntcodeMachine::new(&adder(), input, IntcodeHistoryOutput::new());
+        let mut outputs = Vec::new();
+        while outputs.len() < 2 {
+            machine.step().unwrap();
+            outputs = machine.output_handler().history().to_vec();
+        }
+        assert_eq!(outputs, &["11", "20"]);
+
+        let input = IntcodeIteratorInput::new(vec![1].into_iter()).with_exhaustion(IntcodeExhaustion::Fault);
+        let mut machine = IntcodeMachine::new(&adder(), input, IntcodeHistoryOutput::new());
+        assert_eq!(machine.try_run(), Err(IntcodeFault::InputExhausted));
+    }
+
+    #[test]
+    fn test_closure_sees_outputs() {
+        // Feed back each output plus one, starting from 0
+        let output = crate::intcode::output::IntcodeSharedOutput::new();
+        let values = output.clone();
+        let input = IntcodeFnInput::new(move || {
+            let last = values.values().last().copied().unwrap_or(0);
+            if last < 5 { Some(last + 1) } else { None }
+        });
+        let mut machine = IntcodeMachine::new(&[3,7,4,7,1105,1,0,0], input, output.clone());
+        machine.run();
+
+        assert_eq!(output.values(), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_chain_input() {
+        let input = IntcodeChainInput::new()
+            .then(IntcodeIteratorInput::new(vec![5].into_iter()))
+            .then(IntcodeFnInput::new(|| Some(2)));
+        let mut machine = IntcodeMachine::new(&adder(), input, IntcodeHistoryOutput::new());
+        for _ in 0..9 {
+            machine.step().unwrap();
+        }
+
+        assert_eq!(machine.output_handler().history(), &["7", "4"]);
+    }
+
+    #[test]
+    fn test_chain_waits_on_closure() {
+        // Reads a phase, then outputs the phase plus each input after it
+        let program = vec![3,100,3,101,1,100,101,102,4,102,1105,1,2];
+        let feedback = std::rc::Rc::new(std::cell::RefCell::new(VecDeque::new()));
+        let queue = feedback.clone();
+        let input = IntcodeChainInput::new()
+            .then(IntcodeIteratorInput::new(vec![5].into_iter()))
+            .then(IntcodeFnInput::new(move || queue.borrow_mut().pop_front()));
+        let mut machine = IntcodeMachine::new(&program, input, IntcodeHistoryOutput::new());
+
+        machine.run();
+        assert_eq!(machine.state(), &IntcodeState::Suspended);
+        for value in [42, 1] {
+            feedback.borrow_mut().push_back(value);
+            machine.run();
+        }
+
+        assert_eq!(machine.output_handler().history(), &["47", "6"]);
+        assert_eq!(machine.state(), &IntcodeState::Suspended);
+    }
+
+    #[test]
+    fn test_file_input() {
+        let path = std::env::temp_dir().join("intcode_file_input_test");
+        std::fs::write(&path, "1, 2\n3 4\n").unwrap();
+        let input = IntcodeIteratorInput::from_file(&path).unwrap();
+        let mut machine = IntcodeMachine::new(&adder(), input, IntcodeHistoryOutput::new());
+        machine.run();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(machine.output_handler().history(), &["3", "7"]);
+    }
+}
@@ -1,94 +1,206 @@
 use crate::utils::conversion;
-use crate::intcode::IntcodeValue;
+use crate::intcode::{IntcodeValue, IntcodeFault, IntcodeExtensions};
 
 #[derive(Clone, PartialEq)]
 pub enum IntcodeInstruction {
-    Add{x: IntcodeValue, y: IntcodeValue, position: usize},
-    Multiply{x: IntcodeValue, y: IntcodeValue, position: usize},
-    Input{position: usize},
+    Add{x: IntcodeValue, y: IntcodeValue, position: IntcodeValue},
+    Multiply{x: IntcodeValue, y: IntcodeValue, position: IntcodeValue},
+    Input{position: IntcodeValue},
     Output{value: IntcodeValue},
     JumpIfTrue{test_position: IntcodeValue, jump_position: IntcodeValue},
     JumpIfFalse{test_position: IntcodeValue, jump_position: IntcodeValue},
-    IsLessThan{x: IntcodeValue, y: IntcodeValue, position: usize},
-    IsEquals{x: IntcodeValue, y: IntcodeValue, position: usize},
+    IsLessThan{x: IntcodeValue, y: IntcodeValue, position: IntcodeValue},
+    IsEquals{x: IntcodeValue, y: IntcodeValue, position: IntcodeValue},
     SetRelativeBase{offset: IntcodeValue},
     Halt,
+    Extension{opcode: i64, params: Vec<IntcodeValue>, position: Option<IntcodeValue>},
 }
 
 impl IntcodeInstruction {
     pub fn new(opcode_and_param_modes: i64, params: &[i64]) -> Self {
+        Self::try_new(opcode_and_param_modes, params).unwrap_or_else(|fault| panic!("{}", fault))
+    }
+
+    pub fn try_new(opcode_and_param_modes: i64, params: &[i64]) -> Result<Self, IntcodeFault> {
+        Self::try_decode(opcode_and_param_modes, params, &IntcodeExtensions::new())
+    }
+
+    pub fn try_decode(opcode_and_param_modes: i64, params: &[i64], extensions: &IntcodeExtensions) -> Result<Self, IntcodeFault> {
         use IntcodeInstruction::*;
 
+        if opcode_and_param_modes <= 0 {
+            return Err(IntcodeFault::InvalidInstruction(opcode_and_param_modes));
+        }
+
         let digits: Vec<usize> = conversion::i64_into_digits(&opcode_and_param_modes)
             .into_iter()
             .rev()
             .collect();
             
         let opcode = digits[0] + 10 * digits.get(1).unwrap_or(&0);
+        let get_param = |param_position: usize| {
+            params.get(param_position)
+                .copied()
+                .ok_or(IntcodeFault::TruncatedInstruction(opcode_and_param_modes))
+        };
         let get_value = |param_position| {
             let mode = *digits.get(param_position + 2).unwrap_or(&0);
             match mode {
-                0 => IntcodeValue::Position(params[param_position] as usize),
-                1 => IntcodeValue::Immediate(params[param_position]),
-                2 => IntcodeValue::Relative(params[param_position]),
-                _ => panic!("Invalid parameter mode: {}", mode),
+                0 => Ok(IntcodeValue::Position(get_param(param_position)? as usize)),
+                1 => Ok(IntcodeValue::Immediate(get_param(param_position)?)),
+                2 => Ok(IntcodeValue::Relative(get_param(param_position)?)),
+                _ => Err(IntcodeFault::InvalidParameterMode(mode as i64)),
+            }
+        };
+        // Written parameters are addresses, so immediate mode makes no sense for them
+        let get_target = |param_position| {
+            match get_value(param_position)? {
+                IntcodeValue::Immediate(_) => Err(IntcodeFault::InvalidParameterMode(1)),
+                target => Ok(target),
             }
         };
 
-        match opcode {
+        let instruction = match opcode {
             1 => {
                 Add {
-                    x: get_value(0),
-                    y: get_value(1),
-                    position: params[2] as usize,
+                    x: get_value(0)?,
+                    y: get_value(1)?,
+                    position: get_target(2)?,
                 }
             },
             2 =>  {
                 Multiply{ 
-                    x: get_value(0),
-                    y: get_value(1),
-                    position: params[2] as usize,
+                    x: get_value(0)?,
+                    y: get_value(1)?,
+                    position: get_target(2)?,
                 }                
             },
             3 =>  {
-                Input{ position: params[0] as usize }
+                Input{ position: get_target(0)? }
             },
             4 =>  {
                 Output{ 
-                    value: get_value(0)
+                    value: get_value(0)?
                 }
             },
             5 => {
                 JumpIfTrue { 
-                    test_position: get_value(0),
-                    jump_position: get_value(1),
+                    test_position: get_value(0)?,
+                    jump_position: get_value(1)?,
                 }
             },
             6 => {
                 JumpIfFalse { 
-                    test_position: get_value(0),
-                    jump_position: get_value(1),
+                    test_position: get_value(0)?,
+                    jump_position: get_value(1)?,
                 }
             },
             7 => {
                 IsLessThan {
-                    x: get_value(0),
-                    y: get_value(1),
-                    position: params[2] as usize,
+                    x: get_value(0)?,
+                    y: get_value(1)?,
+                    position: get_target(2)?,
                 }
             },
             8 => {
                 IsEquals {
-                    x: get_value(0),
-                    y: get_value(1),
-                    position: params[2] as usize,
+                    x: get_value(0)?,
+                    y: get_value(1)?,
+                    position: get_target(2)?,
                 }
             },
-            9 => SetRelativeBase { offset: get_value(0) },
+            9 => SetRelativeBase { offset: get_value(0)? },
             99 => Halt,
-            _ => panic!("Invalid instruction: {:?}", opcode),
+            _ => match extensions.get(opcode as i64) {
+                Some(extension) => {
+                    let reads = extension.reads();
+                    Extension {
+                        opcode: opcode as i64,
+                        params: (0..reads).map(get_value).collect::<Result<Vec<IntcodeValue>, IntcodeFault>>()?,
+                        position: if extension.writes() { Some(get_target(reads)?) } else { None },
+                    }
+                },
+                None => return Err(IntcodeFault::InvalidInstruction(opcode as i64)),
+            },
+        };
+
+        Ok(instruction)
+    }
+
+    pub fn size(&self) -> usize {
+        use IntcodeInstruction::*;
+
+        match self {
+            Add{..} | Multiply{..} | IsLessThan{..} | IsEquals{..} => 4,
+            JumpIfTrue{..} | JumpIfFalse{..} => 3,
+            Input{..} | Output{..} | SetRelativeBase{..} => 2,
+            Halt => 1,
+            Extension{params, position, ..} => 1 + params.len() + position.iter().count(),
+        }
+    }
+
+    pub fn written_target(&self) -> Option<&IntcodeValue> {
+        use IntcodeInstruction::*;
+
+        match self {
+            Add{position, ..} | Multiply{position, ..} | IsLessThan{position, ..} | IsEquals{position, ..} => Some(position),
+            Input{position} => Some(position),
+            Extension{position, ..} => position.as_ref(),
+            _ => None,
         }
     }
+
+    pub fn written_position(&self, relative_base: usize) -> Option<i64> {
+        self.written_target().and_then(|target| target.address(relative_base).ok().flatten())
+    }
+
+    pub fn opcode(&self) -> i64 {
+        use IntcodeInstruction::*;
+
+        match self {
+            Add{..} => 1,
+            Multiply{..} => 2,
+            Input{..} => 3,
+            Output{..} => 4,
+            JumpIfTrue{..} => 5,
+            JumpIfFalse{..} => 6,
+            IsLessThan{..} => 7,
+            IsEquals{..} => 8,
+            SetRelativeBase{..} => 9,
+            Halt => 99,
+            Extension{opcode, ..} => *opcode,
+        }
+    }
+
+    // In the order they're encoded, written parameter last
+    pub fn params(&self) -> Vec<&IntcodeValue> {
+        use IntcodeInstruction::*;
+
+        match self {
+            Add{x, y, position} | Multiply{x, y, position} | IsLessThan{x, y, position} | IsEquals{x, y, position} => vec![x, y, position],
+            Input{position} => vec![position],
+            Output{value} => vec![value],
+            JumpIfTrue{test_position, jump_position} | JumpIfFalse{test_position, jump_position} => vec![test_position, jump_position],
+            SetRelativeBase{offset} => vec![offset],
+            Halt => vec![],
+            Extension{params, position, ..} => params.iter().chain(position.iter()).collect(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<i64> {
+        let mut words = vec![self.opcode()];
+        for (index, param) in self.params().into_iter().enumerate() {
+            let (mode, value) = match param {
+                IntcodeValue::Position(position) => (0, *position as i64),
+                IntcodeValue::Immediate(value) => (1, *value),
+                IntcodeValue::Relative(offset) => (2, *offset),
+            };
+            words[0] += mode * 10i64.pow(index as u32 + 2);
+            words.push(value);
+        }
+
+        words
+    }
 }
 
 impl std::fmt::Debug for IntcodeInstruction {
@@ -106,6 +218,16 @@ impl std::fmt::Debug for IntcodeInstruction {
             IsEquals{x, y, position} => format!("Eqt {:?} {:?} {:?}", x, y, position),
             SetRelativeBase{offset} => format!("Srb {:?}", offset),
             Halt => format!("Halt"),
+            Extension{opcode, params, position} => {
+                let mut text = format!("Op{}", opcode);
+                for param in params {
+                    text.push_str(&format!(" {:?}", param));
+                }
+                if let Some(position) = position {
+                    text.push_str(&format!(" {:?}", position));
+                }
+                text
+            },
         };
         
         write!(f, "{}", text)
@@ -123,36 +245,45 @@ mod tests {
     fn test_param_modes() {
         assert_eq!(
             IntcodeInstruction::new(1, &[1, 2, 3]), 
-            Add{x: Position(1), y: Position(2), position: 3});
+            Add{x: Position(1), y: Position(2), position: Position(3)});
 
         assert_eq!(
             IntcodeInstruction::new(101, &[4, 5, 6]), 
-            Add{x: Immediate(4), y: Position(5), position: 6});
+            Add{x: Immediate(4), y: Position(5), position: Position(6)});
 
         assert_eq!(
             IntcodeInstruction::new(1001, &[4, 5, 6]), 
-            Add{x: Position(4), y: Immediate(5), position: 6});
+            Add{x: Position(4), y: Immediate(5), position: Position(6)});
 
         assert_eq!(
             IntcodeInstruction::new(1101, &[4, 5, 6]), 
-            Add{x: Immediate(4), y: Immediate(5), position: 6});
+            Add{x: Immediate(4), y: Immediate(5), position: Position(6)});
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        for words in [vec![1101,4,5,6], vec![21002,1,-3,7], vec![3,10], vec![204,-1], vec![1105,0,7], vec![209,4], vec![99]] {
+            let instruction = IntcodeInstruction::new(words[0], &words[1..]);
+            assert_eq!(instruction.encode(), words);
+            assert_eq!(instruction.size(), words.len());
+        }
     }
 
     #[test]
     fn test_add() {
         assert_eq!(
             IntcodeInstruction::new(1, &[0, 1, 2]), 
-            Add{x: Position(0), y: Position(1), position: 2}
+            Add{x: Position(0), y: Position(1), position: Position(2)}
         );
             
         assert_eq!(
             IntcodeInstruction::new(101, &[0, 1, 2]), 
-            Add{x: Immediate(0), y: Position(1), position: 2}
+            Add{x: Immediate(0), y: Position(1), position: Position(2)}
         );
 
         assert_eq!(
             IntcodeInstruction::new(1001, &[0, 1, 2]), 
-            Add{x: Position(0), y: Immediate(1), position: 2}
+            Add{x: Position(0), y: Immediate(1), position: Position(2)}
         );
     }
 
@@ -160,17 +291,17 @@ mod tests {
     fn test_multiply() {
         assert_eq!(
             IntcodeInstruction::new(2, &[0, 1, 2]), 
-            Multiply{x: Position(0), y: Position(1), position: 2}
+            Multiply{x: Position(0), y: Position(1), position: Position(2)}
         );
 
         assert_eq!(
             IntcodeInstruction::new(102, &[0, 1, 2]), 
-            Multiply{x: Immediate(0), y: Position(1), position: 2}
+            Multiply{x: Immediate(0), y: Position(1), position: Position(2)}
         );
 
         assert_eq!(
             IntcodeInstruction::new(1002, &[0, 1, 2]), 
-            Multiply{x: Position(0), y: Immediate(1), position: 2}
+            Multiply{x: Position(0), y: Immediate(1), position: Position(2)}
         );
     }
 
@@ -178,7 +309,7 @@ mod tests {
     fn test_input() {
         assert_eq!(
             IntcodeInstruction::new(3, &[0]), 
-            Input{position: 0}
+            Input{position: Position(0)}
         );
     }
     
@@ -235,17 +366,17 @@ mod tests {
     fn test_less_than() {
         assert_eq!(
             IntcodeInstruction::new(7, &[0, 1, 2]), 
-            IsLessThan{x: Position(0), y: Position(1), position: 2}
+            IsLessThan{x: Position(0), y: Position(1), position: Position(2)}
         );
 
         assert_eq!(
             IntcodeInstruction::new(107, &[0, 1, 2]), 
-            IsLessThan{x: Immediate(0), y: Position(1), position: 2}
+            IsLessThan{x: Immediate(0), y: Position(1), position: Position(2)}
         );
 
         assert_eq!(
             IntcodeInstruction::new(1007, &[0, 1, 2]), 
-            IsLessThan{x: Position(0), y: Immediate(1), position: 2}
+            IsLessThan{x: Position(0), y: Immediate(1), position: Position(2)}
         );
     }
     
@@ -253,17 +384,17 @@ mod tests {
     fn test_equals() {
         assert_eq!(
             IntcodeInstruction::new(8, &[0, 1, 2]), 
-            IsEquals{x: Position(0), y: Position(1), position: 2}
+            IsEquals{x: Position(0), y: Position(1), position: Position(2)}
         );
 
         assert_eq!(
             IntcodeInstruction::new(108, &[0, 1, 2]), 
-            IsEquals{x: Immediate(0), y: Position(1), position: 2}
+            IsEquals{x: Immediate(0), y: Position(1), position: Position(2)}
         );
 
         assert_eq!(
             IntcodeInstruction::new(1008, &[0, 1, 2]), 
-            IsEquals{x: Position(0), y: Immediate(1), position: 2}
+            IsEquals{x: Position(0), y: Immediate(1), position: Position(2)}
         );
     }
 
@@ -289,4 +420,13 @@ mod tests {
     fn test_halt() {
         assert_eq!(IntcodeInstruction::new(99, &[]), Halt); 
     }
+
+    #[test]
+    fn test_faults() {
+        assert_eq!(IntcodeInstruction::try_new(0, &[]), Err(IntcodeFault::InvalidInstruction(0)));
+        assert_eq!(IntcodeInstruction::try_new(-1, &[]), Err(IntcodeFault::InvalidInstruction(-1)));
+        assert_eq!(IntcodeInstruction::try_new(42, &[]), Err(IntcodeFault::InvalidInstruction(42)));
+        assert_eq!(IntcodeInstruction::try_new(301, &[0, 0, 0]), Err(IntcodeFault::InvalidParameterMode(3)));
+        assert_eq!(IntcodeInstruction::try_new(1, &[0, 0]), Err(IntcodeFault::TruncatedInstruction(1)));
+    }
 }
\ No newline at end of file
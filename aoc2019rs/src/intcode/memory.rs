@@ -0,0 +1,260 @@
+use anyhow::{anyhow, bail, Result};
+
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::intcode::{IntcodeMachine, IntcodeProgram, MAX_MEMORY};
+
+const SHADES: &[u8] = b" .:-=+*#%@";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntcodeAccessCounts {
+    reads: Vec<u64>,
+    writes: Vec<u64>,
+}
+
+impl IntcodeAccessCounts {
+    pub fn new(size: usize) -> Self {
+        Self { reads: vec![0; size], writes: vec![0; size] }
+    }
+
+    pub fn record_read(&mut self, address: i64) {
+        if let Some(address) = self.grow(address) {
+            self.reads[address] += 1;
+        }
+    }
+
+    pub fn record_write(&mut self, address: i64) {
+        if let Some(address) = self.grow(address) {
+            self.writes[address] += 1;
+        }
+    }
+
+    // Follows the machine's memory as it grows, but not out to addresses it would fault on
+    fn grow(&mut self, address: i64) -> Option<usize> {
+        if address < 0 || address as usize >= MAX_MEMORY {
+            return None;
+        }
+        let address = address as usize;
+        if address >= self.reads.len() {
+            self.reads.resize(address + 1, 0);
+            self.writes.resize(address + 1, 0);
+        }
+        Some(address)
+    }
+
+    pub fn reads(&self, address: usize) -> u64 {
+        self.reads.get(address).copied().unwrap_or(0)
+    }
+
+    pub fn writes(&self, address: usize) -> u64 {
+        self.writes.get(address).copied().unwrap_or(0)
+    }
+
+    // Everything up to the last address that was accessed at all
+    pub fn len(&self) -> usize {
+        (0..self.reads.len())
+            .rev()
+            .find(|address| self.reads[*address] + self.writes[*address] > 0)
+            .map(|address| address + 1)
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // One character per address, darker for more accesses on a log scale
+    pub fn heatmap(&self, width: usize) -> Result<String> {
+        if width == 0 {
+            bail!("Heatmap width must be at least 1");
+        }
+        let length = self.len();
+        let max = (0..length).map(|address| self.reads(address) + self.writes(address)).max().unwrap_or(0);
+        let mut text = String::new();
+        for row in (0..length).step_by(width) {
+            text.push_str(&format!("{:>6} |", row));
+            for address in row..(row + width).min(length) {
+                let level = shade(self.reads(address) + self.writes(address), max, SHADES.len() - 1);
+                text.push(SHADES[level] as char);
+            }
+            text.push_str("|\n");
+        }
+
+        Ok(text)
+    }
+
+    // Binary PPM with a square of `scale` pixels per address: reads in green, writes in red
+    pub fn ppm(&self, width: usize, scale: usize) -> Result<Vec<u8>> {
+        if width == 0 || scale == 0 {
+            bail!("Image width and scale must be at least 1");
+        }
+        let length = self.len();
+        let max_reads = (0..length).map(|address| self.reads(address)).max().unwrap_or(0);
+        let max_writes = (0..length).map(|address| self.writes(address)).max().unwrap_or(0);
+        let rows = length.div_ceil(width);
+
+        let mut image = format!("P6\n{} {}\n255\n", width * scale, rows * scale).into_bytes();
+        for row in 0..rows {
+            let pixels: Vec<[u8; 3]> = (0..width)
+                .map(|column| {
+                    let address = row * width + column;
+                    let red = shade(self.writes(address), max_writes, 255) as u8;
+                    let green = shade(self.reads(address), max_reads, 255) as u8;
+                    [red, green, if address < length { 32 } else { 0 }]
+                })
+                .collect();
+            for _ in 0..scale {
+                for pixel in &pixels {
+                    for _ in 0..scale {
+                        image.extend_from_slice(pixel);
+                    }
+                }
+            }
+        }
+
+        Ok(image)
+    }
+}
+
+fn shade(count: u64, max: u64, levels: usize) -> usize {
+    if count == 0 || max == 0 {
+        return 0;
+    }
+    let fraction = ((count + 1) as f64).ln() / ((max + 1) as f64).ln();
+    ((fraction * levels as f64).ceil() as usize).clamp(1, levels)
+}
+
+// A run of consecutive addresses that differ between two memory images
+#[derive(Clone, PartialEq)]
+pub struct IntcodeMemoryChange {
+    pub start: usize,
+    pub before: Vec<i64>,
+    pub after: Vec<i64>,
+}
+
+impl std::fmt::Debug for IntcodeMemoryChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.before.len() {
+            1 => write!(f, "{}: {} -> {}", self.start, self.before[0], self.after[0]),
+            length => write!(f, "{}..{}: {:?} -> {:?}", self.start, self.start + length, self.before, self.after),
+        }
+    }
+}
+
+// Memory past the end of the shorter image counts as zero
+pub fn diff(before: &[i64], after: &[i64]) -> Vec<IntcodeMemoryChange> {
+    let mut changes: Vec<IntcodeMemoryChange> = Vec::new();
+    for address in 0..before.len().max(after.len()) {
+        let old = before.get(address).copied().unwrap_or(0);
+        let new = after.get(address).copied().unwrap_or(0);
+        if old == new {
+            continue;
+        }
+
+        match changes.last_mut() {
+            Some(change) if change.start + change.before.len() == address => {
+                change.before.push(old);
+                change.after.push(new);
+            },
+            _ => changes.push(IntcodeMemoryChange { start: address, before: vec![old], after: vec![new] }),
+        }
+    }
+
+    changes
+}
+
+// Usage: heatmap <file in input/> <image.ppm> [inputs...]
+pub fn run_command(args: &[String]) -> Result<String> {
+    if args.len() < 2 {
+        bail!("Usage: heatmap <program file> <image file> [inputs...]");
+    }
+    let program = IntcodeProgram::load_from_args(args)?.code;
+    let inputs = args[2..].iter()
+        .map(|arg| i64::from_str(arg).map_err(|_| anyhow!("Invalid input value: {}", arg)))
+        .collect::<Result<Vec<i64>>>()?;
+
+    let mut machine = IntcodeMachine::new_automated_machine(&program, &inputs);
+    let initial = machine.memory().to_vec();
+    machine.start_tracking();
+    machine.try_run()?;
+    let counts = machine.stop_tracking().unwrap_or_else(|| IntcodeAccessCounts::new(0));
+    fs::write(Path::new(&args[1]), counts.ppm(64, 8)?)?;
+
+    let changes: Vec<String> = diff(&initial, machine.memory()).iter().map(|change| format!("{:?}", change)).collect();
+    Ok(format!("{}\n{}", counts.heatmap(64)?, changes.join("\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let changes = diff(&[1,2,3,4,5], &[1,9,9,4,5,0,7]);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(format!("{:?}", changes[0]), "1..3: [2, 3] -> [9, 9]");
+        assert_eq!(format!("{:?}", changes[1]), "6: 0 -> 7");
+
+        assert!(diff(&[1,2], &[1,2,0]).is_empty());
+    }
+
+    #[test]
+    fn test_diff_between_runs() {
+        let program = vec![3,9,1002,9,2,10,4,10,99,0,0];
+        let mut first = IntcodeMachine::new_automated_machine(&program, &[3]);
+        let mut second = IntcodeMachine::new_automated_machine(&program, &[3, 4]);
+        first.run();
+        second.run();
+
+        assert!(diff(first.memory(), second.memory()).is_empty());
+        let mut third = IntcodeMachine::new_automated_machine(&program, &[5]);
+        third.run();
+        assert_eq!(format!("{:?}", diff(first.memory(), third.memory())), "[9..11: [3, 6] -> [5, 10]]");
+    }
+
+    #[test]
+    fn test_access_tracking() {
+        // Counts a cell down from 3
+        let program = vec![1101,0,3,12,1001,12,-1,12,1005,12,4,99,0];
+        let mut machine = IntcodeMachine::new_automated_machine(&program, &[]);
+        machine.start_tracking();
+        machine.run();
+        let counts = machine.stop_tracking().unwrap();
+
+        assert_eq!((counts.reads(12), counts.writes(12)), (6, 4));
+        assert_eq!(counts.len(), 13);
+        assert_eq!(machine.access_counts(), None);
+    }
+
+    #[test]
+    fn test_access_tracking_follows_memory_growth() {
+        let program = vec![1101,0,7,2000,4,2000,99];
+        let mut machine = IntcodeMachine::new_automated_machine(&program, &[]);
+        machine.start_tracking();
+        machine.run();
+        let counts = machine.stop_tracking().unwrap();
+
+        assert_eq!((counts.reads(2000), counts.writes(2000)), (1, 1));
+        assert_eq!(counts.len(), 2001);
+    }
+
+    #[test]
+    fn test_heatmap_rendering() {
+        let mut counts = IntcodeAccessCounts::new(16);
+        counts.record_write(0);
+        for _ in 0..100 {
+            counts.record_read(5);
+        }
+
+        assert_eq!(counts.heatmap(4).unwrap(), "     0 |:   |\n     4 | @|\n");
+        assert!(counts.heatmap(0).is_err());
+        assert!(counts.ppm(0, 2).is_err());
+
+        let image = counts.ppm(4, 2).unwrap();
+        let header = b"P6\n8 4\n255\n";
+        assert_eq!(&image[..header.len()], header);
+        assert_eq!(image.len(), header.len() + 8 * 4 * 3);
+    }
+}
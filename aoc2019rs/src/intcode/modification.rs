@@ -0,0 +1,262 @@
+use anyhow::{anyhow, Result};
+
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::intcode::{IntcodeMachine, IntcodeInstruction, IntcodeInput, IntcodeOutput, IntcodeProgram, IntcodeState};
+use crate::intcode::differential::DEFAULT_MAX_STEPS;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntcodeRegion {
+    Code,
+    Data,
+    SelfModified,
+    Unused,
+}
+
+// A write to an address that was executed before it, or is executed after it. Every write made
+// before an execution is reported, not just the last one. Instructions are given with the
+// address they start at.
+#[derive(Clone, PartialEq)]
+pub struct IntcodeModification {
+    pub step: usize,
+    pub writer: usize,
+    pub address: usize,
+    pub old: i64,
+    pub new: i64,
+    pub before: Option<(usize, IntcodeInstruction)>,
+    pub after: Option<(usize, IntcodeInstruction)>,
+}
+
+impl IntcodeModification {
+    pub fn is_opcode(&self) -> bool {
+        self.after.as_ref().or(self.before.as_ref())
+            .map(|(start, _)| *start == self.address)
+            .unwrap_or(false)
+    }
+}
+
+impl std::fmt::Debug for IntcodeModification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let role = if self.is_opcode() { "opcode" } else { "operand" };
+        write!(f, "step {}: write at {} changes {} {} from {} to {}", self.step, self.writer, role, self.address, self.old, self.new)?;
+        if let Some((start, instruction)) = &self.before {
+            write!(f, ", previously executed as {:?} at {}", instruction, start)?;
+        }
+        if let Some((start, instruction)) = &self.after {
+            write!(f, ", later executed as {:?} at {}", instruction, start)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct IntcodeModificationReport {
+    pub steps: usize,
+    pub modifications: Vec<IntcodeModification>,
+    pub regions: Vec<(Range<usize>, IntcodeRegion)>,
+}
+
+impl std::fmt::Debug for IntcodeModificationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} steps, {} self-modifying writes", self.steps, self.modifications.len())?;
+        for modification in &self.modifications {
+            writeln!(f, "{:?}", modification)?;
+        }
+        for (range, region) in &self.regions {
+            let name = match region {
+                IntcodeRegion::Code => "code",
+                IntcodeRegion::Data => "data",
+                IntcodeRegion::SelfModified => "self-modified",
+                IntcodeRegion::Unused => "unused",
+            };
+            writeln!(f, "{:>6}..{:<6} {}", range.start, range.end, name)?;
+        }
+        Ok(())
+    }
+}
+
+// Runs the machine until it stops or reaches the step limit, watching every executed and accessed address
+pub fn analyse<I, O>(machine: &mut IntcodeMachine<I, O>, max_steps: usize) -> IntcodeModificationReport
+where I: IntcodeInput,
+      O: IntcodeOutput,
+{
+    let mut executed = BTreeSet::new();
+    let mut accessed = BTreeSet::new();
+    let mut written = BTreeSet::new();
+    let mut last_executed: HashMap<usize, usize> = HashMap::new();
+    let mut instructions: HashMap<usize, IntcodeInstruction> = HashMap::new();
+    // Writes to each address since it was last executed
+    let mut pending: HashMap<usize, Vec<IntcodeModification>> = HashMap::new();
+    let mut modifications = Vec::new();
+
+    let mut steps = 0;
+    while steps < max_steps {
+        let pointer = machine.instruction_pointer();
+        let instruction = match machine.try_decode_instruction(pointer) {
+            Ok(instruction) => instruction,
+            Err(_) => break,
+        };
+
+        for address in pointer..pointer + instruction.size() {
+            executed.insert(address);
+            last_executed.insert(address, pointer);
+            for mut modification in pending.remove(&address).unwrap_or_default() {
+                modification.after = Some((pointer, instruction.clone()));
+                modifications.push(modification);
+            }
+        }
+        instructions.insert(pointer, instruction.clone());
+
+        let relative_base = machine.relative_base();
+        let target = instruction.written_position(relative_base).filter(|address| *address >= 0).map(|address| address as usize);
+        accessed.extend(instruction.params()
+            .into_iter()
+            .filter_map(|param| param.address(relative_base).ok().flatten())
+            .filter(|address| *address >= 0)
+            .map(|address| address as usize));
+        // Memory past the end reads as zero until the write grows it
+        let old = target.map(|address| machine.memory().get(address).copied().unwrap_or(0));
+
+        let result = machine.step();
+        steps += 1;
+        // An input that suspends the machine hasn't written anything yet
+        let finished = machine.instruction_pointer() != pointer || machine.state() == &IntcodeState::Running;
+
+        if let (Some(address), Some(old), Ok(()), true) = (target, old, &result, finished) {
+            written.insert(address);
+            let modification = IntcodeModification {
+                step: steps - 1,
+                writer: pointer,
+                address,
+                old,
+                new: machine.memory().get(address).copied().unwrap_or(0),
+                before: last_executed.get(&address).map(|start| (*start, instructions[start].clone())),
+                after: None,
+            };
+            pending.entry(address).or_default().push(modification);
+        }
+
+        if result.is_err() || machine.state() != &IntcodeState::Running {
+            break;
+        }
+    }
+
+    modifications.extend(pending.into_values().flatten().filter(|modification| modification.before.is_some()));
+    modifications.sort_by_key(|modification| modification.step);
+
+    let end = executed.iter().chain(&accessed).max().map(|address| address + 1).unwrap_or(0);
+    let mut regions: Vec<(Range<usize>, IntcodeRegion)> = Vec::new();
+    for address in 0..end {
+        let region = match (executed.contains(&address), written.contains(&address), accessed.contains(&address)) {
+            (true, true, _) => IntcodeRegion::SelfModified,
+            (true, false, _) => IntcodeRegion::Code,
+            (false, _, true) => IntcodeRegion::Data,
+            (false, _, false) => IntcodeRegion::Unused,
+        };
+        match regions.last_mut() {
+            Some((range, last)) if *last == region => range.end = address + 1,
+            _ => regions.push((address..address + 1, region)),
+        }
+    }
+
+    IntcodeModificationReport { steps, modifications, regions }
+}
+
+// Usage: modifications <file in input/> [inputs...]
+pub fn run_command(args: &[String]) -> Result<String> {
+    let program = IntcodeProgram::load_from_args(args)?.code;
+    let inputs = args[1..].iter()
+        .map(|arg| i64::from_str(arg).map_err(|_| anyhow!("Invalid input value: {}", arg)))
+        .collect::<Result<Vec<i64>>>()?;
+
+    let mut machine = IntcodeMachine::new_automated_machine(&program, &inputs);
+    Ok(format!("{:?}", analyse(&mut machine, DEFAULT_MAX_STEPS)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use IntcodeRegion::*;
+
+    fn report(program: &[i64]) -> IntcodeModificationReport {
+        let mut machine = IntcodeMachine::new_automated_machine(program, &[]);
+        analyse(&mut machine, DEFAULT_MAX_STEPS)
+    }
+
+    #[test]
+    fn test_write_to_later_opcode() {
+        let report = report(&[1002,4,3,4,33]);
+
+        assert_eq!(report.modifications.len(), 1);
+        let modification = &report.modifications[0];
+        assert_eq!((modification.writer, modification.address, modification.old, modification.new), (0, 4, 33, 99));
+        assert_eq!(modification.before, None);
+        assert_eq!(modification.after, Some((4, IntcodeInstruction::Halt)));
+        assert!(modification.is_opcode());
+        assert_eq!(report.regions, vec![(0..4, Code), (4..5, SelfModified)]);
+    }
+
+    #[test]
+    fn test_write_to_executed_operand() {
+        let report = report(&[1,0,0,3,99]);
+
+        assert_eq!(format!("{:?}", report.modifications[0]), "step 0: write at 0 changes operand 3 from 3 to 2, previously executed as Add 0p 0p 3p at 0");
+        assert_eq!(report.regions, vec![(0..3, Code), (3..4, SelfModified), (4..5, Code)]);
+    }
+
+    #[test]
+    fn test_data_writes_are_not_reported() {
+        let report = report(&[1101,1,1,9,4,9,99,0,0,0]);
+
+        assert!(report.modifications.is_empty());
+        assert_eq!(report.regions, vec![(0..7, Code), (7..9, Unused), (9..10, Data)]);
+    }
+
+    #[test]
+    fn test_every_write_before_execution_is_kept() {
+        // Writes 1 and then 99 to address 10 before executing it
+        let report = report(&[1101,0,1,10,1101,0,99,10,104,5,0]);
+
+        assert_eq!(report.modifications.len(), 2);
+        assert_eq!(report.modifications.iter().map(|modification| (modification.old, modification.new)).collect::<Vec<_>>(), vec![(0, 1), (1, 99)]);
+        assert!(report.modifications.iter().all(|modification| modification.after == Some((10, IntcodeInstruction::Halt))));
+    }
+
+    #[test]
+    fn test_write_past_the_end_of_memory() {
+        let report = report(&[1101,0,99,2000,1105,1,2000]);
+
+        assert_eq!(report.modifications.len(), 1);
+        let modification = &report.modifications[0];
+        assert_eq!((modification.address, modification.old, modification.new), (2000, 0, 99));
+        assert_eq!(modification.after, Some((2000, IntcodeInstruction::Halt)));
+    }
+
+    #[test]
+    fn test_suspended_input_is_not_a_write() {
+        for program in [vec![3,1,99], vec![3,2000,99]] {
+            let mut machine = IntcodeMachine::new_blocking_machine(&program);
+            let report = analyse(&mut machine, DEFAULT_MAX_STEPS);
+
+            assert!(report.modifications.is_empty());
+            assert_eq!(report.steps, 1);
+        }
+    }
+
+    #[test]
+    fn test_day2_program() {
+        let mut program = IntcodeProgram::load("input/input2").unwrap().code;
+        program[1] = 12;
+        program[2] = 2;
+        let report = report(&program);
+
+        // Every instruction writes to its own last operand except the final one, which stores the answer
+        assert_eq!(report.modifications.len(), 35);
+        let last = report.modifications.last().unwrap();
+        assert_eq!((last.address, last.new), (0, 7594646));
+        assert!(last.is_opcode());
+        assert_eq!(report.regions[0..3], [(0..1, SelfModified), (1..3, Code), (3..4, SelfModified)]);
+    }
+}
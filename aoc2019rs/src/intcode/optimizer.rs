@@ -0,0 +1,357 @@
+use anyhow::{bail, Result};
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::intcode::{IntcodeMachine, IntcodeInstruction, IntcodeValue, IntcodeState, IntcodeOutput};
+use crate::intcode::differential::DEFAULT_MAX_STEPS;
+
+#[derive(Clone, PartialEq)]
+pub struct IntcodeRewrite {
+    pub position: usize,
+    pub before: IntcodeInstruction,
+    // None when the instruction is removed
+    pub after: Option<IntcodeInstruction>,
+}
+
+impl std::fmt::Debug for IntcodeRewrite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.after {
+            Some(after) => write!(f, "{}: {:?} => {:?}", self.position, self.before, after),
+            None => write!(f, "{}: {:?} => removed", self.position, self.before),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct IntcodeOptimization {
+    pub program: Vec<i64>,
+    pub rewrites: Vec<IntcodeRewrite>,
+    pub relocated: bool,
+}
+
+// Rewrites instructions in place by default. With relocation enabled it also removes instructions
+// that do nothing, but only when every address in the program can be found and shifted: no
+// relative mode, no computed jumps and no code that is read or written as data.
+//
+// Instructions whose words are accessed as data, whether statically or in any of the sample runs,
+// are never touched. The result is checked by running both programs on every sample, comparing
+// outputs and, unless code was moved, every word of memory outside the rewritten instructions.
+#[derive(Default)]
+pub struct IntcodeOptimizer {
+    relocate: bool,
+    samples: Vec<Vec<i64>>,
+}
+
+impl IntcodeOptimizer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn relocate(mut self, relocate: bool) -> Self {
+        self.relocate = relocate;
+        self
+    }
+
+    pub fn sample(mut self, inputs: &[i64]) -> Self {
+        self.samples.push(inputs.to_vec());
+        self
+    }
+
+    pub fn optimize(&self, program: &[i64]) -> Result<IntcodeOptimization> {
+        let samples = if self.samples.is_empty() { vec![Vec::new()] } else { self.samples.clone() };
+        let traces: Vec<Trace> = samples.iter().map(|inputs| Trace::run(program, inputs)).collect();
+
+        let executed = traces.iter().flat_map(|trace| trace.executed.iter().copied());
+        let (code, dynamic) = discover(program, executed);
+
+        let mut accessed: BTreeSet<usize> = traces.iter().flat_map(|trace| trace.accessed.iter().copied()).collect();
+        for instruction in code.values() {
+            for param in instruction.params() {
+                if let IntcodeValue::Position(position) = param {
+                    accessed.insert(*position);
+                }
+            }
+        }
+
+        let untouched = |position: usize, instruction: &IntcodeInstruction| {
+            (position..position + instruction.size()).all(|word| !accessed.contains(&word))
+        };
+        let can_relocate = self.relocate && !dynamic && code.iter().all(|(position, instruction)| untouched(*position, instruction));
+
+        let mut rewrites = Vec::new();
+        for (position, instruction) in &code {
+            if !untouched(*position, instruction) {
+                continue;
+            }
+
+            let simplified = simplify(instruction);
+            let current = simplified.as_ref().unwrap_or(instruction);
+            if can_relocate && is_removable(*position, current) {
+                rewrites.push(IntcodeRewrite { position: *position, before: instruction.clone(), after: None });
+            } else if simplified.is_some() {
+                rewrites.push(IntcodeRewrite { position: *position, before: instruction.clone(), after: simplified });
+            }
+        }
+
+        let optimized = apply(program, &code, &rewrites);
+        let relocated = rewrites.iter().any(|rewrite| rewrite.after.is_none());
+        let rewritten: BTreeSet<usize> = rewrites.iter()
+            .flat_map(|rewrite| rewrite.position..rewrite.position + rewrite.before.size())
+            .collect();
+        for (inputs, trace) in samples.iter().zip(&traces) {
+            let result = Trace::run(&optimized, inputs);
+            if let Some(difference) = trace.difference(&result, if relocated { None } else { Some(&rewritten) }) {
+                bail!("Optimized program diverged with inputs {:?}: {}", inputs, difference);
+            }
+        }
+
+        Ok(IntcodeOptimization { program: optimized, rewrites, relocated })
+    }
+}
+
+struct Trace {
+    halted: bool,
+    outputs: Vec<String>,
+    memory: Vec<i64>,
+    executed: BTreeSet<usize>,
+    accessed: BTreeSet<usize>,
+}
+
+impl Trace {
+    fn run(program: &[i64], inputs: &[i64]) -> Self {
+        let mut machine = IntcodeMachine::new_automated_machine(program, inputs);
+        let mut executed = BTreeSet::new();
+        let mut accessed = BTreeSet::new();
+
+        while machine.instruction_count() < DEFAULT_MAX_STEPS {
+            let position = machine.instruction_pointer();
+            if let Ok(instruction) = machine.try_decode_instruction(position) {
+                executed.insert(position);
+                let addresses = instruction.params()
+                    .into_iter()
+                    .filter_map(|param| param.address(machine.relative_base()).ok().flatten())
+                    .filter(|address| *address >= 0);
+                accessed.extend(addresses.map(|address| address as usize));
+            }
+
+            if machine.step().is_err() || machine.state() != &IntcodeState::Running {
+                break;
+            }
+        }
+
+        Self {
+            halted: machine.state() == &IntcodeState::Halted,
+            outputs: machine.output_handler().history().to_vec(),
+            memory: machine.memory().to_vec(),
+            executed,
+            accessed,
+        }
+    }
+
+    // Memory is only compared when given the addresses that are allowed to change
+    fn difference(&self, other: &Trace, ignored: Option<&BTreeSet<usize>>) -> Option<String> {
+        if self.halted != other.halted || self.outputs != other.outputs {
+            return Some(format!("outputs {:?} became {:?}", self.outputs, other.outputs));
+        }
+
+        let ignored = ignored?;
+        let word = |memory: &[i64], address: usize| memory.get(address).copied().unwrap_or(0);
+        (0..self.memory.len().max(other.memory.len()))
+            .find(|address| !ignored.contains(address) && word(&self.memory, *address) != word(&other.memory, *address))
+            .map(|address| format!("memory[{}] {} became {}", address, word(&self.memory, address), word(&other.memory, address)))
+    }
+}
+
+// Follows constant jumps from the start and every executed address. The flag is set when some
+// address can't be known statically.
+fn discover<E>(program: &[i64], executed: E) -> (BTreeMap<usize, IntcodeInstruction>, bool)
+where E: Iterator<Item = usize>
+{
+    let mut code = BTreeMap::new();
+    let mut dynamic = false;
+    let mut pending: Vec<usize> = std::iter::once(0).chain(executed).collect();
+
+    while let Some(position) = pending.pop() {
+        if position >= program.len() || code.contains_key(&position) {
+            continue;
+        }
+        let instruction = match IntcodeInstruction::try_new(program[position], &program[position + 1..]) {
+            Ok(instruction) => instruction,
+            Err(_) => continue,
+        };
+
+        let next = position + instruction.size();
+        dynamic |= instruction.params().iter().any(|param| matches!(param, IntcodeValue::Relative(_)));
+        match &instruction {
+            IntcodeInstruction::Halt => {},
+            IntcodeInstruction::JumpIfTrue{test_position, jump_position} | IntcodeInstruction::JumpIfFalse{test_position, jump_position} => {
+                match jump_position {
+                    IntcodeValue::Immediate(target) if *target >= 0 => pending.push(*target as usize),
+                    _ => dynamic = true,
+                }
+                let jumps_if_zero = matches!(instruction, IntcodeInstruction::JumpIfFalse{..});
+                let always_taken = matches!(test_position, IntcodeValue::Immediate(value) if (*value == 0) == jumps_if_zero);
+                if !always_taken {
+                    pending.push(next);
+                }
+            },
+            _ => pending.push(next),
+        }
+        code.insert(position, instruction);
+    }
+
+    (code, dynamic)
+}
+
+// A same-sized equivalent: constant operations become a copy of the result and multiplying by
+// one becomes a copy
+fn simplify(instruction: &IntcodeInstruction) -> Option<IntcodeInstruction> {
+    use IntcodeInstruction::*;
+    use IntcodeValue::Immediate;
+
+    let copy = |value: IntcodeValue, position: &IntcodeValue| Add{x: value, y: Immediate(0), position: position.clone()};
+    let simplified = match instruction {
+        Add{x: Immediate(x), y: Immediate(y), position} => copy(Immediate(x.checked_add(*y)?), position),
+        Multiply{x: Immediate(x), y: Immediate(y), position} => copy(Immediate(x.checked_mul(*y)?), position),
+        IsLessThan{x: Immediate(x), y: Immediate(y), position} => copy(Immediate((x < y) as i64), position),
+        IsEquals{x: Immediate(x), y: Immediate(y), position} => copy(Immediate((x == y) as i64), position),
+        Multiply{x, y: Immediate(1), position} | Multiply{x: Immediate(1), y: x, position} => copy(x.clone(), position),
+        _ => return None,
+    };
+
+    if &simplified == instruction {
+        None
+    } else {
+        Some(simplified)
+    }
+}
+
+fn is_removable(position: usize, instruction: &IntcodeInstruction) -> bool {
+    use IntcodeInstruction::*;
+    use IntcodeValue::Immediate;
+
+    match instruction {
+        JumpIfTrue{jump_position: Immediate(target), ..} | JumpIfFalse{jump_position: Immediate(target), ..}
+            if *target == (position + instruction.size()) as i64 => true,
+        JumpIfTrue{test_position: Immediate(0), ..} => true,
+        JumpIfFalse{test_position: Immediate(value), ..} => *value != 0,
+        Add{x, y: Immediate(0), position} => x == position,
+        _ => false,
+    }
+}
+
+fn apply(program: &[i64], code: &BTreeMap<usize, IntcodeInstruction>, rewrites: &[IntcodeRewrite]) -> Vec<i64> {
+    let mut output = program.to_vec();
+    for rewrite in rewrites {
+        if let Some(after) = &rewrite.after {
+            let words = after.encode();
+            output[rewrite.position..rewrite.position + words.len()].copy_from_slice(&words);
+        }
+    }
+
+    let removed: BTreeSet<usize> = rewrites.iter()
+        .filter(|rewrite| rewrite.after.is_none())
+        .flat_map(|rewrite| rewrite.position..rewrite.position + rewrite.before.size())
+        .collect();
+    if removed.is_empty() {
+        return output;
+    }
+
+    // Addresses past the end of the program are scratch memory and stay where they are
+    let relocate = |address: usize| match address < program.len() {
+        true => address - removed.range(..address).count(),
+        false => address,
+    };
+    for position in code.keys().filter(|position| !removed.contains(position)) {
+        let instruction = IntcodeInstruction::new(output[*position], &output[*position + 1..]);
+        let is_jump = matches!(instruction, IntcodeInstruction::JumpIfTrue{..} | IntcodeInstruction::JumpIfFalse{..});
+        let mut words = instruction.encode();
+        for (index, param) in instruction.params().into_iter().enumerate() {
+            match param {
+                IntcodeValue::Position(address) => words[index + 1] = relocate(*address) as i64,
+                IntcodeValue::Immediate(target) if is_jump && index == 1 => words[index + 1] = relocate(*target as usize) as i64,
+                _ => {},
+            }
+        }
+        output[*position..*position + words.len()].copy_from_slice(&words);
+    }
+
+    output.into_iter()
+        .enumerate()
+        .filter(|(position, _)| !removed.contains(position))
+        .map(|(_, word)| word)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_place_rewrites() {
+        let program = vec![1101,2,3,12,1002,12,1,13,4,13,99];
+        let optimization = IntcodeOptimizer::new().relocate(true).optimize(&program).unwrap();
+
+        assert_eq!(optimization.program, vec![1101,5,0,12,1001,12,0,13,4,13,99]);
+        assert_eq!(format!("{:?}", optimization.rewrites[0]), "0: Add 2i 3i 12p => Add 5i 0i 12p");
+        assert!(!optimization.relocated);
+    }
+
+    #[test]
+    fn test_written_code_is_left_alone() {
+        // The first instruction patches the second one's first operand
+        let program = vec![1101,20,22,5,1101,1,1,11,4,11,99];
+        let optimization = IntcodeOptimizer::new().optimize(&program).unwrap();
+
+        assert_eq!(optimization.rewrites.iter().map(|rewrite| rewrite.position).collect::<Vec<usize>>(), vec![0]);
+        assert_eq!(&optimization.program[4..8], &[1101,1,1,11]);
+    }
+
+    #[test]
+    fn test_relocation_removes_no_ops() {
+        // A jump to the next instruction, a jump that's never taken and a copy onto itself
+        let program = vec![3,20,1105,1,5,1106,1,0,1002,20,1,20,1005,20,17,104,0,4,20,99,0];
+        let optimization = IntcodeOptimizer::new().relocate(true).sample(&[5]).sample(&[0]).optimize(&program).unwrap();
+
+        assert!(optimization.relocated);
+        assert_eq!(optimization.rewrites.len(), 3);
+        assert_eq!(optimization.program, vec![3,10,1005,10,7,104,0,4,10,99,0]);
+    }
+
+    #[test]
+    fn test_relative_mode_prevents_relocation() {
+        let program = vec![109,1,1105,1,5,204,-1,99];
+        let optimization = IntcodeOptimizer::new().relocate(true).optimize(&program).unwrap();
+
+        assert!(!optimization.relocated);
+        assert_eq!(optimization.program, program);
+    }
+
+    #[test]
+    fn test_memory_differences_outside_rewrites_diverge() {
+        let trace = |memory: Vec<i64>| Trace {
+            halted: true,
+            outputs: vec![String::from("1")],
+            memory,
+            executed: BTreeSet::new(),
+            accessed: BTreeSet::new(),
+        };
+        let rewritten = BTreeSet::from([0, 1, 2, 3]);
+
+        assert_eq!(trace(vec![1101,2,3,5,99,5]).difference(&trace(vec![1101,5,0,5,99,5]), Some(&rewritten)), None);
+        assert_eq!(trace(vec![1101,2,3,5,99,5]).difference(&trace(vec![1101,5,0,5,99,6]), Some(&rewritten)), Some(String::from("memory[5] 5 became 6")));
+        assert_eq!(trace(vec![1101,2,3,5,99,5]).difference(&trace(vec![1101,5,0,5,99,6]), None), None);
+        assert_eq!(trace(vec![99]).difference(&trace(vec![99,0,0]), Some(&rewritten)), None);
+    }
+
+    #[test]
+    fn test_day9_program() {
+        let program = crate::intcode::IntcodeProgram::load("input/input9").unwrap().code;
+        let optimization = IntcodeOptimizer::new().relocate(true).sample(&[1]).optimize(&program).unwrap();
+
+        // The self-test reads its first instruction as data
+        assert!(!optimization.relocated);
+        assert_eq!(&optimization.program[0..4], &program[0..4]);
+        assert!(!optimization.rewrites.is_empty());
+    }
+}
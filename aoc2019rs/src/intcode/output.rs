@@ -1,3 +1,11 @@
+use anyhow::Result;
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+
 pub trait IntcodeOutput {
     fn process(&mut self, value: i64);
     fn history(&self) -> &[String];
@@ -44,3 +52,220 @@ impl IntcodeOutput for IntcodeHistoryOutput {
         &self.history
     }
 }
+
+
+// Clones share the recorded values, so a handle kept outside the machine (e.g. by an input
+// closure) sees outputs as they happen
+#[derive(Clone)]
+pub struct IntcodeSharedOutput {
+    values: Rc<RefCell<Vec<i64>>>,
+    history: Vec<String>,
+}
+
+impl IntcodeSharedOutput {
+    pub fn new() -> Self {
+        Self { values: Rc::new(RefCell::new(Vec::new())), history: Vec::new() }
+    }
+
+    pub fn values(&self) -> Vec<i64> {
+        self.values.borrow().clone()
+    }
+}
+
+impl IntcodeOutput for IntcodeSharedOutput {
+    fn process(&mut self, value: i64) {
+        self.values.borrow_mut().push(value);
+        self.history.push(format!("{}", value));
+    }
+
+    fn history(&self) -> &[String] {
+        &self.history
+    }
+}
+
+pub struct IntcodeFnOutput<F> {
+    callback: F,
+    history: Vec<String>,
+}
+
+impl<F> IntcodeFnOutput<F>
+where F: FnMut(i64),
+{
+    pub fn new(callback: F) -> Self {
+        Self { callback, history: Vec::new() }
+    }
+}
+
+impl<F> IntcodeOutput for IntcodeFnOutput<F>
+where F: FnMut(i64),
+{
+    fn process(&mut self, value: i64) {
+        (self.callback)(value);
+        self.history.push(format!("{}", value));
+    }
+
+    fn history(&self) -> &[String] {
+        &self.history
+    }
+}
+
+// Hands outputs to the callback in fixed-size groups, e.g. x/y/tile triples or packets
+pub struct IntcodeGroupedOutput<F> {
+    size: usize,
+    pending: Vec<i64>,
+    callback: F,
+    history: Vec<String>,
+}
+
+impl<F> IntcodeGroupedOutput<F>
+where F: FnMut(&[i64]),
+{
+    pub fn new(size: usize, callback: F) -> Self {
+        assert!(size > 0, "Output groups must not be empty");
+        Self { size, pending: Vec::with_capacity(size), callback, history: Vec::new() }
+    }
+
+    pub fn pending(&self) -> &[i64] {
+        &self.pending
+    }
+}
+
+impl<F> IntcodeOutput for IntcodeGroupedOutput<F>
+where F: FnMut(&[i64]),
+{
+    fn process(&mut self, value: i64) {
+        self.pending.push(value);
+        self.history.push(format!("{}", value));
+        if self.pending.len() == self.size {
+            (self.callback)(&self.pending);
+            self.pending.clear();
+        }
+    }
+
+    fn history(&self) -> &[String] {
+        &self.history
+    }
+}
+
+pub struct IntcodeTeeOutput {
+    sinks: Vec<Box<dyn IntcodeOutput>>,
+    history: Vec<String>,
+}
+
+impl IntcodeTeeOutput {
+    pub fn new() -> Self {
+        Self { sinks: Vec::new(), history: Vec::new() }
+    }
+
+    pub fn with<S>(mut self, sink: S) -> Self
+    where S: IntcodeOutput + 'static
+    {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+}
+
+impl IntcodeOutput for IntcodeTeeOutput {
+    fn process(&mut self, value: i64) {
+        for sink in self.sinks.iter_mut() {
+            sink.process(value);
+        }
+        self.history.push(format!("{}", value));
+    }
+
+    fn history(&self) -> &[String] {
+        &self.history
+    }
+}
+
+// One value per line, flushed as it is written so the file is usable as a trace of a crashed run.
+// Writing stops at the first error, which is kept for the caller to check after the run.
+pub struct IntcodeFileOutput {
+    file: File,
+    history: Vec<String>,
+    error: Option<std::io::Error>,
+}
+
+impl IntcodeFileOutput {
+    pub fn create(path: &Path) -> Result<Self> {
+        Ok(Self { file: File::create(path)?, history: Vec::new(), error: None })
+    }
+
+    pub fn error(&self) -> Option<&std::io::Error> {
+        self.error.as_ref()
+    }
+}
+
+impl IntcodeOutput for IntcodeFileOutput {
+    fn process(&mut self, value: i64) {
+        if self.error.is_none() {
+            if let Err(error) = writeln!(self.file, "{}", value) {
+                self.error = Some(error);
+            }
+        }
+        self.history.push(format!("{}", value));
+    }
+
+    fn history(&self) -> &[String] {
+        &self.history
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{IntcodeMachine, IntcodePresetInput};
+
+    // Outputs 1 to 7
+    fn run<O: IntcodeOutput>(output: O) -> O {
+        let program = vec![104,1,104,2,104,3,104,4,104,5,104,6,104,7,99];
+        let mut machine = IntcodeMachine::new(&program, IntcodePresetInput::new(&[]), output);
+        machine.run();
+        let (_, _, _, output) = machine.teardown();
+        output
+    }
+
+    #[test]
+    fn test_closure_output() {
+        let mut total = 0;
+        run(IntcodeFnOutput::new(|value| total += value));
+        assert_eq!(total, 28);
+    }
+
+    #[test]
+    fn test_grouped_output() {
+        let mut groups = Vec::new();
+        let output = run(IntcodeGroupedOutput::new(3, |group: &[i64]| groups.push(group.to_vec())));
+
+        assert_eq!(output.pending(), &[7]);
+        assert_eq!(output.history().len(), 7);
+        drop(output);
+        assert_eq!(groups, vec![vec![1, 2, 3], vec![4, 5, 6]]);
+    }
+
+    #[test]
+    fn test_tee_and_file_output() {
+        let path = std::env::temp_dir().join("intcode_tee_output_test");
+        let shared = IntcodeSharedOutput::new();
+        let tee = IntcodeTeeOutput::new()
+            .with(IntcodeHistoryOutput::new())
+            .with(shared.clone())
+            .with(IntcodeFileOutput::create(&path).unwrap());
+        let tee = run(tee);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(tee.last_output().unwrap(), "7");
+        assert_eq!(shared.values(), vec![1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(contents, "1\n2\n3\n4\n5\n6\n7\n");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_file_output_keeps_write_error() {
+        let output = run(IntcodeFileOutput::create(Path::new("/dev/full")).unwrap());
+
+        assert_eq!(output.history().len(), 7);
+        assert_eq!(output.error().map(|error| error.raw_os_error()), Some(Some(28)));
+    }
+}
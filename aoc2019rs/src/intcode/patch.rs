@@ -0,0 +1,296 @@
+use anyhow::{anyhow, bail, Result};
+
+use std::collections::BTreeMap;
+
+use crate::intcode::{IntcodeExtensions, IntcodeInput, IntcodeInstruction, IntcodeMachine, IntcodeOutput, IntcodeValue};
+use crate::intcode::assembly::assemble;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntcodePatch {
+    pub address: usize,
+    pub words: Vec<i64>,
+}
+
+impl IntcodePatch {
+    pub fn end(&self) -> usize {
+        self.address + self.words.len()
+    }
+}
+
+// A list of patches applied in order, either built in code or parsed from text like
+//
+//   # free play
+//   0: 2
+//   # skip the ball check
+//   1200: nop 3
+//   1300: Add 1p 2i 3; Out 3p
+//
+// Each line replaces the words starting at an address with comma-separated values, `nop <size>`
+// or assembly instructions separated by `;`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IntcodePatchSet {
+    patches: Vec<IntcodePatch>,
+}
+
+impl IntcodePatchSet {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn patch(mut self, address: usize, words: &[i64]) -> Self {
+        self.patches.push(IntcodePatch { address, words: words.to_vec() });
+        self
+    }
+
+    pub fn nop(self, address: usize, size: usize) -> Self {
+        let words = nop_words(size).expect("Intcode has no single word instruction");
+        self.patch(address, &words)
+    }
+
+    pub fn parse(text: &str, extensions: &IntcodeExtensions) -> Result<Self> {
+        let mut patch_set = Self::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let patch = parse_line(line, extensions).map_err(|error| anyhow!("Line {}: {}", number + 1, error))?;
+            patch_set.patches.push(patch);
+        }
+
+        Ok(patch_set)
+    }
+
+    pub fn patches(&self) -> &[IntcodePatch] {
+        &self.patches
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patches.is_empty()
+    }
+
+    // Patches the program in place, growing it if a patch runs past the end. Returns the patch
+    // set that reverts the change.
+    pub fn apply(&self, program: &mut Vec<i64>) -> Result<IntcodePatchSet> {
+        self.apply_with(program, &IntcodeExtensions::new())
+    }
+
+    // Same for a machine that may already be running; patches can't reach past its memory
+    pub fn apply_to<I, O>(&self, machine: &mut IntcodeMachine<I, O>) -> Result<IntcodePatchSet>
+    where I: IntcodeInput,
+          O: IntcodeOutput,
+    {
+        let size = machine.memory().len();
+        if let Some(patch) = self.patches.iter().find(|patch| patch.end() > size) {
+            bail!("Patch at {}..{} is outside the machine's {} words of memory", patch.address, patch.end(), size);
+        }
+
+        let mut memory = machine.memory().to_vec();
+        let revert = self.apply_with(&mut memory, machine.extensions())?;
+        for patch in &self.patches {
+            for (offset, word) in memory[patch.address..patch.end()].iter().enumerate() {
+                machine.write_memory(patch.address + offset, *word);
+            }
+        }
+
+        Ok(revert)
+    }
+
+    fn apply_with(&self, program: &mut Vec<i64>, extensions: &IntcodeExtensions) -> Result<IntcodePatchSet> {
+        let mut patched = program.clone();
+        let mut reverts = Vec::new();
+        for patch in &self.patches {
+            if patched.len() < patch.end() {
+                patched.resize(patch.end(), 0);
+            }
+            let before = patched.clone();
+            reverts.push(IntcodePatch { address: patch.address, words: before[patch.address..patch.end()].to_vec() });
+            patched[patch.address..patch.end()].copy_from_slice(&patch.words);
+            validate(&before, &patched, patch, extensions)?;
+        }
+
+        *program = patched;
+        reverts.reverse();
+        Ok(IntcodePatchSet { patches: reverts })
+    }
+}
+
+impl std::fmt::Display for IntcodePatchSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for patch in &self.patches {
+            let words: Vec<String> = patch.words.iter().map(|word| word.to_string()).collect();
+            writeln!(f, "{}: {}", patch.address, words.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_line(line: &str, extensions: &IntcodeExtensions) -> Result<IntcodePatch> {
+    let (address, content) = line.split_once(':').ok_or(anyhow!("Expected <address>: <words>"))?;
+    let address = address.trim().parse::<usize>().map_err(|_| anyhow!("Invalid address: {}", address.trim()))?;
+    let content = content.trim();
+
+    let words = if let Some(size) = content.strip_prefix("nop ") {
+        let size = size.trim().parse::<usize>().map_err(|_| anyhow!("Invalid nop size: {}", size.trim()))?;
+        nop_words(size).ok_or(anyhow!("Can't fill a single word with a nop"))?
+    } else if let Ok(words) = content.split(',').map(|word| word.trim().parse::<i64>()).collect::<Result<Vec<i64>, _>>() {
+        words
+    } else {
+        assemble(&content.replace(';', "\n"), extensions)?
+    };
+
+    if words.is_empty() {
+        bail!("Patch at {} is empty", address);
+    }
+    Ok(IntcodePatch { address, words })
+}
+
+// `Srb 0i` and `JmT 0i 0i` do nothing, and together they cover every size from 2 up
+fn nop_words(size: usize) -> Option<Vec<i64>> {
+    if size < 2 {
+        return None;
+    }
+    let mut words = Vec::new();
+    if size % 2 == 1 {
+        words.extend_from_slice(&[1105, 0, 0]);
+    }
+    while words.len() < size {
+        words.extend_from_slice(&[109, 0]);
+    }
+    Some(words)
+}
+
+// Instruction boundaries come from following the code from address 0, so data is free to
+// change to anything. A patch may rewrite operands or swap an opcode for one of the same size, but an instruction
+// running past its end has to keep its size, so everything from there on decodes as before.
+fn validate(before: &[i64], after: &[i64], patch: &IntcodePatch, extensions: &IntcodeExtensions) -> Result<()> {
+    let old = instruction_spans(before, extensions);
+    let new = instruction_spans(after, extensions);
+    let end = patch.end();
+
+    let crosses = |(start, size): &&(usize, usize)| *start < end && start + size > end;
+    let crossing = old.iter().filter(crosses).find(|span| !new.contains(span))
+        .or_else(|| new.iter().filter(crosses).find(|span| !old.contains(span)));
+    if let Some((start, size)) = crossing {
+        bail!("Patch at {}..{} splits the instruction at {}..{}", patch.address, end, start, start + size);
+    }
+    Ok(())
+}
+
+// Every instruction reachable from address 0 through the next instruction or a constant jump
+fn instruction_spans(program: &[i64], extensions: &IntcodeExtensions) -> Vec<(usize, usize)> {
+    let mut spans = BTreeMap::new();
+    let mut pending = vec![0];
+    while let Some(position) = pending.pop() {
+        if position >= program.len() || spans.contains_key(&position) {
+            continue;
+        }
+        let instruction = match IntcodeInstruction::try_decode(program[position], &program[position+1..], extensions) {
+            Ok(instruction) => instruction,
+            Err(_) => continue,
+        };
+        spans.insert(position, instruction.size());
+
+        match &instruction {
+            IntcodeInstruction::Halt => continue,
+            IntcodeInstruction::JumpIfTrue{jump_position: IntcodeValue::Immediate(target), ..}
+            | IntcodeInstruction::JumpIfFalse{jump_position: IntcodeValue::Immediate(target), ..} if *target >= 0 => {
+                pending.push(*target as usize);
+            },
+            _ => {},
+        }
+        pending.push(position + instruction.size());
+    }
+
+    spans.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_and_revert() {
+        let original = vec![1,0,0,3,2,3,11,0,99,30,40,50];
+        let mut program = original.clone();
+        let revert = IntcodePatchSet::new().patch(1, &[9, 10]).patch(13, &[7]).apply(&mut program).unwrap();
+
+        assert_eq!(program, vec![1,9,10,3,2,3,11,0,99,30,40,50,0,7]);
+        assert_eq!(revert.to_string(), "13: 0\n1: 0,0\n");
+
+        revert.apply(&mut program).unwrap();
+        assert_eq!(program[..original.len()], original[..]);
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = "# operands\n1: 12, 2\n\n4: nop 4 # skip the multiply\n8: Out 0p; Halt\n";
+        let patch_set = IntcodePatchSet::parse(text, &IntcodeExtensions::new()).unwrap();
+
+        assert_eq!(patch_set.to_string(), "1: 12,2\n4: 109,0,109,0\n8: 4,0,99\n");
+        assert_eq!(IntcodePatchSet::parse("1: 2\nx: 3", &IntcodeExtensions::new()).unwrap_err().to_string(), "Line 2: Invalid address: x");
+        assert!(IntcodePatchSet::parse("1: nop 1", &IntcodeExtensions::new()).is_err());
+    }
+
+    #[test]
+    fn test_nop_sizes() {
+        for size in 2..8 {
+            let mut program = vec![99; size];
+            program.push(99);
+            let mut machine = IntcodeMachine::new_automated_machine(&program, &[]);
+            IntcodePatchSet::new().nop(0, size).apply_to(&mut machine).unwrap();
+            machine.run();
+            assert_eq!(machine.instruction_pointer(), size);
+        }
+    }
+
+    #[test]
+    fn test_split_instructions_are_rejected() {
+        let program = vec![1101,1,2,7,4,7,99,0];
+
+        // Replacing an Add with a shorter Out leaves its last operand behind
+        let mut patched = program.clone();
+        let error = IntcodePatchSet::new().patch(0, &[104, 5]).apply(&mut patched).unwrap_err();
+        assert_eq!(error.to_string(), "Patch at 0..2 splits the instruction at 0..4");
+        assert_eq!(patched, program);
+
+        // A longer instruction would swallow the start of the next one
+        assert!(IntcodePatchSet::new().patch(4, &[1101]).apply(&mut patched).is_err());
+        // Operands and same-sized instructions are fine
+        assert!(IntcodePatchSet::new().patch(2, &[3]).patch(0, &[1102]).apply(&mut patched).is_ok());
+        assert_eq!(patched, vec![1102,1,3,7,4,7,99,0]);
+    }
+
+    #[test]
+    fn test_data_words_can_change_freely() {
+        // 1 at address 3 would decode as a four word Add, but it's only ever output
+        let mut program = vec![4,3,99,0,0,0,0];
+        IntcodePatchSet::new().patch(3, &[1]).apply(&mut program).unwrap();
+        assert_eq!(program, vec![4,3,99,1,0,0,0]);
+
+        // Code past a constant jump is still checked
+        let mut program = vec![1105,1,4,99,1101,1,2,9,99,0];
+        assert!(IntcodePatchSet::new().patch(4, &[104]).apply(&mut program).is_err());
+    }
+
+    #[test]
+    fn test_hot_patch_running_machine() {
+        // Counts down from 7 and outputs every value
+        let mut program = vec![1001,20,-1,20,4,20,1005,20,0,99];
+        program.resize(21, 0);
+        program[20] = 7;
+        let mut machine = IntcodeMachine::new_automated_machine(&program, &[]);
+        for _ in 0..3 {
+            machine.step().unwrap();
+        }
+
+        // Make every later decrement subtract 2
+        let revert = IntcodePatchSet::new().patch(2, &[-2]).apply_to(&mut machine).unwrap();
+        machine.run();
+        assert_eq!(machine.output_handler().history(), &["6", "4", "2", "0"]);
+        revert.apply_to(&mut machine).unwrap();
+        assert_eq!(machine.read_memory_position(2), -1);
+
+        assert!(IntcodePatchSet::new().patch(1023, &[1, 2]).apply_to(&mut machine).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::{bail, Result};
+
+use std::str::FromStr;
+
+use crate::intcode::IntcodeFault;
+
+// The instruction set as of each puzzle that revised it. Extended also allows registered extensions.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum IntcodeProfile {
+    V2,
+    V5,
+    V9,
+    #[default]
+    Extended,
+}
+
+impl IntcodeProfile {
+    pub fn supports_opcode(&self, opcode: i64) -> bool {
+        use IntcodeProfile::*;
+
+        match self {
+            V2 => matches!(opcode, 1 | 2 | 99),
+            V5 => matches!(opcode, 1..=8 | 99),
+            V9 => matches!(opcode, 1..=9 | 99),
+            Extended => true,
+        }
+    }
+
+    pub fn supports_mode(&self, mode: i64) -> bool {
+        use IntcodeProfile::*;
+
+        match self {
+            V2 => mode == 0,
+            V5 => mode <= 1,
+            V9 | Extended => mode <= 2,
+        }
+    }
+
+    // Checked against the raw word before decoding, so a later feature is reported as such
+    // rather than as whatever decoding would make of it
+    pub fn check(&self, opcode_and_param_modes: i64) -> Result<(), IntcodeFault> {
+        if opcode_and_param_modes <= 0 {
+            return Ok(());
+        }
+
+        let opcode = opcode_and_param_modes % 100;
+        if !self.supports_opcode(opcode) {
+            return Err(IntcodeFault::UnsupportedOpcode(*self, opcode));
+        }
+
+        let mut modes = opcode_and_param_modes / 100;
+        while modes > 0 {
+            if !self.supports_mode(modes % 10) {
+                return Err(IntcodeFault::UnsupportedParameterMode(*self, modes % 10));
+            }
+            modes /= 10;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for IntcodeProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use IntcodeProfile::*;
+
+        let name = match self {
+            V2 => "v2",
+            V5 => "v5",
+            V9 => "v9",
+            Extended => "extended",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for IntcodeProfile {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_lowercase().as_ref() {
+            "v2" | "2" => Ok(IntcodeProfile::V2),
+            "v5" | "5" => Ok(IntcodeProfile::V5),
+            "v9" | "9" => Ok(IntcodeProfile::V9),
+            "extended" => Ok(IntcodeProfile::Extended),
+            _ => bail!("Unknown Intcode profile: {}", input),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{IntcodeMachine, IntcodeExtension};
+
+    fn run_with_profile(program: &[i64], profile: IntcodeProfile) -> Result<(), IntcodeFault> {
+        let mut machine = IntcodeMachine::new_automated_machine(program, &[5]);
+        machine.set_profile(profile);
+        machine.try_run()
+    }
+
+    #[test]
+    fn test_v2_rejects_io_and_modes() {
+        assert_eq!(run_with_profile(&[1,0,0,0,99], IntcodeProfile::V2), Ok(()));
+        assert_eq!(run_with_profile(&[3,0,99], IntcodeProfile::V2), Err(IntcodeFault::UnsupportedOpcode(IntcodeProfile::V2, 3)));
+        assert_eq!(run_with_profile(&[1101,1,1,0,99], IntcodeProfile::V2), Err(IntcodeFault::UnsupportedParameterMode(IntcodeProfile::V2, 1)));
+    }
+
+    #[test]
+    fn test_v5_rejects_relative_mode() {
+        assert_eq!(run_with_profile(&[3,0,1005,0,6,99,99], IntcodeProfile::V5), Ok(()));
+        assert_eq!(run_with_profile(&[109,1,99], IntcodeProfile::V5), Err(IntcodeFault::UnsupportedOpcode(IntcodeProfile::V5, 9)));
+        assert_eq!(run_with_profile(&[204,0,99], IntcodeProfile::V5), Err(IntcodeFault::UnsupportedParameterMode(IntcodeProfile::V5, 2)));
+    }
+
+    #[test]
+    fn test_v9_relative_writes() {
+        let mut machine = IntcodeMachine::new_automated_machine(&[109,10,203,-3,99], &[5]);
+        machine.set_profile(IntcodeProfile::V9);
+        machine.run();
+
+        assert_eq!(machine.memory()[7], 5);
+    }
+
+    #[test]
+    fn test_extensions_need_extended_profile() {
+        let mut machine = IntcodeMachine::new_automated_machine(&[1110,7,2,0,99], &[]);
+        machine.register_extension(IntcodeExtension::divide(10)).unwrap();
+        machine.set_profile(IntcodeProfile::V9);
+        assert_eq!(machine.try_run(), Err(IntcodeFault::UnsupportedOpcode(IntcodeProfile::V9, 10)));
+
+        machine.set_profile(IntcodeProfile::Extended);
+        assert_eq!(machine.try_run(), Ok(()));
+    }
+
+    #[test]
+    fn test_parse_profile() {
+        assert_eq!("V5".parse::<IntcodeProfile>().unwrap(), IntcodeProfile::V5);
+        assert!("v7".parse::<IntcodeProfile>().is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use anyhow::{anyhow, bail, Result};
+
+use std::fs;
+use std::path::Path;
+
+use crate::intcode::IntcodeProfile;
+
+// A program file is a list of values separated by commas and/or whitespace, with `#` starting a
+// comment that runs to the end of the line. Comment lines before the first value can carry
+// metadata:
+//
+//   # name: Sensor BOOST
+//   # profile: v9
+//   # inputs: 1
+//   # answer part1: 3460311188
+//   1102,34463338,34463338,63,...
+//
+// Comments with any other key are ignored.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IntcodeProgram {
+    pub name: Option<String>,
+    pub profile: Option<IntcodeProfile>,
+    pub inputs: Vec<i64>,
+    pub answers: Vec<(String, i64)>,
+    pub code: Vec<i64>,
+}
+
+impl IntcodeProgram {
+    pub fn new(code: &[i64]) -> Self {
+        Self { code: code.to_vec(), ..Default::default() }
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut program = Self::new(&[]);
+        for (number, line) in text.lines().enumerate() {
+            let (content, comment) = match line.find('#') {
+                Some(start) => (&line[..start], Some(&line[start + 1..])),
+                None => (line, None),
+            };
+
+            if let (Some(comment), true) = (comment, program.code.is_empty() && content.trim().is_empty()) {
+                program.parse_metadata(comment).map_err(|error| anyhow!("Line {}: {}", number + 1, error))?;
+            }
+
+            let mut token_start = None;
+            for (column, c) in content.char_indices().chain(std::iter::once((content.len(), ','))) {
+                let separator = c == ',' || c.is_whitespace();
+                match (token_start, separator) {
+                    (None, false) => token_start = Some(column),
+                    (Some(start), true) => {
+                        let token = &content[start..column];
+                        let value = token.parse::<i64>().map_err(|_| {
+                            anyhow!("Line {}, column {}: invalid value '{}'", number + 1, content[..start].chars().count() + 1, token)
+                        })?;
+                        program.code.push(value);
+                        token_start = None;
+                    },
+                    _ => {},
+                }
+            }
+        }
+
+        Ok(program)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|error| anyhow!("{}: {}", path.display(), error))?;
+        Self::parse(&text).map_err(|error| anyhow!("{}: {}", path.display(), error))
+    }
+
+    // Loads the file in input/ named by the first command argument
+    pub fn load_from_args(args: &[String]) -> Result<Self> {
+        let file_name = args.first().ok_or(anyhow!("Please provide an input file name"))?;
+        Self::load(format!("input/{}", file_name))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    pub fn answer(&self, label: &str) -> Option<i64> {
+        self.answers.iter()
+            .find(|(existing, _)| existing == label)
+            .map(|(_, value)| *value)
+    }
+
+    fn parse_metadata(&mut self, comment: &str) -> Result<()> {
+        let (key, value) = match comment.split_once(':') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => return Ok(()),
+        };
+
+        match key.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            ["name"] => self.name = Some(String::from(value)),
+            ["profile"] => self.profile = Some(value.parse()?),
+            ["inputs"] => {
+                self.inputs = value.split(',')
+                    .map(|input| input.trim())
+                    .filter(|input| !input.is_empty())
+                    .map(|input| input.parse::<i64>().map_err(|_| anyhow!("Invalid input '{}'", input)))
+                    .collect::<Result<Vec<i64>>>()?;
+            },
+            ["answer", label] => {
+                let answer = value.parse::<i64>().map_err(|_| anyhow!("Invalid answer '{}'", value))?;
+                self.answers.push((String::from(*label), answer));
+            },
+            ["answer"] => bail!("Answers need a label, e.g. 'answer part1: {}'", value),
+            _ => {},
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for IntcodeProgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(name) = &self.name {
+            writeln!(f, "# name: {}", name)?;
+        }
+        if let Some(profile) = &self.profile {
+            writeln!(f, "# profile: {}", profile)?;
+        }
+        if !self.inputs.is_empty() {
+            let inputs: Vec<String> = self.inputs.iter().map(|input| input.to_string()).collect();
+            writeln!(f, "# inputs: {}", inputs.join(", "))?;
+        }
+        for (label, answer) in &self.answers {
+            writeln!(f, "# answer {}: {}", label, answer)?;
+        }
+
+        let code: Vec<String> = self.code.iter().map(|value| value.to_string()).collect();
+        writeln!(f, "{}", code.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_with_metadata_and_comments() {
+        let text = "# name: Doubler\n# profile: v5\n# inputs: 21\n# answer part1: 42\n# author notes\n\n3,9, 1002,9,2,9 # double it\n4,9,\n99,0\n";
+        let program = IntcodeProgram::parse(text).unwrap();
+
+        assert_eq!(program.name.as_deref(), Some("Doubler"));
+        assert_eq!(program.profile, Some(IntcodeProfile::V5));
+        assert_eq!(program.inputs, vec![21]);
+        assert_eq!(program.answer("part1"), Some(42));
+        assert_eq!(program.code, vec![3,9,1002,9,2,9,4,9,99,0]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let program = IntcodeProgram {
+            name: Some(String::from("Example")),
+            profile: Some(IntcodeProfile::V9),
+            inputs: vec![1, -2],
+            answers: vec![(String::from("part1"), 7), (String::from("part2"), -8)],
+            code: vec![109,-1,204,1,99],
+        };
+
+        assert_eq!(IntcodeProgram::parse(&program.to_string()).unwrap(), program);
+        assert_eq!(IntcodeProgram::parse(&IntcodeProgram::new(&[99]).to_string()).unwrap(), IntcodeProgram::new(&[99]));
+    }
+
+    #[test]
+    fn test_errors_have_positions() {
+        let error = IntcodeProgram::parse("1,2,3\n4, 5x,6").unwrap_err();
+        assert_eq!(error.to_string(), "Line 2, column 4: invalid value '5x'");
+
+        assert!(IntcodeProgram::parse("# profile: v7\n99").is_err());
+        assert!(IntcodeProgram::parse("# answer: 5\n99").is_err());
+    }
+
+    #[test]
+    fn test_metadata_only_in_header() {
+        let program = IntcodeProgram::parse("99\n# name: ignored").unwrap();
+        assert_eq!(program.name, None);
+    }
+
+    #[test]
+    fn test_newlined_input() {
+        let newlined = IntcodeProgram::load("input/day9newlined").unwrap();
+        let commas = IntcodeProgram::load("input/input9").unwrap();
+
+        assert_eq!(newlined.code, commas.code);
+        assert_eq!(commas.code.len(), 973);
+    }
+
+    #[test]
+    fn test_loads_puzzle_inputs() {
+        for day in [2, 5, 7, 9] {
+            let program = IntcodeProgram::load(crate::utils::input::input_file_name(day)).unwrap();
+            assert_eq!(program.code, crate::utils::input::read_input_list_as::<i64>(day, b',').unwrap());
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::{bail, Result};
+
+use std::collections::HashSet;
+
+use crate::intcode::{IntcodeMachine, IntcodeOutput, IntcodeState};
+use crate::utils::grid::{Direction, Grid, Point};
+
+pub const BLACK: i64 = 0;
+pub const WHITE: i64 = 1;
+
+// Moves over a hull of black and white panels. The program reads the color under the robot and
+// answers with a color to paint and a turn (0 left, 1 right), after which the robot steps forward.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntcodeHullRobot {
+    hull: Grid<i64>,
+    painted: HashSet<Point>,
+    position: Point,
+    heading: Direction,
+}
+
+impl Default for IntcodeHullRobot {
+    fn default() -> Self {
+        Self { hull: Grid::new(), painted: HashSet::new(), position: (0, 0), heading: Direction::Up }
+    }
+}
+
+impl IntcodeHullRobot {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn starting_on(color: i64) -> Self {
+        let mut robot = Self::new();
+        robot.hull.set(robot.position, color);
+        robot
+    }
+
+    pub fn position(&self) -> Point {
+        self.position
+    }
+
+    pub fn heading(&self) -> Direction {
+        self.heading
+    }
+
+    pub fn camera(&self) -> i64 {
+        self.hull.get(self.position).copied().unwrap_or(BLACK)
+    }
+
+    pub fn command(&mut self, color: i64, turn: i64) -> Result<()> {
+        if color != BLACK && color != WHITE {
+            bail!("Invalid color: {}", color);
+        }
+        self.heading = match turn {
+            0 => self.heading.turn_left(),
+            1 => self.heading.turn_right(),
+            _ => bail!("Invalid turn: {}", turn),
+        };
+
+        self.hull.set(self.position, color);
+        self.painted.insert(self.position);
+        self.position = self.heading.step(self.position);
+        Ok(())
+    }
+
+    // Panels painted at least once, whatever their color is now
+    pub fn painted(&self) -> usize {
+        self.painted.len()
+    }
+
+    pub fn run(&mut self, program: &[i64]) -> Result<()> {
+        let mut machine = IntcodeMachine::new_blocking_machine(program);
+        let mut consumed = 0;
+        loop {
+            machine.try_run()?;
+            let outputs = machine.output_handler().history()[consumed..]
+                .iter()
+                .map(|output| output.parse::<i64>())
+                .collect::<Result<Vec<i64>, _>>()?;
+            for command in outputs.chunks_exact(2) {
+                self.command(command[0], command[1])?;
+                consumed += 2;
+            }
+
+            match machine.state() {
+                IntcodeState::Halted => return Ok(()),
+                IntcodeState::Suspended => machine.input(self.camera()),
+                state => bail!("Robot program stopped unexpectedly: {:?}", state),
+            }
+        }
+    }
+
+    // White panels as `#`, everything else as `.`
+    pub fn render(&self) -> String {
+        self.hull.render(|_, color| if color == Some(&WHITE) { '#' } else { '.' })
+    }
+
+    // Same with the robot drawn as an arrow in the direction it's facing
+    pub fn render_with_robot(&self) -> String {
+        let mut hull = self.hull.clone();
+        hull.set(self.position, self.camera());
+        hull.render(|point, color| match (point == self.position, color) {
+            (true, _) => match self.heading {
+                Direction::Up => '^',
+                Direction::Right => '>',
+                Direction::Down => 'v',
+                Direction::Left => '<',
+            },
+            (false, Some(&WHITE)) => '#',
+            _ => '.',
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example_sequence() {
+        let mut robot = IntcodeHullRobot::new();
+        for (color, turn) in [(1, 0), (0, 0), (1, 0), (1, 0)] {
+            assert_eq!(robot.camera(), BLACK);
+            robot.command(color, turn).unwrap();
+        }
+        assert_eq!(robot.render_with_robot(), ".^\n##");
+        assert_eq!(robot.camera(), WHITE);
+
+        for (color, turn) in [(0, 1), (1, 0), (1, 0)] {
+            robot.command(color, turn).unwrap();
+        }
+        assert_eq!(robot.painted(), 6);
+        assert_eq!((robot.position(), robot.heading()), ((0, -1), Direction::Left));
+        assert_eq!(robot.render_with_robot(), ".<#\n..#\n##.");
+        assert_eq!(robot.render(), "..#\n..#\n##.");
+    }
+
+    #[test]
+    fn test_invalid_commands() {
+        let mut robot = IntcodeHullRobot::new();
+        assert!(robot.command(2, 0).is_err());
+        assert!(robot.command(1, 5).is_err());
+        assert_eq!(robot.painted(), 0);
+    }
+
+    #[test]
+    fn test_program_sees_camera() {
+        // Paints each panel the opposite color and turns right, four times
+        let program = vec![3,100,1008,100,0,101,4,101,104,1,1001,102,1,102,1007,102,4,103,1005,103,0,99];
+        let mut robot = IntcodeHullRobot::starting_on(WHITE);
+        robot.run(&program).unwrap();
+
+        assert_eq!(robot.painted(), 4);
+        assert_eq!(robot.render(), ".#\n##");
+        assert_eq!((robot.position(), robot.heading()), ((0, 0), Direction::Up));
+    }
+}
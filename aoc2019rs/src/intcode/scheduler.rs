@@ -0,0 +1,389 @@
+use anyhow::{bail, Result};
+
+use crate::intcode::{IntcodeMachine, IntcodeHistoryOutput, IntcodeOutput, IntcodeState, IntcodeFault};
+use crate::intcode::asynchronous::IntcodeChannel;
+
+pub const DEFAULT_TIME_SLICE: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntcodeSchedulingPolicy {
+    RoundRobin,
+    // Only the highest priority runnable machine runs until it blocks; ties go round-robin
+    Priority,
+}
+
+// Where a machine's outputs go
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntcodeRoute {
+    Discard,
+    Forward(Vec<usize>),
+    // Groups of `size` values, the first being the destination machine and the rest the payload
+    Packets(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntcodeSchedulerOutcome {
+    Halted,
+    // The first machine found faulted stops the whole network
+    Faulted(usize, IntcodeFault),
+    Deadlock,
+    Idle,
+    InstructionLimit,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IntcodeMachineStats {
+    pub instructions: usize,
+    pub slices: usize,
+    pub inputs: usize,
+    pub outputs: usize,
+    pub idle_reads: usize,
+}
+
+struct ScheduledMachine {
+    machine: IntcodeMachine<IntcodeChannel, IntcodeHistoryOutput>,
+    inbox: IntcodeChannel,
+    route: IntcodeRoute,
+    priority: i64,
+    idle_input: Option<i64>,
+    packet: Vec<i64>,
+    stats: IntcodeMachineStats,
+}
+
+impl ScheduledMachine {
+    fn is_runnable(&self) -> bool {
+        match self.machine.state() {
+            IntcodeState::Halted | IntcodeState::Faulted(_) => false,
+            IntcodeState::Suspended => !self.inbox.is_empty() || self.idle_input.is_some(),
+            _ => true,
+        }
+    }
+}
+
+pub struct IntcodeScheduler {
+    machines: Vec<ScheduledMachine>,
+    policy: IntcodeSchedulingPolicy,
+    time_slice: usize,
+    next: usize,
+    undelivered: Vec<(i64, Vec<i64>)>,
+}
+
+impl IntcodeScheduler {
+    pub fn new(policy: IntcodeSchedulingPolicy, time_slice: usize) -> Self {
+        Self {
+            machines: Vec::new(),
+            policy,
+            time_slice: time_slice.max(1),
+            next: 0,
+            undelivered: Vec::new(),
+        }
+    }
+
+    pub fn add_machine(&mut self, machine_code: &[i64], inputs: &[i64]) -> usize {
+        let inbox = IntcodeChannel::new();
+        for input in inputs {
+            inbox.send(*input);
+        }
+
+        self.machines.push(ScheduledMachine {
+            machine: IntcodeMachine::new(machine_code, inbox.clone(), IntcodeHistoryOutput::new()),
+            inbox,
+            route: IntcodeRoute::Discard,
+            priority: 0,
+            idle_input: None,
+            packet: Vec::new(),
+            stats: Default::default(),
+        });
+        self.machines.len() - 1
+    }
+
+    pub fn connect(&mut self, from: usize, to: usize) -> Result<()> {
+        self.check_id(from)?;
+        self.check_id(to)?;
+
+        match &mut self.machines[from].route {
+            IntcodeRoute::Forward(targets) => targets.push(to),
+            route => *route = IntcodeRoute::Forward(vec![to]),
+        }
+        Ok(())
+    }
+
+    pub fn set_route(&mut self, id: usize, route: IntcodeRoute) -> Result<()> {
+        self.check_id(id)?;
+        if let IntcodeRoute::Packets(size) = route {
+            if size < 2 {
+                bail!("Packets need a destination and at least one value");
+            }
+        }
+        self.machines[id].route = route;
+        Ok(())
+    }
+
+    pub fn set_priority(&mut self, id: usize, priority: i64) -> Result<()> {
+        self.check_id(id)?;
+        self.machines[id].priority = priority;
+        Ok(())
+    }
+
+    // Value read instead of suspending when the machine's queue is empty, like the -1 of a network card
+    pub fn set_idle_input(&mut self, id: usize, value: Option<i64>) -> Result<()> {
+        self.check_id(id)?;
+        self.machines[id].idle_input = value;
+        Ok(())
+    }
+
+    pub fn send(&mut self, id: usize, value: i64) -> Result<()> {
+        self.check_id(id)?;
+        self.machines[id].inbox.send(value);
+        Ok(())
+    }
+
+    pub fn machine(&self, id: usize) -> &IntcodeMachine<IntcodeChannel, IntcodeHistoryOutput> {
+        &self.machines[id].machine
+    }
+
+    pub fn stats(&self, id: usize) -> &IntcodeMachineStats {
+        &self.machines[id].stats
+    }
+
+    // Packets addressed to machines that don't exist, in the order they were sent
+    pub fn undelivered(&self) -> &[(i64, Vec<i64>)] {
+        &self.undelivered
+    }
+
+    pub fn run(&mut self, max_instructions: usize) -> IntcodeSchedulerOutcome {
+        let mut executed = 0;
+        let mut idle_slices = 0;
+        loop {
+            let faulted = self.machines.iter().enumerate().find_map(|(id, scheduled)| match scheduled.machine.state() {
+                IntcodeState::Faulted(fault) => Some((id, fault.clone())),
+                _ => None,
+            });
+            if let Some((id, fault)) = faulted {
+                return IntcodeSchedulerOutcome::Faulted(id, fault);
+            }
+            if self.machines.iter().all(|scheduled| scheduled.machine.state() == &IntcodeState::Halted) {
+                return IntcodeSchedulerOutcome::Halted;
+            }
+            let id = match self.pick() {
+                Some(id) => id,
+                None => return IntcodeSchedulerOutcome::Deadlock,
+            };
+            if executed >= max_instructions {
+                return IntcodeSchedulerOutcome::InstructionLimit;
+            }
+
+            let (instructions, active) = self.run_slice(id);
+            executed += instructions;
+
+            // Every runnable machine getting a slice without doing anything but idle reads means nothing will change
+            idle_slices = if active { 0 } else { idle_slices + 1 };
+            let runnable = self.machines.iter().filter(|scheduled| scheduled.is_runnable()).count();
+            if idle_slices > 0 && idle_slices >= runnable {
+                return IntcodeSchedulerOutcome::Idle;
+            }
+        }
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        for (id, scheduled) in self.machines.iter().enumerate() {
+            let stats = &scheduled.stats;
+            report.push_str(&format!(
+                "#{}: {:?}, {} instructions in {} slices, {} inputs ({} idle), {} outputs\n",
+                id, scheduled.machine.state(), stats.instructions, stats.slices, stats.inputs, stats.idle_reads, stats.outputs));
+        }
+        report
+    }
+
+    fn check_id(&self, id: usize) -> Result<()> {
+        if id >= self.machines.len() {
+            bail!("No machine with id {}", id);
+        }
+        Ok(())
+    }
+
+    fn pick(&mut self) -> Option<usize> {
+        let count = self.machines.len();
+        let mut order = (0..count).map(|offset| (self.next + offset) % count);
+        let id = match self.policy {
+            IntcodeSchedulingPolicy::RoundRobin => order.find(|id| self.machines[*id].is_runnable()),
+            IntcodeSchedulingPolicy::Priority => {
+                let runnable: Vec<usize> = order.filter(|id| self.machines[*id].is_runnable()).collect();
+                let highest = runnable.iter().map(|id| self.machines[*id].priority).max();
+                runnable.into_iter().find(|id| Some(self.machines[*id].priority) == highest)
+            },
+        }?;
+
+        self.next = (id + 1) % count;
+        Some(id)
+    }
+
+    // Returns the instructions executed and whether the slice did anything besides idle reads
+    fn run_slice(&mut self, id: usize) -> (usize, bool) {
+        let scheduled = &mut self.machines[id];
+        scheduled.stats.slices += 1;
+
+        let start = scheduled.machine.instruction_count();
+        let mut active = false;
+        let mut idle_read = false;
+        let mut outputs = Vec::new();
+        for _ in 0..self.time_slice {
+            let idle = match scheduled.idle_input {
+                Some(value) if scheduled.inbox.is_empty() => {
+                    scheduled.inbox.send(value);
+                    true
+                },
+                _ => false,
+            };
+            let queued = scheduled.inbox.len();
+            let output_count = scheduled.machine.output_handler().history().len();
+            if scheduled.machine.step().is_err() {
+                break;
+            }
+
+            if scheduled.inbox.len() < queued {
+                scheduled.stats.inputs += 1;
+                if idle {
+                    scheduled.stats.idle_reads += 1;
+                    idle_read = true;
+                } else {
+                    active = true;
+                }
+            } else if idle {
+                // The idle value wasn't read, so take it back
+                scheduled.inbox.try_recv();
+            }
+            if let Some(output) = scheduled.machine.output_handler().history().get(output_count) {
+                outputs.push(output.parse::<i64>().expect("Invalid output"));
+                active = true;
+            }
+
+            if let IntcodeState::Suspended | IntcodeState::Halted = scheduled.machine.state() {
+                break;
+            }
+        }
+
+        let instructions = scheduled.machine.instruction_count() - start;
+        scheduled.stats.instructions += instructions;
+        scheduled.stats.outputs += outputs.len();
+        for output in outputs {
+            self.route(id, output);
+        }
+
+        (instructions, active || !idle_read)
+    }
+
+    fn route(&mut self, id: usize, value: i64) {
+        match self.machines[id].route.clone() {
+            IntcodeRoute::Discard => {},
+            IntcodeRoute::Forward(targets) => {
+                for target in targets {
+                    self.machines[target].inbox.send(value);
+                }
+            },
+            IntcodeRoute::Packets(size) => {
+                let packet = &mut self.machines[id].packet;
+                packet.push(value);
+                if packet.len() == size {
+                    let packet: Vec<i64> = self.machines[id].packet.drain(..).collect();
+                    match self.machines.get(packet[0] as usize).filter(|_| packet[0] >= 0) {
+                        Some(target) => {
+                            for value in &packet[1..] {
+                                target.inbox.send(*value);
+                            }
+                        },
+                        None => self.undelivered.push((packet[0], packet[1..].to_vec())),
+                    }
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amplifier_ring(program: &[i64], phases: &[i64], policy: IntcodeSchedulingPolicy) -> i64 {
+        let mut scheduler = IntcodeScheduler::new(policy, DEFAULT_TIME_SLICE);
+        let ids: Vec<usize> = phases.iter().map(|phase| scheduler.add_machine(program, &[*phase])).collect();
+        for (i, id) in ids.iter().enumerate() {
+            scheduler.connect(*id, ids[(i + 1) % ids.len()]).unwrap();
+        }
+        scheduler.send(ids[0], 0).unwrap();
+
+        assert_eq!(scheduler.run(1_000_000), IntcodeSchedulerOutcome::Halted);
+        scheduler.machine(ids[4]).output_handler().last_output().unwrap().parse().unwrap()
+    }
+
+    #[test]
+    fn test_amplifier_feedback_loop() {
+        let program = vec![3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5];
+        assert_eq!(amplifier_ring(&program, &[9,8,7,6,5], IntcodeSchedulingPolicy::RoundRobin), 139629729);
+        assert_eq!(amplifier_ring(&program, &[9,8,7,6,5], IntcodeSchedulingPolicy::Priority), 139629729);
+    }
+
+    #[test]
+    fn test_deadlock() {
+        let mut scheduler = IntcodeScheduler::new(IntcodeSchedulingPolicy::RoundRobin, 10);
+        let a = scheduler.add_machine(&[3,0,4,0,99], &[]);
+        let b = scheduler.add_machine(&[3,0,4,0,99], &[]);
+        scheduler.connect(a, b).unwrap();
+        scheduler.connect(b, a).unwrap();
+
+        assert_eq!(scheduler.run(1000), IntcodeSchedulerOutcome::Deadlock);
+        assert_eq!(scheduler.machine(a).state(), &IntcodeState::Suspended);
+    }
+
+    #[test]
+    fn test_fault_stops_network() {
+        let mut scheduler = IntcodeScheduler::new(IntcodeSchedulingPolicy::RoundRobin, 10);
+        let a = scheduler.add_machine(&[3,0,4,0,99], &[]);
+        let b = scheduler.add_machine(&[104,7,42], &[]);
+        scheduler.connect(b, a).unwrap();
+
+        assert_eq!(scheduler.run(1000), IntcodeSchedulerOutcome::Faulted(b, IntcodeFault::InvalidInstruction(42)));
+        assert_eq!(scheduler.machine(a).state(), &IntcodeState::Suspended);
+        assert_eq!(scheduler.stats(b).outputs, 1);
+    }
+
+    #[test]
+    fn test_packets_and_idle_network() {
+        // Each machine reads its address, then sends (address + 1, address * 10) once and idles on -1 forever
+        let program = vec![3,100,1001,100,1,101,1002,100,10,102,4,101,4,102,3,103,1105,1,14];
+        let mut scheduler = IntcodeScheduler::new(IntcodeSchedulingPolicy::RoundRobin, DEFAULT_TIME_SLICE);
+        for address in 0..3 {
+            let id = scheduler.add_machine(&program, &[address]);
+            scheduler.set_route(id, IntcodeRoute::Packets(2)).unwrap();
+            scheduler.set_idle_input(id, Some(-1)).unwrap();
+        }
+
+        assert_eq!(scheduler.run(100_000), IntcodeSchedulerOutcome::Idle);
+        assert_eq!(scheduler.undelivered(), &[(3, vec![20])]);
+        assert_eq!(scheduler.stats(1).outputs, 2);
+        assert_eq!(scheduler.stats(1).inputs - scheduler.stats(1).idle_reads, 2);
+    }
+
+    #[test]
+    fn test_priority_runs_highest_first() {
+        let mut scheduler = IntcodeScheduler::new(IntcodeSchedulingPolicy::Priority, 1);
+        let low = scheduler.add_machine(&[104,1,99], &[]);
+        let high = scheduler.add_machine(&[104,1,104,2,99], &[]);
+        scheduler.set_priority(high, 5).unwrap();
+
+        assert_eq!(scheduler.run(2), IntcodeSchedulerOutcome::InstructionLimit);
+        assert_eq!(scheduler.stats(high).instructions, 2);
+        assert_eq!(scheduler.stats(low).instructions, 0);
+        assert_eq!(scheduler.run(100), IntcodeSchedulerOutcome::Halted);
+        assert!(scheduler.report().contains("#0: Halted, 2 instructions in 2 slices"));
+    }
+
+    #[test]
+    fn test_invalid_ids_are_rejected() {
+        let mut scheduler = IntcodeScheduler::new(IntcodeSchedulingPolicy::RoundRobin, 1);
+        let id = scheduler.add_machine(&[99], &[]);
+        assert!(scheduler.connect(id, 3).is_err());
+        assert!(scheduler.set_route(id, IntcodeRoute::Packets(1)).is_err());
+    }
+}
@@ -0,0 +1,304 @@
+use std::ops::RangeInclusive;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::intcode::{IntcodeMachine, IntcodeQueueInput, IntcodeHistoryOutput, IntcodeState};
+
+pub const DEFAULT_MAX_INSTRUCTIONS: usize = 10_000_000;
+
+type SearchMachine = IntcodeMachine<IntcodeQueueInput, IntcodeHistoryOutput>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntcodeSearchGoal {
+    // Stops as soon as any thread finds a candidate scoring exactly this
+    Target(i64),
+    Maximize,
+    Minimize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntcodeSearchResult<P> {
+    pub candidate: P,
+    pub value: i64,
+    pub evaluated: usize,
+}
+
+// Candidates that search threads fetch by index, so they don't all have to exist up front
+pub trait IntcodeCandidates {
+    type Candidate: Clone;
+
+    fn len(&self) -> usize;
+    fn candidate(&self, index: usize) -> Self::Candidate;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<P: Clone> IntcodeCandidates for [P] {
+    type Candidate = P;
+
+    fn len(&self) -> usize {
+        <[P]>::len(self)
+    }
+
+    fn candidate(&self, index: usize) -> P {
+        self[index].clone()
+    }
+}
+
+impl<P: Clone> IntcodeCandidates for Vec<P> {
+    type Candidate = P;
+
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn candidate(&self, index: usize) -> P {
+        self[index].clone()
+    }
+}
+
+impl<P: Clone, const N: usize> IntcodeCandidates for [P; N] {
+    type Candidate = P;
+
+    fn len(&self) -> usize {
+        N
+    }
+
+    fn candidate(&self, index: usize) -> P {
+        self[index].clone()
+    }
+}
+
+// Every combination of one value from each range, e.g. all noun/verb pairs, with the last range
+// changing fastest. Combinations are computed from their index when asked for.
+#[derive(Clone, Debug)]
+pub struct IntcodeGrid {
+    ranges: Vec<RangeInclusive<i64>>,
+    sizes: Vec<usize>,
+    len: usize,
+}
+
+impl IntcodeGrid {
+    pub fn iter(&self) -> impl Iterator<Item = Vec<i64>> + '_ {
+        (0..self.len).map(move |index| self.candidate(index))
+    }
+}
+
+impl IntcodeCandidates for IntcodeGrid {
+    type Candidate = Vec<i64>;
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn candidate(&self, mut index: usize) -> Vec<i64> {
+        let mut combination = vec![0; self.ranges.len()];
+        for (position, (range, size)) in self.ranges.iter().zip(&self.sizes).enumerate().rev() {
+            combination[position] = (*range.start() as i128 + (index % size) as i128) as i64;
+            index /= size;
+        }
+        combination
+    }
+}
+
+// Grids with more than usize::MAX combinations are cut short
+pub fn grid(ranges: &[RangeInclusive<i64>]) -> IntcodeGrid {
+    let sizes: Vec<usize> = ranges.iter()
+        .map(|range| (*range.end() as i128 - *range.start() as i128 + 1).clamp(0, usize::MAX as i128) as usize)
+        .collect();
+    let len = sizes.iter().fold(1usize, |len, size| len.saturating_mul(*size));
+
+    IntcodeGrid {
+        ranges: ranges.to_vec(),
+        sizes,
+        len,
+    }
+}
+
+pub fn default_threads() -> usize {
+    std::thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1)
+}
+
+// Evaluates candidates across threads. Candidates the evaluator returns None for are skipped.
+// Ties keep the earliest candidate, so results don't depend on the thread count except for
+// which of several Target matches is found first.
+pub fn search<C, E>(candidates: &C, threads: usize, goal: IntcodeSearchGoal, evaluate: E) -> Option<IntcodeSearchResult<C::Candidate>>
+where C: IntcodeCandidates + Sync + ?Sized,
+      E: Fn(&C::Candidate) -> Option<i64> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let evaluated = AtomicUsize::new(0);
+    let found = AtomicBool::new(false);
+    let best: Mutex<Option<(usize, i64)>> = Mutex::new(None);
+
+    let better = |value: i64, index: usize, current: &Option<(usize, i64)>| match (goal, current) {
+        (_, None) => true,
+        (IntcodeSearchGoal::Target(_), Some((best_index, _))) => index < *best_index,
+        (IntcodeSearchGoal::Maximize, Some((best_index, best_value))) => value > *best_value || (value == *best_value && index < *best_index),
+        (IntcodeSearchGoal::Minimize, Some((best_index, best_value))) => value < *best_value || (value == *best_value && index < *best_index),
+    };
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| {
+                while !found.load(Ordering::SeqCst) {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    if index >= candidates.len() {
+                        break;
+                    }
+
+                    let value = evaluate(&candidates.candidate(index));
+                    evaluated.fetch_add(1, Ordering::SeqCst);
+                    let value = match (goal, value) {
+                        (IntcodeSearchGoal::Target(target), Some(value)) if value != target => continue,
+                        (_, Some(value)) => value,
+                        (_, None) => continue,
+                    };
+
+                    let mut best = best.lock().unwrap();
+                    if better(value, index, &best) {
+                        *best = Some((index, value));
+                    }
+                    if let IntcodeSearchGoal::Target(_) = goal {
+                        found.store(true, Ordering::SeqCst);
+                    }
+                }
+            });
+        }
+    });
+
+    let best = best.into_inner().unwrap();
+    best.map(|(index, value)| IntcodeSearchResult {
+        candidate: candidates.candidate(index),
+        value,
+        evaluated: evaluated.load(Ordering::SeqCst),
+    })
+}
+
+// Searches over runs of a single machine: `setup` patches a copy of the program's memory for the
+// candidate and returns its inputs, `objective` scores the finished machine. Runs that fault or
+// don't halt within the instruction limit are skipped.
+pub struct IntcodeSearch {
+    program: Vec<i64>,
+    threads: usize,
+    max_instructions: usize,
+}
+
+impl IntcodeSearch {
+    pub fn new(program: &[i64]) -> Self {
+        Self {
+            program: program.to_vec(),
+            threads: default_threads(),
+            max_instructions: DEFAULT_MAX_INSTRUCTIONS,
+        }
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    pub fn max_instructions(mut self, max_instructions: usize) -> Self {
+        self.max_instructions = max_instructions;
+        self
+    }
+
+    pub fn run<C, S, F>(&self, candidates: &C, goal: IntcodeSearchGoal, setup: S, objective: F) -> Option<IntcodeSearchResult<C::Candidate>>
+    where C: IntcodeCandidates + Sync + ?Sized,
+          S: Fn(&C::Candidate, &mut Vec<i64>) -> Vec<i64> + Sync,
+          F: Fn(&SearchMachine) -> Option<i64> + Sync,
+    {
+        search(candidates, self.threads, goal, |candidate| {
+            let mut memory = self.program.clone();
+            let inputs = setup(candidate, &mut memory);
+            let mut machine = IntcodeMachine::new(&memory, IntcodeQueueInput::new(&inputs), IntcodeHistoryOutput::new());
+
+            while machine.state() != &IntcodeState::Halted {
+                if machine.instruction_count() >= self.max_instructions || machine.state() == &IntcodeState::Suspended {
+                    return None;
+                }
+                machine.step().ok()?;
+            }
+            objective(&machine)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::IntcodeOutput;
+    use crate::utils::math;
+
+    #[test]
+    fn test_grid() {
+        let collect = |ranges: &[RangeInclusive<i64>]| grid(ranges).iter().collect::<Vec<_>>();
+        assert_eq!(collect(&[0..=1, 5..=6]), vec![vec![0, 5], vec![0, 6], vec![1, 5], vec![1, 6]]);
+        assert_eq!(collect(&[]), vec![Vec::<i64>::new()]);
+        assert_eq!(collect(&[-2..=0, RangeInclusive::new(3, 2)]), Vec::<Vec<i64>>::new());
+        assert_eq!(collect(&[-1..=1, 4..=5, 0..=2]), math::product(&[-1..=1, 4..=5, 0..=2]).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_grid_is_not_built_up_front() {
+        let candidates = grid(&[i64::MIN..=i64::MAX, 0..=1_000_000_000]);
+        assert_eq!(candidates.len(), usize::MAX);
+        assert_eq!(candidates.candidate(1_000_000_002), vec![i64::MIN + 1, 1]);
+
+        let result = search(&candidates, 4, IntcodeSearchGoal::Target(5), |candidate| Some(candidate[1]));
+        assert_eq!(result.unwrap().candidate, vec![i64::MIN, 5]);
+    }
+
+    #[test]
+    fn test_target_with_memory_patches() {
+        // memory[0] = memory[9] * memory[10] + 7
+        let program = vec![2,9,10,0,1001,0,7,0,99,0,0];
+        let candidates = grid(&[0..=20, 0..=20]);
+        let result = IntcodeSearch::new(&program).threads(4).run(
+            &candidates,
+            IntcodeSearchGoal::Target(150),
+            |candidate, memory| {
+                memory[9] = candidate[0];
+                memory[10] = candidate[1];
+                Vec::new()
+            },
+            |machine| Some(machine.read_memory_position(0)));
+
+        let result = result.unwrap();
+        assert_eq!(result.value, 150);
+        assert_eq!(result.candidate[0] * result.candidate[1], 143);
+    }
+
+    #[test]
+    fn test_maximize_over_permutations() {
+        // Outputs 10 * first input + second input
+        let program = vec![3,15,3,16,1002,15,10,15,1,15,16,15,4,15,99,0,0];
+        let candidates = math::permutations_cloned(&[3, 1, 2]);
+        for threads in 1..4 {
+            let result = IntcodeSearch::new(&program).threads(threads).run(
+                &candidates,
+                IntcodeSearchGoal::Maximize,
+                |candidate, _| candidate.clone(),
+                |machine| machine.output_handler().last_output()?.parse().ok());
+
+            let result = result.unwrap();
+            assert_eq!(result.value, 32);
+            assert_eq!(result.evaluated, 6);
+        }
+    }
+
+    #[test]
+    fn test_failed_runs_are_skipped() {
+        let candidates = vec![0, 1, 2];
+        let result = search(&candidates, 2, IntcodeSearchGoal::Minimize, |candidate| {
+            if *candidate == 0 { None } else { Some(*candidate * 10) }
+        });
+        assert_eq!(result.unwrap().candidate, 1);
+
+        let looping = IntcodeSearch::new(&[1105,1,0]).max_instructions(100)
+            .run(&[0], IntcodeSearchGoal::Maximize, |_, _| Vec::new(), |_| Some(1));
+        assert_eq!(looping, None);
+    }
+}
@@ -0,0 +1,269 @@
+use anyhow::{anyhow, bail, Result};
+
+use std::fs;
+use std::path::Path;
+
+use crate::intcode::differential::DEFAULT_MAX_STEPS;
+use crate::intcode::{IntcodeMachine, IntcodeProgram, IntcodeQueueInput, IntcodeHistoryOutput, IntcodeInput, IntcodeOutput, IntcodeState};
+
+// Each event carries the number of instructions executed before the one that performed the I/O
+#[derive(Clone, Copy, PartialEq)]
+pub enum IntcodeSessionEvent {
+    Input{count: usize, value: i64},
+    Output{count: usize, value: i64},
+}
+
+impl std::fmt::Debug for IntcodeSessionEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntcodeSessionEvent::Input{count, value} => write!(f, "in {} {}", count, value),
+            IntcodeSessionEvent::Output{count, value} => write!(f, "out {} {}", count, value),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IntcodeSession {
+    events: Vec<IntcodeSessionEvent>,
+}
+
+impl IntcodeSession {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn push(&mut self, event: IntcodeSessionEvent) {
+        self.events.push(event);
+    }
+
+    pub fn events(&self) -> &[IntcodeSessionEvent] {
+        &self.events
+    }
+
+    pub fn inputs(&self) -> Vec<i64> {
+        self.events.iter()
+            .filter_map(|event| match event {
+                IntcodeSessionEvent::Input{value, ..} => Some(*value),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut session = Self::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let parse_error = || anyhow!("Invalid session event on line {}: {}", number + 1, line);
+            if parts.len() != 3 {
+                return Err(parse_error());
+            }
+            let count = parts[1].parse().map_err(|_| parse_error())?;
+            let value = parts[2].parse().map_err(|_| parse_error())?;
+            let event = match parts[0] {
+                "in" => IntcodeSessionEvent::Input{count, value},
+                "out" => IntcodeSessionEvent::Output{count, value},
+                _ => return Err(parse_error()),
+            };
+            session.push(event);
+        }
+
+        Ok(session)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut contents = String::new();
+        for event in &self.events {
+            contents.push_str(&format!("{:?}\n", event));
+        }
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    // Feeds the recorded inputs back to the program and checks every event as it happens,
+    // returning the number of matching events or the first divergence. Running for more than
+    // `max_steps` instructions counts as a divergence too.
+    pub fn replay(&self, program: &[i64], max_steps: usize) -> Result<usize, IntcodeReplayDivergence> {
+        let mut machine = IntcodeMachine::new(program, IntcodeQueueInput::new(&self.inputs()), IntcodeHistoryOutput::new());
+        machine.start_recording();
+
+        let mut matched = 0;
+        for _ in 0..max_steps {
+            let result = machine.step();
+            let actual = machine.recording().map(|session| session.events()).unwrap_or(&[]);
+            while matched < actual.len() {
+                if self.events.get(matched) != Some(&actual[matched]) {
+                    return Err(IntcodeReplayDivergence {
+                        event: matched,
+                        expected: self.events.get(matched).copied(),
+                        actual: Some(actual[matched]),
+                        reason: String::from("event mismatch"),
+                    });
+                }
+                matched += 1;
+            }
+
+            let stopped = match result {
+                Err(fault) => Some(format!("machine faulted: {}", fault)),
+                Ok(()) => match machine.state() {
+                    IntcodeState::Halted => Some(String::from("machine halted")),
+                    IntcodeState::Suspended => Some(String::from("machine waiting for input")),
+                    _ => None,
+                },
+            };
+            if let Some(reason) = stopped {
+                if matched < self.events.len() {
+                    return Err(IntcodeReplayDivergence {
+                        event: matched,
+                        expected: self.events.get(matched).copied(),
+                        actual: None,
+                        reason,
+                    });
+                }
+                return Ok(matched);
+            }
+        }
+
+        Err(IntcodeReplayDivergence {
+            event: matched,
+            expected: self.events.get(matched).copied(),
+            actual: None,
+            reason: format!("machine still running after {} steps", max_steps),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntcodeReplayDivergence {
+    pub event: usize,
+    pub expected: Option<IntcodeSessionEvent>,
+    pub actual: Option<IntcodeSessionEvent>,
+    pub reason: String,
+}
+
+impl std::fmt::Display for IntcodeReplayDivergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Replay diverged at event {} ({}): expected {:?}, got {:?}", self.event, self.reason, self.expected, self.actual)
+    }
+}
+
+pub fn record<I, O>(machine: &mut IntcodeMachine<I, O>) -> IntcodeSession
+where I: IntcodeInput,
+      O: IntcodeOutput,
+{
+    machine.start_recording();
+    machine.run();
+    machine.stop_recording().unwrap_or_default()
+}
+
+// record <program> <session>: plays a program interactively and saves the session
+// replay <program> <session>: checks a saved session against the program
+pub fn run_command(command: &str, args: &[String]) -> Result<String> {
+    if args.len() != 2 {
+        bail!("Usage: {} <program file> <session file>", command);
+    }
+    let program = IntcodeProgram::load_from_args(args)?.code;
+    let path = Path::new(&args[1]);
+
+    match command {
+        "record" => {
+            let mut machine = IntcodeMachine::new_console_machine(&program);
+            let session = record(&mut machine);
+            session.save(path)?;
+            Ok(format!("Recorded {} events to {}", session.events().len(), path.display()))
+        },
+        _ => {
+            let session = IntcodeSession::load(path)?;
+            match session.replay(&program, DEFAULT_MAX_STEPS) {
+                Ok(matched) => Ok(format!("Replayed {} events", matched)),
+                Err(divergence) => Ok(format!("{}", divergence)),
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Echoes each input doubled until it reads a zero
+    fn program() -> Vec<i64> {
+        vec![3,16,1006,16,15,1002,16,2,16,4,16,1105,1,0,99,99,0]
+    }
+
+    #[test]
+    fn test_record() {
+        let mut machine = IntcodeMachine::new_automated_machine(&program(), &[3,5,0]);
+        let session = record(&mut machine);
+
+        assert_eq!(session.events(), &[
+            IntcodeSessionEvent::Input{count: 0, value: 3},
+            IntcodeSessionEvent::Output{count: 3, value: 6},
+            IntcodeSessionEvent::Input{count: 5, value: 5},
+            IntcodeSessionEvent::Output{count: 8, value: 10},
+            IntcodeSessionEvent::Input{count: 10, value: 0},
+        ]);
+    }
+
+    #[test]
+    fn test_round_trip_and_replay() {
+        let mut machine = IntcodeMachine::new_automated_machine(&program(), &[3,5,0]);
+        let session = record(&mut machine);
+        let parsed = IntcodeSession::parse(&format!("# doubler\n{}", session.events().iter().map(|event| format!("{:?}\n", event)).collect::<String>())).unwrap();
+
+        assert_eq!(parsed, session);
+        assert_eq!(parsed.replay(&program(), DEFAULT_MAX_STEPS), Ok(5));
+    }
+
+    #[test]
+    fn test_replay_stops_at_divergence() {
+        let session = IntcodeSession::parse("in 0 3\nout 3 7\nin 5 0").unwrap();
+        let divergence = session.replay(&program(), DEFAULT_MAX_STEPS).unwrap_err();
+
+        assert_eq!(divergence.event, 1);
+        assert_eq!(divergence.actual, Some(IntcodeSessionEvent::Output{count: 3, value: 6}));
+    }
+
+    #[test]
+    fn test_replay_reports_missing_events() {
+        let session = IntcodeSession::parse("in 0 0\nout 3 0").unwrap();
+        let divergence = session.replay(&program(), DEFAULT_MAX_STEPS).unwrap_err();
+
+        assert_eq!(divergence.event, 1);
+        assert_eq!(divergence.actual, None);
+        assert_eq!(divergence.reason, "machine halted");
+    }
+
+    #[test]
+    fn test_replay_gives_up_on_endless_loops() {
+        // Reads one input, then loops forever whatever it was
+        let session = IntcodeSession::parse("in 0 1\nout 1 1").unwrap();
+        let divergence = session.replay(&[3,10,1105,1,2], 1000).unwrap_err();
+
+        assert_eq!(divergence.event, 1);
+        assert_eq!(divergence.actual, None);
+        assert_eq!(divergence.reason, "machine still running after 1000 steps");
+    }
+
+    #[test]
+    fn test_blocking_machine_records_inputs() {
+        let mut machine = IntcodeMachine::new_blocking_machine(&program());
+        machine.start_recording();
+        for value in [3, 5, 0] {
+            machine.run();
+            machine.input(value);
+        }
+        machine.run();
+
+        let mut automated = IntcodeMachine::new_automated_machine(&program(), &[3,5,0]);
+        assert_eq!(machine.stop_recording(), Some(record(&mut automated)));
+    }
+}
@@ -0,0 +1,394 @@
+use anyhow::{anyhow, bail, Result};
+
+use crate::intcode::IntcodeProgram;
+use crate::intcode::ascii::IntcodeAsciiMachine;
+
+pub const MAX_INSTRUCTIONS: usize = 15;
+
+// How far a jump carries the droid
+const JUMP_DISTANCE: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntcodeSpringMode {
+    Walk,
+    Run,
+}
+
+impl IntcodeSpringMode {
+    // Sensors A to D when walking, A to I when running
+    pub fn sensors(&self) -> usize {
+        match self {
+            IntcodeSpringMode::Walk => 4,
+            IntcodeSpringMode::Run => 9,
+        }
+    }
+
+    fn readable(&self) -> Vec<char> {
+        ('A'..='I').take(self.sensors()).chain(['T', 'J']).collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntcodeSpringOperation {
+    And,
+    Or,
+    Not,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct IntcodeSpringInstruction {
+    pub operation: IntcodeSpringOperation,
+    pub x: char,
+    pub y: char,
+}
+
+impl std::fmt::Debug for IntcodeSpringInstruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self.operation {
+            IntcodeSpringOperation::And => "AND",
+            IntcodeSpringOperation::Or => "OR",
+            IntcodeSpringOperation::Not => "NOT",
+        };
+        write!(f, "{} {} {}", name, self.x, self.y)
+    }
+}
+
+// A springscript program: instructions over the sensor registers and the T and J registers,
+// ending in WALK or RUN. The droid jumps whenever J is true afterwards.
+#[derive(Clone, PartialEq)]
+pub struct IntcodeSpringScript {
+    pub instructions: Vec<IntcodeSpringInstruction>,
+    pub mode: IntcodeSpringMode,
+}
+
+impl IntcodeSpringScript {
+    pub fn new(instructions: &[IntcodeSpringInstruction], mode: IntcodeSpringMode) -> Result<Self> {
+        let script = Self { instructions: instructions.to_vec(), mode };
+        script.validate()?;
+        Ok(script)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let lines: Vec<(usize, &str)> = text.lines()
+            .map(|line| line.trim())
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty())
+            .collect();
+
+        let (mode, instructions) = match lines.split_last() {
+            Some(((_, "WALK"), instructions)) => (IntcodeSpringMode::Walk, instructions),
+            Some(((_, "RUN"), instructions)) => (IntcodeSpringMode::Run, instructions),
+            _ => bail!("A springscript ends with WALK or RUN"),
+        };
+
+        let instructions = instructions.iter()
+            .map(|(number, line)| parse_instruction(line).map_err(|error| anyhow!("Line {}: {}", number + 1, error)))
+            .collect::<Result<Vec<IntcodeSpringInstruction>>>()?;
+        Self::new(&instructions, mode)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.instructions.len() > MAX_INSTRUCTIONS {
+            bail!("{} instructions is more than the droid's memory of {}", self.instructions.len(), MAX_INSTRUCTIONS);
+        }
+
+        let readable = self.mode.readable();
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            if !readable.contains(&instruction.x) {
+                bail!("Instruction {} ({:?}) reads {}, which can't be read when {:?}", index + 1, instruction, instruction.x, self.mode);
+            }
+            if instruction.y != 'T' && instruction.y != 'J' {
+                bail!("Instruction {} ({:?}) writes {}, which isn't T or J", index + 1, instruction, instruction.y);
+            }
+        }
+        Ok(())
+    }
+
+    // Whether the droid jumps given its sensors, where true is hull
+    pub fn jumps(&self, sensors: &[bool]) -> bool {
+        let (mut t, mut j) = (false, false);
+        for instruction in &self.instructions {
+            let x = match instruction.x {
+                'T' => t,
+                'J' => j,
+                register => sensors[(register as u8 - b'A') as usize],
+            };
+            let y = if instruction.y == 'T' { &mut t } else { &mut j };
+            *y = match instruction.operation {
+                IntcodeSpringOperation::And => x && *y,
+                IntcodeSpringOperation::Or => x || *y,
+                IntcodeSpringOperation::Not => !x,
+            };
+        }
+
+        j
+    }
+
+    // Plays a stretch of hull with the droid starting on its first tile. Everything past the end
+    // counts as hull, so surviving to there means the scenario is passed.
+    pub fn survives(&self, scenario: &IntcodeSpringScenario) -> bool {
+        let ground = |position: usize| scenario.ground.get(position).copied().unwrap_or(true);
+        let mut position = 0;
+        while position < scenario.ground.len() {
+            if !ground(position) {
+                return false;
+            }
+            let sensors: Vec<bool> = (1..=self.mode.sensors()).map(|offset| ground(position + offset)).collect();
+            position += if self.jumps(&sensors) { JUMP_DISTANCE } else { 1 };
+        }
+
+        true
+    }
+}
+
+impl std::fmt::Display for IntcodeSpringScript {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for instruction in &self.instructions {
+            writeln!(f, "{:?}", instruction)?;
+        }
+        writeln!(f, "{}", if self.mode == IntcodeSpringMode::Walk { "WALK" } else { "RUN" })
+    }
+}
+
+impl std::fmt::Debug for IntcodeSpringScript {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+fn parse_instruction(line: &str) -> Result<IntcodeSpringInstruction> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let (operation, x, y) = match tokens[..] {
+        [operation, x, y] => (operation, x, y),
+        _ => bail!("Expected <AND|OR|NOT> <X> <Y>, got '{}'", line),
+    };
+
+    let operation = match operation {
+        "AND" => IntcodeSpringOperation::And,
+        "OR" => IntcodeSpringOperation::Or,
+        "NOT" => IntcodeSpringOperation::Not,
+        _ => bail!("Unknown instruction: {}", operation),
+    };
+    let register = |token: &str| match token.chars().collect::<Vec<char>>()[..] {
+        [register] if register.is_ascii_uppercase() => Ok(register),
+        _ => Err(anyhow!("Invalid register: {}", token)),
+    };
+
+    Ok(IntcodeSpringInstruction { operation, x: register(x)?, y: register(y)? })
+}
+
+// The hull under and ahead of the droid from one of its failure frames, true being hull
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntcodeSpringScenario {
+    pub ground: Vec<bool>,
+}
+
+impl IntcodeSpringScenario {
+    // Takes the first frame: the ground is the first row with hull in it, seen from the column
+    // of the droid in the rows above
+    pub fn from_frames(text: &str) -> Option<Self> {
+        let rows: Vec<&str> = text.lines()
+            .filter(|line| !line.is_empty() && line.chars().all(|c| matches!(c, '.' | '#' | '@')))
+            .collect();
+        let ground_row = rows.iter().position(|row| row.contains('#'))?;
+        let droid = rows[..ground_row].iter().find_map(|row| row.find('@'))?;
+
+        let ground = rows[ground_row].chars().skip(droid).map(|c| c == '#').collect();
+        Some(Self { ground })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntcodeSpringOutcome {
+    // The hull damage the droid reported after making it across
+    Survived(i64),
+    Fell { frames: String, scenario: Option<IntcodeSpringScenario> },
+}
+
+// Sends the script as ASCII lines and reads back either the damage or the failure frames
+pub fn run(program: &[i64], script: &IntcodeSpringScript) -> Result<IntcodeSpringOutcome> {
+    script.validate()?;
+
+    let mut machine = IntcodeAsciiMachine::new(program);
+    let mut text = machine.run()?;
+    for line in script.to_string().lines() {
+        text.push_str(&machine.send_line(line)?);
+    }
+    text.push_str(&machine.run()?);
+
+    match machine.value() {
+        Some(damage) => Ok(IntcodeSpringOutcome::Survived(damage)),
+        None => {
+            let frames = text.find("Didn't make it across").map(|start| &text[start..]).unwrap_or(&text).to_string();
+            let scenario = IntcodeSpringScenario::from_frames(&frames);
+            Ok(IntcodeSpringOutcome::Fell { frames, scenario })
+        },
+    }
+}
+
+// Every script of up to `max_length` instructions, shortest first, until one survives all the
+// scenarios. Only suitable for short scripts, as there are (3 * registers * 2)^length of them.
+pub fn search(scenarios: &[IntcodeSpringScenario], mode: IntcodeSpringMode, max_length: usize) -> Option<IntcodeSpringScript> {
+    let mut choices = Vec::new();
+    for operation in [IntcodeSpringOperation::And, IntcodeSpringOperation::Or, IntcodeSpringOperation::Not] {
+        for x in mode.readable() {
+            for y in ['T', 'J'] {
+                choices.push(IntcodeSpringInstruction { operation, x, y });
+            }
+        }
+    }
+
+    for length in 1..=max_length.min(MAX_INSTRUCTIONS) {
+        let mut indices = vec![0; length];
+        loop {
+            let instructions: Vec<IntcodeSpringInstruction> = indices.iter().map(|index| choices[*index]).collect();
+            // Only J decides anything, so scripts whose last instruction writes T are never needed
+            if instructions[length - 1].y == 'J' {
+                let script = IntcodeSpringScript { instructions, mode };
+                if scenarios.iter().all(|scenario| script.survives(scenario)) {
+                    return Some(script);
+                }
+            }
+
+            // Next combination, like counting in base choices.len()
+            let mut position = length;
+            while position > 0 {
+                position -= 1;
+                indices[position] += 1;
+                if indices[position] < choices.len() {
+                    break;
+                }
+                indices[position] = 0;
+            }
+            if indices.iter().all(|index| *index == 0) {
+                break;
+            }
+        }
+    }
+
+    None
+}
+
+// Alternates between searching for a script that passes every scenario seen so far and trying it
+// on the program, which either succeeds or shows a new scenario
+pub fn solve(program: &[i64], mode: IntcodeSpringMode, max_length: usize) -> Result<(IntcodeSpringScript, i64)> {
+    let mut scenarios = Vec::new();
+    loop {
+        let script = search(&scenarios, mode, max_length)
+            .ok_or(anyhow!("No script of up to {} instructions passes all {} scenarios", max_length, scenarios.len()))?;
+        match run(program, &script)? {
+            IntcodeSpringOutcome::Survived(damage) => return Ok((script, damage)),
+            IntcodeSpringOutcome::Fell { scenario: Some(scenario), .. } if !scenarios.contains(&scenario) => scenarios.push(scenario),
+            IntcodeSpringOutcome::Fell { frames, .. } => bail!("Script failed without a new scenario:\n{}{}", script, frames),
+        }
+    }
+}
+
+// Usage: springdroid <file in input/> <script file>
+//        springdroid <file in input/> --search <walk|run> <max length>
+pub fn run_command(args: &[String]) -> Result<String> {
+    let program = IntcodeProgram::load_from_args(args)?.code;
+
+    match &args[1..] {
+        [flag, mode, max_length] if flag == "--search" => {
+            let mode = match mode.as_str() {
+                "walk" => IntcodeSpringMode::Walk,
+                "run" => IntcodeSpringMode::Run,
+                _ => bail!("Mode must be walk or run"),
+            };
+            let max_length = max_length.parse().map_err(|_| anyhow!("Invalid length: {}", max_length))?;
+            let (script, damage) = solve(&program, mode, max_length)?;
+            Ok(format!("{}Hull damage: {}", script, damage))
+        },
+        [script_file] => {
+            let script = IntcodeSpringScript::parse(&std::fs::read_to_string(script_file)?)?;
+            match run(&program, &script)? {
+                IntcodeSpringOutcome::Survived(damage) => Ok(format!("Hull damage: {}", damage)),
+                IntcodeSpringOutcome::Fell { frames, .. } => Ok(frames),
+            }
+        },
+        _ => bail!("Usage: springdroid <program file> <script file> | --search <walk|run> <max length>"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAMES: &str = "\
+Input instructions:
+
+Walking...
+
+
+Didn't make it across:
+
+.................
+.................
+@................
+#####.###########
+
+.................
+.................
+.@...............
+#####.###########
+";
+
+    fn scenario(ground: &str) -> IntcodeSpringScenario {
+        IntcodeSpringScenario { ground: ground.chars().map(|c| c == '#').collect() }
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        let text = "NOT A J\nNOT B T\nOR T J\n\nAND D J\nWALK\n";
+        let script = IntcodeSpringScript::parse(text).unwrap();
+
+        assert_eq!(script.instructions.len(), 4);
+        assert_eq!(script.mode, IntcodeSpringMode::Walk);
+        assert_eq!(script.to_string(), text.replace("\n\n", "\n"));
+    }
+
+    #[test]
+    fn test_validation() {
+        assert_eq!(IntcodeSpringScript::parse("NOT E J\nWALK").unwrap_err().to_string(), "Instruction 1 (NOT E J) reads E, which can't be read when Walk");
+        assert!(IntcodeSpringScript::parse("NOT E J\nRUN").is_ok());
+        assert!(IntcodeSpringScript::parse("NOT A B\nWALK").is_err());
+        assert_eq!(IntcodeSpringScript::parse("NOT A J\nXOR A J\nWALK").unwrap_err().to_string(), "Line 2: Unknown instruction: XOR");
+        assert!(IntcodeSpringScript::parse("NOT A J").is_err());
+        let too_long = format!("{}WALK", "NOT A J\n".repeat(16));
+        assert_eq!(IntcodeSpringScript::parse(&too_long).unwrap_err().to_string(), "16 instructions is more than the droid's memory of 15");
+    }
+
+    #[test]
+    fn test_simulation() {
+        let script = IntcodeSpringScript::parse("NOT A J\nWALK").unwrap();
+        assert!(script.jumps(&[false, true, true, true]));
+        assert!(script.survives(&scenario("#####.###########")));
+        assert!(!script.survives(&scenario("#####..#.########")));
+    }
+
+    #[test]
+    fn test_scenario_from_frames() {
+        assert_eq!(IntcodeSpringScenario::from_frames(FRAMES), Some(scenario("#####.###########")));
+        assert_eq!(IntcodeSpringScenario::from_frames("Input instructions:\n"), None);
+    }
+
+    #[test]
+    fn test_run_sends_script() {
+        // Reads two lines and then reports 12345 as the damage
+        let program = vec![3,100,1008,100,10,101,1,102,101,102,1007,102,2,103,1005,103,0,104,12345,99];
+        let script = IntcodeSpringScript::parse("NOT A J\nWALK").unwrap();
+
+        assert_eq!(run(&program, &script).unwrap(), IntcodeSpringOutcome::Survived(12345));
+    }
+
+    #[test]
+    fn test_search() {
+        let scenarios = vec![scenario("#####.###########"), scenario("#####..#.########")];
+        let script = search(&scenarios, IntcodeSpringMode::Walk, 3).unwrap();
+
+        assert!(scenarios.iter().all(|scenario| script.survives(scenario)));
+        assert!(search(&scenarios[..1], IntcodeSpringMode::Walk, 1).is_some());
+        assert_eq!(search(&[scenario("#....#")], IntcodeSpringMode::Walk, 2), None);
+    }
+}
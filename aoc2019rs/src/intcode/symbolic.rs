@@ -0,0 +1,732 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::RangeInclusive;
+
+use crate::utils::math::{self, RangeProduct};
+
+use super::{IntcodeMachine, IntcodeInstruction, IntcodeValue, IntcodeFault, IntcodeState};
+use super::{IntcodeInput, IntcodeOutput, IntcodeQueueInput, IntcodeHistoryOutput};
+
+const DEFAULT_MAX_STEPS: usize = 10_000_000;
+
+#[derive(Clone, PartialEq)]
+pub enum IntcodeExpression {
+    Constant(i64),
+    Symbol(String),
+    Add(Box<IntcodeExpression>, Box<IntcodeExpression>),
+    Multiply(Box<IntcodeExpression>, Box<IntcodeExpression>),
+    LessThan(Box<IntcodeExpression>, Box<IntcodeExpression>),
+    Equals(Box<IntcodeExpression>, Box<IntcodeExpression>),
+    // A read through an address that depended on a symbol
+    Load(Box<IntcodeExpression>),
+}
+
+// Monomial (symbol names, repeated for powers) -> coefficient
+type Polynomial = BTreeMap<Vec<String>, i64>;
+
+impl IntcodeExpression {
+    pub fn symbol(name: &str) -> Self {
+        IntcodeExpression::Symbol(String::from(name))
+    }
+
+    pub fn add(x: Self, y: Self) -> Self {
+        use IntcodeExpression::*;
+
+        match (x, y) {
+            (Constant(x), Constant(y)) if x.checked_add(y).is_some() => Constant(x + y),
+            (Constant(0), other) | (other, Constant(0)) => other,
+            (x, y) => Add(Box::new(x), Box::new(y)),
+        }
+    }
+
+    pub fn multiply(x: Self, y: Self) -> Self {
+        use IntcodeExpression::*;
+
+        match (x, y) {
+            (Constant(x), Constant(y)) if x.checked_mul(y).is_some() => Constant(x * y),
+            (Constant(0), _) | (_, Constant(0)) => Constant(0),
+            (Constant(1), other) | (other, Constant(1)) => other,
+            (x, y) => Multiply(Box::new(x), Box::new(y)),
+        }
+    }
+
+    pub fn less_than(x: Self, y: Self) -> Self {
+        use IntcodeExpression::*;
+
+        match (x, y) {
+            (Constant(x), Constant(y)) => Constant((x < y) as i64),
+            (x, y) => LessThan(Box::new(x), Box::new(y)),
+        }
+    }
+
+    pub fn equals(x: Self, y: Self) -> Self {
+        use IntcodeExpression::*;
+
+        match (x, y) {
+            (Constant(x), Constant(y)) => Constant((x == y) as i64),
+            (x, y) => Equals(Box::new(x), Box::new(y)),
+        }
+    }
+
+    pub fn constant(&self) -> Option<i64> {
+        match self {
+            IntcodeExpression::Constant(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn symbols(&self) -> BTreeSet<String> {
+        use IntcodeExpression::*;
+
+        let mut symbols = BTreeSet::new();
+        match self {
+            Constant(_) => {},
+            Symbol(name) => { symbols.insert(name.clone()); },
+            Add(x, y) | Multiply(x, y) | LessThan(x, y) | Equals(x, y) => {
+                symbols.extend(x.symbols());
+                symbols.extend(y.symbols());
+            },
+            Load(address) => symbols.extend(address.symbols()),
+        }
+        symbols
+    }
+
+    pub fn evaluate(&self, values: &HashMap<String, i64>) -> Option<i64> {
+        use IntcodeExpression::*;
+
+        match self {
+            Constant(value) => Some(*value),
+            Symbol(name) => values.get(name).copied(),
+            Add(x, y) => x.evaluate(values)?.checked_add(y.evaluate(values)?),
+            Multiply(x, y) => x.evaluate(values)?.checked_mul(y.evaluate(values)?),
+            LessThan(x, y) => Some((x.evaluate(values)? < y.evaluate(values)?) as i64),
+            Equals(x, y) => Some((x.evaluate(values)? == y.evaluate(values)?) as i64),
+            Load(_) => None,
+        }
+    }
+
+    pub fn simplify(&self) -> Self {
+        match self.polynomial() {
+            Some(polynomial) => from_polynomial(&polynomial),
+            None => self.clone(),
+        }
+    }
+
+    fn polynomial(&self) -> Option<Polynomial> {
+        use IntcodeExpression::*;
+
+        let mut polynomial = Polynomial::new();
+        match self {
+            Constant(value) => {
+                polynomial.insert(Vec::new(), *value);
+            },
+            Symbol(name) => {
+                polynomial.insert(vec![name.clone()], 1);
+            },
+            Add(x, y) => {
+                polynomial = x.polynomial()?;
+                for (monomial, coefficient) in y.polynomial()? {
+                    let entry = polynomial.entry(monomial).or_insert(0);
+                    *entry = entry.checked_add(coefficient)?;
+                }
+            },
+            Multiply(x, y) => {
+                let y = y.polynomial()?;
+                for (x_monomial, x_coefficient) in x.polynomial()? {
+                    for (y_monomial, y_coefficient) in &y {
+                        let mut monomial = x_monomial.clone();
+                        monomial.extend(y_monomial.iter().cloned());
+                        monomial.sort();
+                        let entry = polynomial.entry(monomial).or_insert(0);
+                        *entry = entry.checked_add(x_coefficient.checked_mul(*y_coefficient)?)?;
+                    }
+                }
+            },
+            LessThan(..) | Equals(..) | Load(_) => return None,
+        }
+
+        polynomial.retain(|_, coefficient| *coefficient != 0);
+        Some(polynomial)
+    }
+}
+
+fn from_polynomial(polynomial: &Polynomial) -> IntcodeExpression {
+    use IntcodeExpression::*;
+
+    // Highest degree terms first, constant last
+    let mut terms: Vec<(&Vec<String>, &i64)> = polynomial.iter().collect();
+    terms.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()).then(a.cmp(b)));
+
+    terms.into_iter()
+        .map(|(monomial, coefficient)| {
+            monomial.iter().fold(Constant(*coefficient), |term, name| IntcodeExpression::multiply(term, Symbol(name.clone())))
+        })
+        .fold(Constant(0), |sum, term| match sum {
+            Constant(0) => term,
+            sum => Add(Box::new(sum), Box::new(term)),
+        })
+}
+
+impl std::fmt::Debug for IntcodeExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use IntcodeExpression::*;
+
+        match self {
+            Constant(value) => write!(f, "{}", value),
+            Symbol(name) => write!(f, "{}", name),
+            Add(x, y) => write!(f, "({:?} + {:?})", x, y),
+            Multiply(x, y) => write!(f, "{:?} * {:?}", x, y),
+            LessThan(x, y) => write!(f, "({:?} < {:?})", x, y),
+            Equals(x, y) => write!(f, "({:?} == {:?})", x, y),
+            Load(address) => write!(f, "[{:?}]", address),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntcodeSymbolicError {
+    SymbolicBranch(usize),
+    SymbolicAddress(usize),
+    SymbolicInstruction(usize),
+    StepLimitReached(usize),
+    Fault(IntcodeFault),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum IntcodeSymbolicState {
+    Running,
+    Suspended,
+    Halted,
+}
+
+pub struct IntcodeSymbolicMachine {
+    state: IntcodeSymbolicState,
+    instruction_pointer: usize,
+    relative_base: usize,
+    memory: Vec<IntcodeExpression>,
+    inputs: Vec<IntcodeExpression>,
+    outputs: Vec<IntcodeExpression>,
+}
+
+impl IntcodeSymbolicMachine {
+    pub fn new(machine_code: &[i64]) -> Self {
+        let machine = IntcodeMachine::new(machine_code, IntcodeQueueInput::new(&[]), IntcodeHistoryOutput::new());
+        Self::from_machine(&machine)
+    }
+
+    pub fn from_machine<I, O>(machine: &IntcodeMachine<I, O>) -> Self
+    where I: IntcodeInput,
+          O: IntcodeOutput,
+    {
+        Self {
+            state: IntcodeSymbolicState::Running,
+            instruction_pointer: machine.instruction_pointer(),
+            relative_base: machine.relative_base(),
+            memory: machine.memory().iter().map(|value| IntcodeExpression::Constant(*value)).collect(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+
+    pub fn make_symbolic(&mut self, position: usize, name: &str) {
+        self.memory[position] = IntcodeExpression::symbol(name);
+    }
+
+    pub fn write_memory(&mut self, position: usize, value: IntcodeExpression) {
+        self.memory[position] = value;
+    }
+
+    pub fn push_input(&mut self, value: IntcodeExpression) {
+        self.inputs.push(value);
+    }
+
+    pub fn expression_at(&self, position: usize) -> &IntcodeExpression {
+        &self.memory[position]
+    }
+
+    pub fn outputs(&self) -> &[IntcodeExpression] {
+        &self.outputs
+    }
+
+    pub fn state(&self) -> &IntcodeSymbolicState {
+        &self.state
+    }
+
+    pub fn run(&mut self) -> Result<(), IntcodeSymbolicError> {
+        self.state = IntcodeSymbolicState::Running;
+        let mut steps = 0;
+        while self.state == IntcodeSymbolicState::Running {
+            if steps == DEFAULT_MAX_STEPS {
+                return Err(IntcodeSymbolicError::StepLimitReached(steps));
+            }
+            self.step()?;
+            steps += 1;
+        }
+        Ok(())
+    }
+
+    pub fn step(&mut self) -> Result<(), IntcodeSymbolicError> {
+        let instruction = self.decode()?;
+        self.operate(instruction)
+    }
+
+    fn decode(&self) -> Result<IntcodeInstruction, IntcodeSymbolicError> {
+        let ptr = self.instruction_pointer;
+        let fault = |fault| Err(IntcodeSymbolicError::Fault(fault));
+
+        let opcode = match self.memory.get(ptr) {
+            Some(IntcodeExpression::Constant(opcode)) => *opcode,
+            Some(_) => return Err(IntcodeSymbolicError::SymbolicInstruction(ptr)),
+            None => return fault(IntcodeFault::InstructionPointerOutOfRange(ptr)),
+        };
+
+        // Symbolic parameter words are decoded as placeholders; reads through them become opaque loads
+        let params: Vec<i64> = self.memory[ptr+1..].iter()
+            .take(3)
+            .map(|word| word.constant().unwrap_or(0))
+            .collect();
+        let instruction = match IntcodeInstruction::try_new(opcode, &params) {
+            Ok(instruction) => instruction,
+            Err(error) => return fault(error),
+        };
+
+        // The written position is always the last parameter and has to be known
+        let writes_symbolic_address = instruction.written_target().is_some()
+            && self.memory[ptr + instruction.size() - 1].constant().is_none();
+        if writes_symbolic_address {
+            return Err(IntcodeSymbolicError::SymbolicAddress(ptr));
+        }
+
+        Ok(instruction)
+    }
+
+    fn read(&self, value: &IntcodeValue, index: usize) -> Result<IntcodeExpression, IntcodeSymbolicError> {
+        let word = &self.memory[self.instruction_pointer + 1 + index];
+        if word.constant().is_none() {
+            let address = match value {
+                IntcodeValue::Relative(_) => IntcodeExpression::add(IntcodeExpression::Constant(self.relative_base as i64), word.clone()),
+                _ => word.clone(),
+            };
+            return match value {
+                IntcodeValue::Immediate(_) => Ok(word.clone()),
+                _ => Ok(IntcodeExpression::Load(Box::new(address))),
+            };
+        }
+
+        let address = match value {
+            IntcodeValue::Position(position) => *position as i64,
+            IntcodeValue::Immediate(value) => return Ok(IntcodeExpression::Constant(*value)),
+            IntcodeValue::Relative(offset) => (self.relative_base as i64).checked_add(*offset)
+                .ok_or(IntcodeSymbolicError::Fault(IntcodeFault::Overflow))?,
+        };
+
+        if address < 0 || address as usize >= self.memory.len() {
+            return Err(IntcodeSymbolicError::Fault(IntcodeFault::MemoryOutOfRange(address)));
+        }
+        Ok(self.memory[address as usize].clone())
+    }
+
+    fn read_concrete(&self, value: &IntcodeValue, index: usize) -> Result<i64, IntcodeSymbolicError> {
+        self.read(value, index)?
+            .constant()
+            .ok_or(IntcodeSymbolicError::SymbolicBranch(self.instruction_pointer))
+    }
+
+    fn write(&mut self, target: &IntcodeValue, value: IntcodeExpression) -> Result<(), IntcodeSymbolicError> {
+        let address = target.address(self.relative_base).map_err(IntcodeSymbolicError::Fault)?.unwrap_or(-1);
+        match self.memory.get_mut(address as usize) {
+            Some(cell) if address >= 0 => {
+                *cell = value;
+                Ok(())
+            },
+            _ => Err(IntcodeSymbolicError::Fault(IntcodeFault::MemoryOutOfRange(address))),
+        }
+    }
+
+    fn operate(&mut self, instruction: IntcodeInstruction) -> Result<(), IntcodeSymbolicError> {
+        use IntcodeInstruction::*;
+
+        match instruction {
+            Add{x, y, position} => {
+                let value = IntcodeExpression::add(self.read(&x, 0)?, self.read(&y, 1)?);
+                self.write(&position, value)?;
+                self.instruction_pointer += 4;
+            },
+            Multiply{x, y, position} => {
+                let value = IntcodeExpression::multiply(self.read(&x, 0)?, self.read(&y, 1)?);
+                self.write(&position, value)?;
+                self.instruction_pointer += 4;
+            },
+            Input{position} => {
+                if self.inputs.is_empty() {
+                    self.state = IntcodeSymbolicState::Suspended;
+                } else {
+                    let input = self.inputs.remove(0);
+                    self.write(&position, input)?;
+                    self.instruction_pointer += 2;
+                }
+            },
+            Output{value} => {
+                let value = self.read(&value, 0)?;
+                self.outputs.push(value);
+                self.instruction_pointer += 2;
+            },
+            JumpIfTrue{test_position, jump_position} => {
+                if self.read_concrete(&test_position, 0)? != 0 {
+                    self.instruction_pointer = self.read_concrete(&jump_position, 1)? as usize;
+                } else {
+                    self.instruction_pointer += 3;
+                }
+            },
+            JumpIfFalse{test_position, jump_position} => {
+                if self.read_concrete(&test_position, 0)? == 0 {
+                    self.instruction_pointer = self.read_concrete(&jump_position, 1)? as usize;
+                } else {
+                    self.instruction_pointer += 3;
+                }
+            },
+            IsLessThan{x, y, position} => {
+                let value = IntcodeExpression::less_than(self.read(&x, 0)?, self.read(&y, 1)?);
+                self.write(&position, value)?;
+                self.instruction_pointer += 4;
+            },
+            IsEquals{x, y, position} => {
+                let value = IntcodeExpression::equals(self.read(&x, 0)?, self.read(&y, 1)?);
+                self.write(&position, value)?;
+                self.instruction_pointer += 4;
+            },
+            SetRelativeBase{offset} => {
+                let relative_base = (self.relative_base as i64).checked_add(self.read_concrete(&offset, 0)?)
+                    .ok_or(IntcodeSymbolicError::Fault(IntcodeFault::Overflow))?;
+                if relative_base < 0 {
+                    return Err(IntcodeSymbolicError::Fault(IntcodeFault::InvalidRelativeBase(relative_base)));
+                }
+                self.relative_base = relative_base as usize;
+                self.instruction_pointer += 2;
+            },
+            Halt => self.state = IntcodeSymbolicState::Halted,
+            Extension{opcode, ..} => return Err(IntcodeSymbolicError::Fault(IntcodeFault::InvalidInstruction(opcode))),
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntcodeSolveMethod {
+    Symbolic,
+    BruteForce,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntcodeSolution {
+    pub values: Vec<(String, i64)>,
+    pub method: IntcodeSolveMethod,
+}
+
+impl IntcodeSolution {
+    pub fn value(&self, name: &str) -> Option<i64> {
+        self.values.iter().find(|(n, _)| n == name).map(|(_, value)| *value)
+    }
+}
+
+enum UnknownSource {
+    Memory(usize),
+    Input,
+}
+
+struct Unknown {
+    name: String,
+    source: UnknownSource,
+    domain: RangeInclusive<i64>,
+}
+
+enum SolveTarget {
+    Memory(usize),
+    Output(usize),
+}
+
+// Finds values for unknown memory cells or inputs that make a memory cell or output equal a target.
+// The program is run once symbolically; if control flow depends on an unknown, every combination is run concretely.
+pub struct IntcodeSolver {
+    program: Vec<i64>,
+    unknowns: Vec<Unknown>,
+}
+
+impl IntcodeSolver {
+    pub fn new(program: &[i64]) -> Self {
+        Self { program: program.to_vec(), unknowns: Vec::new() }
+    }
+
+    pub fn unknown_memory(&mut self, position: usize, name: &str, domain: RangeInclusive<i64>) {
+        self.unknowns.push(Unknown { name: String::from(name), source: UnknownSource::Memory(position), domain });
+    }
+
+    pub fn unknown_input(&mut self, name: &str, domain: RangeInclusive<i64>) {
+        self.unknowns.push(Unknown { name: String::from(name), source: UnknownSource::Input, domain });
+    }
+
+    pub fn symbolic_machine(&self) -> IntcodeSymbolicMachine {
+        let mut machine = IntcodeSymbolicMachine::new(&self.program);
+        for unknown in &self.unknowns {
+            match unknown.source {
+                UnknownSource::Memory(position) => machine.make_symbolic(position, &unknown.name),
+                UnknownSource::Input => machine.push_input(IntcodeExpression::symbol(&unknown.name)),
+            }
+        }
+        machine
+    }
+
+    pub fn solve_memory(&self, position: usize, target: i64) -> Option<IntcodeSolution> {
+        self.solve(SolveTarget::Memory(position), target)
+    }
+
+    pub fn solve_output(&self, index: usize, target: i64) -> Option<IntcodeSolution> {
+        self.solve(SolveTarget::Output(index), target)
+    }
+
+    fn solve(&self, target: SolveTarget, value: i64) -> Option<IntcodeSolution> {
+        let mut machine = self.symbolic_machine();
+        let expression = match machine.run() {
+            // A program still waiting for input hasn't produced its result yet
+            Ok(()) if machine.state() != &IntcodeSymbolicState::Halted => return None,
+            Ok(()) => match target {
+                SolveTarget::Memory(position) => machine.memory.get(position).cloned(),
+                SolveTarget::Output(index) => machine.outputs.get(index).cloned(),
+            },
+            Err(_) => None,
+        };
+
+        match expression.and_then(|expression| expression.polynomial()) {
+            Some(polynomial) => self.solve_polynomial(&polynomial, value),
+            None => self.brute_force(&target, value),
+        }
+    }
+
+    // Enumerates every unknown but the last, then solves for the last one directly when it appears linearly.
+    fn solve_polynomial(&self, polynomial: &Polynomial, target: i64) -> Option<IntcodeSolution> {
+        let (last, rest) = self.unknowns.split_last()?;
+
+        for assignment in assignments(rest) {
+            let mut values: HashMap<String, i64> = rest.iter()
+                .map(|unknown| unknown.name.clone())
+                .zip(assignment.iter().copied())
+                .collect();
+
+            let candidate = match linear_in(polynomial, &last.name, &values) {
+                Some((0, constant)) if constant == target => Some(*last.domain.start()),
+                Some((0, _)) => None,
+                Some((coefficient, constant)) => target.checked_sub(constant)
+                    .filter(|remainder| remainder.checked_rem(coefficient) == Some(0))
+                    .and_then(|remainder| remainder.checked_div(coefficient)),
+                None => last.domain.clone().find(|candidate| {
+                    values.insert(last.name.clone(), *candidate);
+                    evaluate_polynomial(polynomial, &values) == Some(target)
+                }),
+            };
+
+            if let Some(candidate) = candidate.filter(|candidate| last.domain.contains(candidate)) {
+                let mut solution = assignment;
+                solution.push(candidate);
+                return Some(self.solution(solution, IntcodeSolveMethod::Symbolic));
+            }
+        }
+
+        None
+    }
+
+    fn brute_force(&self, target: &SolveTarget, value: i64) -> Option<IntcodeSolution> {
+        assignments(&self.unknowns)
+            .find(|assignment| self.run_concrete(assignment, target) == Some(value))
+            .map(|assignment| self.solution(assignment, IntcodeSolveMethod::BruteForce))
+    }
+
+    fn run_concrete(&self, assignment: &[i64], target: &SolveTarget) -> Option<i64> {
+        let inputs: Vec<i64> = self.unknowns.iter()
+            .zip(assignment.iter())
+            .filter(|(unknown, _)| matches!(unknown.source, UnknownSource::Input))
+            .map(|(_, value)| *value)
+            .collect();
+
+        let mut machine = IntcodeMachine::new(&self.program, IntcodeQueueInput::new(&inputs), IntcodeHistoryOutput::new());
+        for (unknown, value) in self.unknowns.iter().zip(assignment.iter()) {
+            if let UnknownSource::Memory(position) = unknown.source {
+                machine.write_memory(position, *value);
+            }
+        }
+
+        for _ in 0..DEFAULT_MAX_STEPS {
+            if machine.step().is_err() {
+                return None;
+            }
+            if machine.state() != &IntcodeState::Running {
+                break;
+            }
+        }
+        if machine.state() != &IntcodeState::Halted {
+            return None;
+        }
+
+        match target {
+            SolveTarget::Memory(position) => machine.memory().get(*position).copied(),
+            SolveTarget::Output(index) => machine.output_handler().history().get(*index).and_then(|output| output.parse().ok()),
+        }
+    }
+
+    fn solution(&self, assignment: Vec<i64>, method: IntcodeSolveMethod) -> IntcodeSolution {
+        IntcodeSolution {
+            values: self.unknowns.iter().map(|unknown| unknown.name.clone()).zip(assignment).collect(),
+            method,
+        }
+    }
+}
+
+fn assignments(unknowns: &[Unknown]) -> RangeProduct {
+    let domains: Vec<RangeInclusive<i64>> = unknowns.iter().map(|unknown| unknown.domain.clone()).collect();
+    math::product(&domains)
+}
+
+fn evaluate_polynomial(polynomial: &Polynomial, values: &HashMap<String, i64>) -> Option<i64> {
+    polynomial.iter().try_fold(0_i64, |sum, (monomial, coefficient)| {
+        let term = monomial.iter().try_fold(*coefficient, |product, name| product.checked_mul(*values.get(name)?))?;
+        sum.checked_add(term)
+    })
+}
+
+// Splits the polynomial into coefficient * name + constant once every other symbol is known.
+fn linear_in(polynomial: &Polynomial, name: &str, values: &HashMap<String, i64>) -> Option<(i64, i64)> {
+    let mut coefficient = 0_i64;
+    let mut constant = 0_i64;
+
+    for (monomial, term_coefficient) in polynomial {
+        let degree = monomial.iter().filter(|symbol| *symbol == name).count();
+        let known = monomial.iter()
+            .filter(|symbol| *symbol != name)
+            .try_fold(*term_coefficient, |product, symbol| product.checked_mul(*values.get(symbol)?))?;
+
+        match degree {
+            0 => constant = constant.checked_add(known)?,
+            1 => coefficient = coefficient.checked_add(known)?,
+            _ => return None,
+        }
+    }
+
+    Some((coefficient, constant))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use IntcodeExpression::*;
+    use crate::intcode::IntcodeProgram;
+
+    #[test]
+    fn test_expression_at_address() {
+        let mut machine = IntcodeSymbolicMachine::new(&[1,5,6,0,99,0,0]);
+        machine.make_symbolic(5, "a");
+        machine.make_symbolic(6, "b");
+        machine.run().unwrap();
+        assert_eq!(machine.expression_at(0), &Add(Box::new(Symbol(String::from("a"))), Box::new(Symbol(String::from("b")))));
+    }
+
+    #[test]
+    fn test_simplify() {
+        let x = IntcodeExpression::symbol("x");
+        let expression = IntcodeExpression::add(
+            IntcodeExpression::multiply(IntcodeExpression::add(x.clone(), Constant(2)), Constant(3)),
+            IntcodeExpression::multiply(x.clone(), x.clone()));
+        assert_eq!(format!("{:?}", expression.simplify()), "((x * x + 3 * x) + 6)");
+    }
+
+    #[test]
+    fn test_symbolic_inputs() {
+        let mut machine = IntcodeSymbolicMachine::new(&[3,9,1002,9,3,9,4,9,99,0]);
+        machine.push_input(IntcodeExpression::symbol("n"));
+        machine.run().unwrap();
+        assert_eq!(format!("{:?}", machine.outputs()), "[n * 3]");
+    }
+
+    #[test]
+    fn test_symbolic_branch_is_reported() {
+        let mut machine = IntcodeSymbolicMachine::new(&[3,7,1005,7,6,99,99,0]);
+        machine.push_input(IntcodeExpression::symbol("n"));
+        assert_eq!(machine.run(), Err(IntcodeSymbolicError::SymbolicBranch(2)));
+    }
+
+    #[test]
+    fn test_relative_base_overflow_is_a_fault() {
+        let mut machine = IntcodeSymbolicMachine::new(&[109,1,109,9223372036854775807,99]);
+        assert_eq!(machine.run(), Err(IntcodeSymbolicError::Fault(IntcodeFault::Overflow)));
+
+        let mut machine = IntcodeSymbolicMachine::new(&[109,1,204,9223372036854775807,99]);
+        assert_eq!(machine.run(), Err(IntcodeSymbolicError::Fault(IntcodeFault::Overflow)));
+    }
+
+    #[test]
+    fn test_solve_day2() {
+        let program = IntcodeProgram::load(crate::utils::input::input_file_name(2)).unwrap().code;
+        let mut solver = IntcodeSolver::new(&program);
+        solver.unknown_memory(1, "noun", 0..=99);
+        solver.unknown_memory(2, "verb", 0..=99);
+
+        let solution = solver.solve_memory(0, 19690720).unwrap();
+        assert_eq!(solution.method, IntcodeSolveMethod::Symbolic);
+        assert_eq!(100 * solution.value("noun").unwrap() + solution.value("verb").unwrap(), 3376);
+    }
+
+    #[test]
+    fn test_solve_polynomial_input() {
+        // out = n * n + 5
+        let program = vec![3,13,2,13,13,14,1001,14,5,14,4,14,99];
+        let mut solver = IntcodeSolver::new(&program);
+        solver.unknown_input("n", 0..=20);
+
+        let solution = solver.solve_output(0, 174).unwrap();
+        assert_eq!(solution.method, IntcodeSolveMethod::Symbolic);
+        assert_eq!(solution.value("n"), Some(13));
+    }
+
+    #[test]
+    fn test_falls_back_to_brute_force() {
+        // if n == 8 { out 1 } else { out 0 }, with the comparison feeding a branch
+        let program = vec![3,3,1108,-1,8,3,1005,3,13,104,0,99,0,104,1,99];
+        let mut solver = IntcodeSolver::new(&program);
+        solver.unknown_input("n", 0..=20);
+
+        let solution = solver.solve_output(0, 1).unwrap();
+        assert_eq!(solution.method, IntcodeSolveMethod::BruteForce);
+        assert_eq!(solution.value("n"), Some(8));
+    }
+
+    #[test]
+    fn test_brute_force_over_wide_domains() {
+        // The same branch as above, but with more candidates than could ever be listed
+        let program = vec![3,3,1108,-1,8,3,1005,3,13,104,0,99,0,104,1,99];
+        let mut solver = IntcodeSolver::new(&program);
+        solver.unknown_input("n", 0..=i64::MAX);
+
+        assert_eq!(solver.solve_output(0, 1).and_then(|solution| solution.value("n")), Some(8));
+    }
+
+    #[test]
+    fn test_solve_without_overflowing_division() {
+        // out = -n, with no n giving i64::MIN because -i64::MIN doesn't fit
+        let mut solver = IntcodeSolver::new(&[3,9,1002,9,-1,9,4,9,99,0]);
+        solver.unknown_input("n", -5..=5);
+        assert_eq!(solver.solve_output(0, i64::MIN), None);
+        assert_eq!(solver.solve_output(0, 3).and_then(|solution| solution.value("n")), Some(-3));
+    }
+
+    #[test]
+    fn test_unfinished_runs_are_not_solutions() {
+        // Outputs x + 1 and then waits for an input that never comes
+        let mut solver = IntcodeSolver::new(&[1001,9,1,10,4,10,3,0,99,0,0]);
+        solver.unknown_memory(9, "x", 0..=9);
+        assert_eq!(solver.solve_output(0, 5), None);
+
+        // Only waits after the branch that outputs 1
+        let mut solver = IntcodeSolver::new(&[3,3,1108,-1,8,3,1005,3,13,104,0,99,0,104,1,3,0,99]);
+        solver.unknown_input("n", 0..=20);
+        assert_eq!(solver.solve_output(0, 1), None);
+        assert_eq!(solver.solve_output(0, 0).and_then(|solution| solution.value("n")), Some(0));
+    }
+}
@@ -0,0 +1,282 @@
+use anyhow::{anyhow, bail, Result};
+
+use crate::intcode::IntcodeOutput;
+use crate::intcode::scheduler::{IntcodeScheduler, IntcodeSchedulerOutcome, IntcodeSchedulingPolicy, DEFAULT_TIME_SLICE};
+
+const MAX_INSTRUCTIONS: usize = 100_000_000;
+
+// Describes a network of machines, either built in code or parsed from text like
+//
+//   # amplifier ring with phase settings
+//   A[9, 0] -> B[8] -> C[7] -> D[6] -> E[5] -> A
+//   output E
+//
+// Machines are created when first mentioned, `[...]` appends initial inputs, and each side of
+// an arrow can list several machines (`A -> B, C` fans out, `B, C -> D` fans in).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IntcodeTopology {
+    machines: Vec<(String, Vec<i64>)>,
+    programs: Vec<(String, Vec<i64>)>,
+    connections: Vec<(String, String)>,
+    outputs: Vec<String>,
+}
+
+impl IntcodeTopology {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn machine(mut self, name: &str, inputs: &[i64]) -> Self {
+        self.add_machine(name, inputs);
+        self
+    }
+
+    // Runs a different program on this machine instead of the one given to `run`
+    pub fn program(mut self, name: &str, machine_code: &[i64]) -> Self {
+        self.add_machine(name, &[]);
+        self.programs.retain(|(existing, _)| existing != name);
+        self.programs.push((String::from(name), machine_code.to_vec()));
+        self
+    }
+
+    pub fn connect(mut self, from: &str, to: &str) -> Self {
+        self.add_machine(from, &[]);
+        self.add_machine(to, &[]);
+        self.connections.push((String::from(from), String::from(to)));
+        self
+    }
+
+    pub fn output(mut self, name: &str) -> Self {
+        self.add_machine(name, &[]);
+        self.outputs.push(String::from(name));
+        self
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut topology = Self::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            topology.parse_line(line).map_err(|error| anyhow!("Line {}: {}", number + 1, error))?;
+        }
+
+        Ok(topology)
+    }
+
+    pub fn run(&self, machine_code: &[i64]) -> Result<IntcodeNetworkResult> {
+        let mut scheduler = IntcodeScheduler::new(IntcodeSchedulingPolicy::RoundRobin, DEFAULT_TIME_SLICE);
+        for (name, inputs) in &self.machines {
+            let program = self.programs.iter()
+                .find(|(program_name, _)| program_name == name)
+                .map(|(_, program)| program.as_slice())
+                .unwrap_or(machine_code);
+            scheduler.add_machine(program, inputs);
+        }
+        for (from, to) in &self.connections {
+            scheduler.connect(self.id(from), self.id(to))?;
+        }
+
+        let outcome = scheduler.run(MAX_INSTRUCTIONS);
+        match &outcome {
+            IntcodeSchedulerOutcome::InstructionLimit => bail!("Network didn't settle within {} instructions", MAX_INSTRUCTIONS),
+            IntcodeSchedulerOutcome::Faulted(id, fault) => bail!("Machine {} faulted: {}", self.machines[*id].0, fault),
+            _ => {},
+        }
+
+        let names: Vec<&String> = if self.outputs.is_empty() {
+            self.machines.iter().map(|(name, _)| name).collect()
+        } else {
+            self.outputs.iter().collect()
+        };
+        let outputs = names.into_iter()
+            .map(|name| {
+                let values = scheduler.machine(self.id(name))
+                    .output_handler()
+                    .history()
+                    .iter()
+                    .map(|value| value.parse::<i64>())
+                    .collect::<Result<Vec<i64>, _>>()?;
+                Ok((name.clone(), values))
+            })
+            .collect::<Result<Vec<(String, Vec<i64>)>>>()?;
+
+        Ok(IntcodeNetworkResult { outcome, outputs })
+    }
+
+    fn add_machine(&mut self, name: &str, inputs: &[i64]) {
+        match self.machines.iter_mut().find(|(existing, _)| existing == name) {
+            Some((_, existing)) => existing.extend_from_slice(inputs),
+            None => self.machines.push((String::from(name), inputs.to_vec())),
+        }
+    }
+
+    fn id(&self, name: &str) -> usize {
+        self.machines.iter().position(|(existing, _)| existing == name).expect("Unknown machine")
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<()> {
+        if let Some(names) = line.strip_prefix("output ") {
+            for name in names.split(',') {
+                let (name, inputs) = parse_machine(name)?;
+                if !inputs.is_empty() {
+                    bail!("Inputs can't be given on an output line");
+                }
+                self.outputs.push(name.clone());
+                self.add_machine(&name, &[]);
+            }
+            return Ok(());
+        }
+
+        let mut previous: Option<Vec<String>> = None;
+        for group in line.split("->") {
+            let names = split_group(group)?
+                .into_iter()
+                .map(|machine| {
+                    let (name, inputs) = parse_machine(&machine)?;
+                    self.add_machine(&name, &inputs);
+                    Ok(name)
+                })
+                .collect::<Result<Vec<String>>>()?;
+
+            if let Some(previous) = previous {
+                for from in &previous {
+                    for to in &names {
+                        self.connections.push((from.clone(), to.clone()));
+                    }
+                }
+            }
+            previous = Some(names);
+        }
+
+        Ok(())
+    }
+}
+
+// Splits on commas that aren't inside an input list
+fn split_group(group: &str) -> Result<Vec<String>> {
+    let mut machines = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    for c in group.chars() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                machines.push(current.clone());
+                current.clear();
+                continue;
+            },
+            _ => {},
+        }
+        current.push(c);
+    }
+    machines.push(current);
+
+    if depth != 0 {
+        bail!("Unbalanced brackets in {}", group.trim());
+    }
+    Ok(machines)
+}
+
+fn parse_machine(text: &str) -> Result<(String, Vec<i64>)> {
+    let text = text.trim();
+    let (name, inputs) = match text.find('[') {
+        Some(start) => {
+            let inputs = text[start + 1..].strip_suffix(']').ok_or(anyhow!("Expected ] after inputs in {}", text))?;
+            let inputs = inputs.split(',')
+                .map(|input| input.trim())
+                .filter(|input| !input.is_empty())
+                .map(|input| input.parse::<i64>().map_err(|_| anyhow!("Invalid input {} for {}", input, text)))
+                .collect::<Result<Vec<i64>>>()?;
+            (text[..start].trim(), inputs)
+        },
+        None => (text, Vec::new()),
+    };
+
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        bail!("Invalid machine name: {}", text);
+    }
+    Ok((String::from(name), inputs))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntcodeNetworkResult {
+    pub outcome: IntcodeSchedulerOutcome,
+    pub outputs: Vec<(String, Vec<i64>)>,
+}
+
+impl IntcodeNetworkResult {
+    pub fn output(&self, name: &str) -> Option<&[i64]> {
+        self.outputs.iter()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, values)| values.as_slice())
+    }
+
+    pub fn last_output(&self, name: &str) -> Option<i64> {
+        self.output(name).and_then(|values| values.last().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEEDBACK: [i64; 29] = [3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5];
+
+    #[test]
+    fn test_parse() {
+        let topology = IntcodeTopology::parse("# ring\nA[9, 0] -> B[8] -> A\nA -> C, D\noutput D").unwrap();
+        let expected = IntcodeTopology::new()
+            .machine("A", &[9, 0])
+            .machine("B", &[8])
+            .connect("A", "B")
+            .connect("B", "A")
+            .connect("A", "C")
+            .connect("A", "D")
+            .output("D");
+
+        assert_eq!(topology, expected);
+    }
+
+    #[test]
+    fn test_feedback_ring() {
+        let topology = IntcodeTopology::parse("A[9,0] -> B[8] -> C[7] -> D[6] -> E[5] -> A\noutput E").unwrap();
+        let result = topology.run(&FEEDBACK).unwrap();
+
+        assert_eq!(result.outcome, IntcodeSchedulerOutcome::Halted);
+        assert_eq!(result.last_output("E"), Some(139629729));
+        assert_eq!(result.output("A"), None);
+    }
+
+    #[test]
+    fn test_fan_out_and_fan_in() {
+        // Source outputs its input, doublers double it, the sum machine adds the two results
+        let source = vec![3,9,4,9,99,0,0,0,0,0];
+        let doubler = vec![3,9,102,2,9,9,4,9,99,0];
+        let sum = vec![3,11,3,12,1,11,12,11,4,11,99,0,0];
+        let topology = IntcodeTopology::parse("S[21] -> X, Y -> Sum").unwrap()
+            .program("S", &source)
+            .program("Sum", &sum);
+        let result = topology.run(&doubler).unwrap();
+
+        assert_eq!(result.last_output("Sum"), Some(84));
+        assert_eq!(result.output("X"), Some(&[42][..]));
+    }
+
+    #[test]
+    fn test_faults_are_errors() {
+        let topology = IntcodeTopology::parse("A[1] -> B").unwrap().program("B", &[3,0,42]);
+        let error = topology.run(&[3,0,4,0,99]).unwrap_err();
+
+        assert_eq!(error.to_string(), "Machine B faulted: Invalid instruction: 42");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(IntcodeTopology::parse("A[1 -> B").is_err());
+        assert!(IntcodeTopology::parse("A[x] -> B").is_err());
+        assert!(IntcodeTopology::parse("A -> -> B").is_err());
+    }
+}
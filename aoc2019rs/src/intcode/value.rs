@@ -1,3 +1,5 @@
+use crate::intcode::IntcodeFault;
+
 #[derive(Clone, PartialEq)]
 pub enum IntcodeValue {
     Position(usize),
@@ -7,12 +9,33 @@ pub enum IntcodeValue {
 
 impl IntcodeValue {
     pub fn evaluate(&self, memory: &[i64], relative_base: usize) -> i64 {
+        self.try_evaluate(memory, relative_base).unwrap_or_else(|fault| panic!("{}", fault))
+    }
+
+    // None for immediate values, which don't refer to memory
+    pub fn address(&self, relative_base: usize) -> Result<Option<i64>, IntcodeFault> {
         match self {
-            IntcodeValue::Position(position) => memory[*position],
-            IntcodeValue::Immediate(value) => *value,
-            IntcodeValue::Relative(offset) => memory[(relative_base as i64 + offset) as usize],
+            IntcodeValue::Position(position) => Ok(Some(*position as i64)),
+            IntcodeValue::Immediate(_) => Ok(None),
+            IntcodeValue::Relative(offset) => (relative_base as i64).checked_add(*offset).map(Some).ok_or(IntcodeFault::Overflow),
         }
     }
+
+    pub fn try_evaluate(&self, memory: &[i64], relative_base: usize) -> Result<i64, IntcodeFault> {
+        let address = match self {
+            IntcodeValue::Position(position) => *position as i64,
+            IntcodeValue::Immediate(value) => return Ok(*value),
+            IntcodeValue::Relative(offset) => (relative_base as i64).checked_add(*offset).ok_or(IntcodeFault::Overflow)?,
+        };
+
+        if address < 0 {
+            return Err(IntcodeFault::MemoryOutOfRange(address));
+        }
+
+        memory.get(address as usize)
+            .copied()
+            .ok_or(IntcodeFault::MemoryOutOfRange(address))
+    }
 }
 
 impl std::fmt::Debug for IntcodeValue {
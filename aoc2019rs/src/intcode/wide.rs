@@ -0,0 +1,331 @@
+use anyhow::{anyhow, Result};
+
+use std::collections::VecDeque;
+
+use crate::intcode::{IntcodeExtensions, IntcodeFault, IntcodeInstruction, IntcodeProfile, IntcodeState, IntcodeValue, IntcodeWord};
+use crate::intcode::{INITIAL_MEMORY, MAX_MEMORY};
+
+// A plain v9 interpreter that is generic over the memory cell type, for programs whose values
+// don't fit in an i64. Memory starts and grows the same way as IntcodeMachine's.
+pub struct IntcodeWideMachine<W = i128> {
+    state: IntcodeState,
+    instruction_pointer: usize,
+    relative_base: i64,
+    instruction_count: usize,
+    memory: Vec<W>,
+    inputs: VecDeque<W>,
+    outputs: Vec<W>,
+}
+
+impl<W> IntcodeWideMachine<W>
+where W: IntcodeWord,
+{
+    pub fn new(machine_code: &[W]) -> Self {
+        let mut memory = machine_code.to_vec();
+        if memory.len() < INITIAL_MEMORY {
+            memory.resize(INITIAL_MEMORY, W::zero());
+        }
+
+        Self {
+            state: IntcodeState::Initialized,
+            instruction_pointer: 0,
+            relative_base: 0,
+            instruction_count: 0,
+            memory,
+            inputs: VecDeque::new(),
+            outputs: Vec::new(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let machine_code = text.trim()
+            .split(',')
+            .map(|word| word.trim().parse::<W>().map_err(|_| anyhow!("Invalid Intcode word: {}", word)))
+            .collect::<Result<Vec<W>>>()?;
+
+        Ok(Self::new(&machine_code))
+    }
+
+    pub fn push_input(&mut self, value: W) {
+        self.inputs.push_back(value);
+    }
+
+    pub fn state(&self) -> &IntcodeState {
+        &self.state
+    }
+
+    pub fn instruction_pointer(&self) -> usize {
+        self.instruction_pointer
+    }
+
+    pub fn instruction_count(&self) -> usize {
+        self.instruction_count
+    }
+
+    pub fn outputs(&self) -> &[W] {
+        &self.outputs
+    }
+
+    pub fn memory(&self) -> &[W] {
+        &self.memory
+    }
+
+    pub fn run(&mut self) -> Result<(), IntcodeFault> {
+        self.state = IntcodeState::Running;
+        while self.state == IntcodeState::Running {
+            self.step()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn step(&mut self) -> Result<(), IntcodeFault> {
+        self.state = IntcodeState::Running;
+        match self.operate() {
+            Ok(()) => Ok(()),
+            Err(fault) => {
+                self.state = IntcodeState::Faulted(fault.clone());
+                Err(fault)
+            },
+        }
+    }
+
+    // Decoding is shared with IntcodeMachine, so opcodes, modes, sizes and their faults are the same.
+    // Operands are then read again as full words, since immediates may not fit in an i64.
+    fn decode(&self) -> Result<IntcodeInstruction, IntcodeFault> {
+        let ptr = self.instruction_pointer;
+        let opcode = self.memory.get(ptr)
+            .ok_or(IntcodeFault::InstructionPointerOutOfRange(ptr))?
+            .to_i64()
+            .ok_or(IntcodeFault::Overflow)?;
+        IntcodeProfile::V9.check(opcode)?;
+
+        let params: Vec<i64> = self.memory[ptr + 1..].iter()
+            .take(3)
+            .map(|word| word.to_i64().unwrap_or(0))
+            .collect();
+        IntcodeInstruction::try_decode(opcode, &params, &IntcodeExtensions::new())
+    }
+
+    fn operate(&mut self) -> Result<(), IntcodeFault> {
+        use IntcodeInstruction::*;
+
+        let instruction = self.decode()?;
+        match &instruction {
+            Add{x, y, position} | Multiply{x, y, position} => {
+                let x = self.read(0, x)?;
+                let y = self.read(1, y)?;
+                let value = if let Add{..} = instruction { x.checked_add(&y) } else { x.checked_mul(&y) };
+                self.write(2, position, value.ok_or(IntcodeFault::Overflow)?)?;
+            },
+            Input{position} => {
+                let address = self.address(0, position)?;
+                match self.inputs.pop_front() {
+                    Some(value) => self.store(address, value),
+                    None => {
+                        self.state = IntcodeState::Suspended;
+                        return Ok(());
+                    },
+                }
+            },
+            Output{value} => {
+                let value = self.read(0, value)?;
+                self.outputs.push(value);
+            },
+            JumpIfTrue{test_position, jump_position} | JumpIfFalse{test_position, jump_position} => {
+                let test = !self.read(0, test_position)?.is_zero();
+                if test == matches!(instruction, JumpIfTrue{..}) {
+                    let target = self.read(1, jump_position)?.to_i64().ok_or(IntcodeFault::Overflow)?;
+                    self.instruction_pointer = target as usize;
+                    self.instruction_count += 1;
+                    return Ok(());
+                }
+            },
+            IsLessThan{x, y, position} | IsEquals{x, y, position} => {
+                let x = self.read(0, x)?;
+                let y = self.read(1, y)?;
+                let result = if let IsLessThan{..} = instruction { x < y } else { x == y };
+                self.write(2, position, W::from_i64(result as i64))?;
+            },
+            SetRelativeBase{offset} => {
+                let offset = self.read(0, offset)?.to_i64().ok_or(IntcodeFault::Overflow)?;
+                let relative_base = self.relative_base.checked_add(offset).ok_or(IntcodeFault::Overflow)?;
+                if relative_base < 0 {
+                    return Err(IntcodeFault::InvalidRelativeBase(relative_base));
+                }
+                self.relative_base = relative_base;
+            },
+            Halt => {
+                self.state = IntcodeState::Halted;
+                self.instruction_count += 1;
+                return Ok(());
+            },
+            Extension{opcode, ..} => return Err(IntcodeFault::InvalidInstruction(*opcode)),
+        }
+
+        self.instruction_pointer += instruction.size();
+        self.instruction_count += 1;
+        Ok(())
+    }
+
+    fn param(&self, index: usize) -> W {
+        self.memory[self.instruction_pointer + 1 + index].clone()
+    }
+
+    fn address(&self, index: usize, value: &IntcodeValue) -> Result<usize, IntcodeFault> {
+        let param = self.param(index).to_i64().ok_or(IntcodeFault::Overflow)?;
+        let address = match value {
+            IntcodeValue::Relative(_) => self.relative_base.checked_add(param).ok_or(IntcodeFault::Overflow)?,
+            _ => param,
+        };
+
+        if address < 0 || address as usize >= MAX_MEMORY {
+            return Err(IntcodeFault::MemoryOutOfRange(address));
+        }
+        Ok(address as usize)
+    }
+
+    fn read(&self, index: usize, value: &IntcodeValue) -> Result<W, IntcodeFault> {
+        if let IntcodeValue::Immediate(_) = value {
+            return Ok(self.param(index));
+        }
+
+        let address = self.address(index, value)?;
+        Ok(self.memory.get(address).cloned().unwrap_or_else(W::zero))
+    }
+
+    fn write(&mut self, index: usize, target: &IntcodeValue, value: W) -> Result<(), IntcodeFault> {
+        let address = self.address(index, target)?;
+        self.store(address, value);
+        Ok(())
+    }
+
+    fn store(&mut self, address: usize, value: W) {
+        if address >= self.memory.len() {
+            self.memory.resize(address + 1, W::zero());
+        }
+        self.memory[address] = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{IntcodeBackend, IntcodeHistoryOutput, IntcodeMachine, IntcodeProgram, IntcodeQueueInput};
+    use crate::intcode::differential::{compare_backends, DEFAULT_MAX_STEPS};
+    use crate::intcode::fuzzer::IntcodeFuzzer;
+
+    // Keeps outputs as text so the wide machine can be compared with the others
+    struct WideBackend {
+        machine: IntcodeWideMachine<i64>,
+        outputs: Vec<String>,
+    }
+
+    impl IntcodeBackend for WideBackend {
+        fn name(&self) -> &'static str { "wide" }
+        fn state(&self) -> &IntcodeState { self.machine.state() }
+        fn instruction_pointer(&self) -> usize { self.machine.instruction_pointer() }
+        fn instruction_count(&self) -> usize { self.machine.instruction_count() }
+        fn memory(&self) -> &[i64] { self.machine.memory() }
+        fn outputs(&self) -> &[String] { &self.outputs }
+
+        fn step(&mut self) -> Result<(), IntcodeFault> {
+            let result = self.machine.step();
+            self.outputs = self.machine.outputs().iter().map(|output| output.to_string()).collect();
+            result
+        }
+    }
+
+    fn agrees_with_interpreter(program: &[i64], inputs: &[i64], max_steps: usize) {
+        let mut reference = IntcodeMachine::new(program, IntcodeQueueInput::new(inputs), IntcodeHistoryOutput::new());
+        reference.set_profile(IntcodeProfile::V9);
+        let mut machine = IntcodeWideMachine::new(program);
+        for input in inputs {
+            machine.push_input(*input);
+        }
+        let backends: Vec<Box<dyn IntcodeBackend>> = vec![
+            Box::new(reference),
+            Box::new(WideBackend { machine, outputs: Vec::new() }),
+        ];
+
+        if let Err(divergence) = compare_backends(backends, max_steps) {
+            panic!("{:?}: {}", program, divergence);
+        }
+    }
+
+    fn run_program<W: IntcodeWord>(text: &str) -> Result<Vec<W>, IntcodeFault> {
+        let mut machine = IntcodeWideMachine::<W>::parse(text).unwrap();
+        machine.run()?;
+        Ok(machine.outputs().to_vec())
+    }
+
+    #[test]
+    fn test_quine() {
+        let program = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        let expected: Vec<i128> = program.split(',').map(|word| word.parse().unwrap()).collect();
+        assert_eq!(run_program::<i128>(program), Ok(expected));
+    }
+
+    #[test]
+    fn test_large_values() {
+        assert_eq!(run_program::<i64>("1102,34915192,34915192,7,4,7,99,0"), Ok(vec![1219070632396864]));
+        assert_eq!(run_program::<i64>("104,1125899906842624,99"), Ok(vec![1125899906842624]));
+    }
+
+    #[test]
+    fn test_overflow_depends_on_word() {
+        let program = "1102,4294967296,4294967296,7,4,7,99,0";
+        assert_eq!(run_program::<i64>(program), Err(IntcodeFault::Overflow));
+        assert_eq!(run_program::<i128>(program), Ok(vec![18446744073709551616]));
+    }
+
+    #[test]
+    fn test_memory_grows() {
+        let mut machine = IntcodeWideMachine::<i64>::parse("3,5000,4,5000,99").unwrap();
+        machine.push_input(42);
+        machine.run().unwrap();
+
+        assert_eq!(machine.outputs(), &[42]);
+        assert_eq!(machine.memory().len(), 5001);
+    }
+
+    #[test]
+    fn test_agrees_with_interpreter() {
+        let program = IntcodeProgram::load(crate::utils::input::input_file_name(9)).unwrap().code;
+        agrees_with_interpreter(&program, &[1], DEFAULT_MAX_STEPS);
+
+        let mut fuzzer = IntcodeFuzzer::new(32);
+        for _ in 0..300 {
+            let case = fuzzer.generate();
+            agrees_with_interpreter(&case.program, &case.inputs, 2_000);
+        }
+    }
+
+    #[test]
+    fn test_truncated_instructions_fault_like_interpreter() {
+        // Jump to the last word of memory, which starts an instruction with its parameters missing
+        for tail in &[1, 1101, 4, 104, 1105, 9, 109] {
+            let mut program = vec![0; INITIAL_MEMORY];
+            program[..3].copy_from_slice(&[1105, 1, INITIAL_MEMORY as i64 - 1]);
+            program[INITIAL_MEMORY - 1] = *tail;
+            agrees_with_interpreter(&program, &[], 10);
+
+            let mut machine = IntcodeWideMachine::new(&program);
+            assert_eq!(machine.run(), Err(IntcodeFault::TruncatedInstruction(*tail)));
+        }
+
+        // Two of three parameters present
+        let mut program = vec![0; INITIAL_MEMORY];
+        program[..3].copy_from_slice(&[1105, 1, INITIAL_MEMORY as i64 - 3]);
+        program[INITIAL_MEMORY - 3..].copy_from_slice(&[1101, 2, 3]);
+        agrees_with_interpreter(&program, &[], 10);
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_big_integers() {
+        let program = "1102,170141183460469231731687303715884105727,2,7,4,7,99,0";
+        let outputs = run_program::<num_bigint::BigInt>(program).unwrap();
+        assert_eq!(outputs[0].to_string(), "340282366920938463463374607431768211454");
+    }
+}
@@ -7,11 +7,32 @@ mod solutions;
 mod utils;
 
 fn main() -> Result<()> {
-    let day_num = std::env::args().nth(1).ok_or(anyhow!("Please provide a day number as the first argument"))?;
-    
+    let args: Vec<String> = std::env::args().collect();
+    let command = args.get(1).ok_or(anyhow!("Please provide a day number or command as the first argument"))?;
+
+    let result = match command.as_ref() {
+        "adventure" => intcode::adventure::run_command(&args[2..])?,
+        "arcade" => intcode::arcade::run_command(&args[2..])?,
+        "decompile" => intcode::decompiler::run_command(&args[2..])?,
+        "diff" => intcode::differential::run_command(&args[2..])?,
+        "heatmap" => intcode::memory::run_command(&args[2..])?,
+        "modifications" => intcode::modification::run_command(&args[2..])?,
+        "droid" => intcode::droid::run_command(&args[2..])?,
+        "fuzz" => intcode::fuzzer::run_command(&args[2..])?,
+        "springdroid" => intcode::springdroid::run_command(&args[2..])?,
+        "record" | "replay" => intcode::session::run_command(command, &args[2..])?,
+        day_num => run_day(day_num)?,
+    };
+
+    println!("{}", result);
+
+    Ok(())
+}
+
+fn run_day(day_num: &str) -> Result<String> {
     println!("Running day #{}...", day_num);
 
-    let result = match day_num.as_ref() {
+    let result = match day_num {
         "1"  => solutions::day1::run()?,
         "2"  => solutions::day2::run()?,
         "3"  => solutions::day3::run()?,
@@ -27,7 +48,5 @@ fn main() -> Result<()> {
         _    => bail!("Invalid day number: {}", day_num),
     };
 
-    println!("{}", result);
-
-    Ok(())
+    Ok(result)
 }
@@ -1,5 +1,43 @@
 use anyhow::Result;
 
+use crate::utils::input;
+use crate::intcode::IntcodeProgram;
+use crate::intcode::robot::{IntcodeHullRobot, WHITE};
+
 pub fn run() -> Result<String> {
-    Ok(String::from("Not implemented"))
+    let program = IntcodeProgram::load(input::input_file_name(11))?.code;
+
+    let mut robot = IntcodeHullRobot::new();
+    robot.run(&program)?;
+
+    let mut registration = IntcodeHullRobot::starting_on(WHITE);
+    registration.run(&program)?;
+
+    Ok(format!("Part 1: {}\nPart 2:\n{}\n", robot.painted(), registration.render()))
+}
+
+// Part 1: 2082
+// Part 2: FARBCFJK
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn day11_part1() {
+        let program = IntcodeProgram::load(input::input_file_name(11)).unwrap().code;
+        let mut robot = IntcodeHullRobot::new();
+        robot.run(&program).unwrap();
+
+        assert_eq!(robot.painted(), 2082);
+    }
+
+    #[test]
+    fn day11_part2() {
+        let program = IntcodeProgram::load(input::input_file_name(11)).unwrap().code;
+        let mut robot = IntcodeHullRobot::starting_on(WHITE);
+        robot.run(&program).unwrap();
+
+        assert!(robot.render().starts_with(".####..##..###..###...##..####...##.#..#..."));
+    }
 }
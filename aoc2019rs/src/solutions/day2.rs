@@ -1,31 +1,24 @@
 use anyhow::{bail, Result};
 
 use crate::utils::input;
-use crate::intcode::IntcodeMachine;
+use crate::intcode::IntcodeProgram;
+use crate::intcode::symbolic::IntcodeSolver;
 
 pub fn run() -> Result<String> {
-    let program = input::read_input_list_as::<i64>(2, b',')?;
+    let program = IntcodeProgram::load(input::input_file_name(2))?.code;
+
+    let mut solver = IntcodeSolver::new(&program);
+    solver.unknown_memory(1, "noun", 0..=99);
+    solver.unknown_memory(2, "verb", 0..=99);
     
-    for noun in 0..=99 {
-        for verb in 0..=99 {
-            let result = run_test(&program, noun, verb);
-            if result == 19690720 {
-                return Ok(format!("{}", 100 * noun + verb));
-            }
-            
-        }
+    match solver.solve_memory(0, 19690720) {
+        Some(solution) => {
+            let noun = solution.value("noun").unwrap();
+            let verb = solution.value("verb").unwrap();
+            Ok(format!("{}", 100 * noun + verb))
+        },
+        None => bail!("Couldn't find inputs with output 19690720"),
     }
-
-    bail!("Couldn't find inputs with output 19690720")
-}
-
-fn run_test(program: &[i64], noun: i64, verb: i64) -> i64 {
-    let mut machine = IntcodeMachine::new_console_machine(&program);
-    machine.write_memory(1, noun);
-    machine.write_memory(2, verb);
-    machine.run();
-
-    machine.read_memory_position(0)
 }
 
 // Part 1: 7594646
@@ -33,7 +26,10 @@ fn run_test(program: &[i64], noun: i64, verb: i64) -> i64 {
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use crate::utils::input;
+    use crate::intcode::{IntcodeMachine, IntcodeProgram};
+    use crate::intcode::patch::IntcodePatchSet;
+    use crate::intcode::search::{grid, IntcodeSearch, IntcodeSearchGoal};
 
     #[test]
     fn day2_part1() {
@@ -57,8 +53,34 @@ mod tests {
         assert!(false)
     }
 
+    #[test]
+    fn day2_part2_search() {
+        let input = day2_input();
+        let result = IntcodeSearch::new(&input).run(
+            &grid(&[0..=99, 0..=99]),
+            IntcodeSearchGoal::Target(19690720),
+            |candidate, memory| {
+                memory[1] = candidate[0];
+                memory[2] = candidate[1];
+                Vec::new()
+            },
+            |machine| Some(machine.read_memory_position(0)));
+
+        let candidate = result.unwrap().candidate;
+        assert_eq!(100 * candidate[0] + candidate[1], 3376);
+    }
+
+    #[test]
+    fn day2_part1_patched() {
+        let mut machine = IntcodeMachine::new_console_machine(&day2_input());
+        IntcodePatchSet::new().patch(1, &[12, 2]).apply_to(&mut machine).unwrap();
+        machine.run();
+
+        assert_eq!(machine.read_memory_position(0), 7594646);
+    }
+
     fn day2_input() -> Vec<i64> {
-        crate::utils::input::read_input_list_as::<i64>(2, b',').unwrap()
+        IntcodeProgram::load(input::input_file_name(2)).unwrap().code
     }
 
     fn run_day2_test(program: &[i64], noun: i64, verb: i64) -> i64 {
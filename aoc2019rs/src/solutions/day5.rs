@@ -1,13 +1,13 @@
 use anyhow::Result;
 
 use crate::utils::input;
-use crate::intcode::helpers;
+use crate::intcode::{helpers, IntcodeProgram};
 
 // Part 1: 9025675
 // Part 2: 11981754
 
 pub fn run() -> Result<String> {
-    let program = input::read_input_list_as::<i64>(5, b',')?;
+    let program = IntcodeProgram::load(input::input_file_name(5))?.code;
 
     let part1 = helpers::process_input(&program, &[1])
         .last()
@@ -23,7 +23,8 @@ pub fn run() -> Result<String> {
 
 #[cfg(test)]
 mod tests {
-    use crate::intcode::helpers;
+    use crate::utils::input;
+    use crate::intcode::{helpers, IntcodeProgram};
 
     #[test]
     fn day5_part1() {
@@ -33,7 +34,7 @@ mod tests {
     }
 
     fn day5_input() -> Vec<i64> {
-        crate::utils::input::read_input_list_as::<i64>(5, b',').unwrap()
+        IntcodeProgram::load(input::input_file_name(5)).unwrap().code
     }
 
     #[test]
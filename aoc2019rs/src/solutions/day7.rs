@@ -1,86 +1,64 @@
 use anyhow::Result;
 
-use std::str::FromStr;
-
 use crate::utils::{input, math};
-use crate::intcode::{IntcodeMachine, IntcodeState, IntcodeOutput, helpers};
+use crate::intcode::IntcodeProgram;
+use crate::intcode::topology::IntcodeTopology;
+use crate::intcode::search::{search, default_threads, IntcodeSearchGoal};
 
 // Part 1: 43812
 // Part 2: 59597414
 
 pub fn run() -> Result<String> {
-    let program = input::read_input_list_as::<i64>(7, b',')?;
+    let program = IntcodeProgram::load(input::input_file_name(7))?.code;
 
     Ok(format!("Part 1: {}\nPart 2: {}", run_day_1(&program), run_day_2(&program)))
 }
 
+const AMPLIFIERS: [&str; 5] = ["A", "B", "C", "D", "E"];
+
 fn run_day_1(program: &[i64]) -> i64 {
     let phase_permutations = math::permutations_cloned::<i64>(&[0, 1, 2, 3, 4]);
 
-    phase_permutations.into_iter()
-        .map(|permutation| run_day_1_phase_permutation(&program, &permutation))
-        .max()
-        .unwrap()
+    search(&phase_permutations, default_threads(), IntcodeSearchGoal::Maximize, |permutation| {
+        Some(run_day_1_phase_permutation(program, permutation))
+    }).unwrap().value
 }
 
 fn run_day_1_phase_permutation(program: &[i64], phases: &[i64]) -> i64 {
-    let mut next_input = 0;
-    for phase in phases {
-        let output = helpers::process_input(&program.clone(), &[*phase, next_input])
-            .last()
-            .unwrap()
-            .clone();
-        next_input = i64::from_str(&output).unwrap();
-    }
-
-    next_input
+    run_amplifiers(program, amplifier_chain(phases))
 }
 
 fn run_day_2(program: &[i64]) -> i64 {
     let phase_permutations = math::permutations_cloned::<i64>(&[5, 6, 7, 8, 9]);
     
-    phase_permutations.into_iter()
-        .map(|permutation| run_day_2_phase_permutation(&program, &permutation))
-        .max()
-        .unwrap()
+    search(&phase_permutations, default_threads(), IntcodeSearchGoal::Maximize, |permutation| {
+        Some(run_day_2_phase_permutation(program, permutation))
+    }).unwrap().value
 }
 
 fn run_day_2_phase_permutation(program: &[i64], phases: &[i64]) -> i64 {
-    let mut amplifiers = vec![
-        IntcodeMachine::new_blocking_machine(&program),
-        IntcodeMachine::new_blocking_machine(&program),
-        IntcodeMachine::new_blocking_machine(&program),
-        IntcodeMachine::new_blocking_machine(&program),
-        IntcodeMachine::new_blocking_machine(&program),
-    ];
-
-    // Initialize with phase
-    for (i, amp) in amplifiers.iter_mut().enumerate() {
-        amp.run();
-        amp.input(phases[i]);
-    }
+    // The last amplifier feeds back into the first
+    run_amplifiers(program, amplifier_chain(phases).connect("E", "A"))
+}
 
-    let mut next_input = 0;
-    loop {
-        for amp in amplifiers.iter_mut() {
-            amp.run();
-            amp.input(next_input);
-            amp.run();
-
-            let output = amp.output_handler()
-                .last_output()
-                .expect("No output available")
-                .to_owned();
-
-            next_input = i64::from_str(&output).unwrap();
-        }
-        if amplifiers[4].state() == &IntcodeState::Halted {
-            break;
-        }
+// A -> B -> C -> D -> E, each amplifier starting with its phase and A also with the signal 0
+fn amplifier_chain(phases: &[i64]) -> IntcodeTopology {
+    let mut topology = IntcodeTopology::new();
+    for (name, phase) in AMPLIFIERS.iter().zip(phases) {
+        topology = topology.machine(name, &[*phase]);
     }
+    for pair in AMPLIFIERS.windows(2) {
+        topology = topology.connect(pair[0], pair[1]);
+    }
+
+    topology.machine("A", &[0]).output("E")
+}
 
-    let last_output = amplifiers[4].output_handler().last_output().unwrap().to_owned();
-    i64::from_str(&last_output).unwrap()
+fn run_amplifiers(program: &[i64], topology: IntcodeTopology) -> i64 {
+    topology.run(program)
+        .expect("Amplifiers failed")
+        .last_output("E")
+        .expect("No output available")
 }
 
 #[cfg(test)] 
@@ -89,8 +67,15 @@ mod tests {
 
     #[test]
     fn day7_part1_test() {
-        let program = input::read_input_list_as::<i64>(7, b',').unwrap();
+        let program = IntcodeProgram::load(input::input_file_name(7)).unwrap().code;
         let result = run_day_1(&program);
         assert_eq!(result, 43812);
     }
+
+    #[test]
+    fn day7_part2_test() {
+        let program = IntcodeProgram::load(input::input_file_name(7)).unwrap().code;
+        let result = run_day_2(&program);
+        assert_eq!(result, 59597414);
+    }
 }
@@ -1,10 +1,10 @@
 use anyhow::Result;
 
 use crate::utils::input;
-use crate::intcode::helpers;
+use crate::intcode::{helpers, IntcodeProgram};
 
 pub fn run() -> Result<String> {
-    let program = input::read_input_list_as::<i64>(9, b',')?;
+    let program = IntcodeProgram::load(input::input_file_name(9))?.code;
 
     
     let(instructions, output) = helpers::debug_process_input(&program, &[1]);
@@ -1,5 +1,7 @@
 pub mod conversion;
 pub mod graph;
+pub mod grid;
 pub mod input;
 pub mod math;
 pub mod parser;
+pub mod random;
@@ -149,6 +149,67 @@ where T: Hash + Eq + Copy
 
         None
     }
+
+    // Breadth first, so the path has the fewest edges
+    pub fn shortest_path(&self, start: T, target: T) -> Option<Vec<T>> {
+        use std::collections::VecDeque;
+
+        if !self.adjacency_map.contains_key(&start) {
+            return None;
+        }
+
+        let mut queue = VecDeque::new();
+        let mut previous: HashMap<T, T> = HashMap::new();
+
+        queue.push_back(start);
+        previous.insert(start, start);
+        while let Some(next) = queue.pop_front() {
+            if next == target {
+                let mut path = vec![next];
+                let mut node = next;
+                while node != start {
+                    node = previous[&node];
+                    path.push(node);
+                }
+                path.reverse();
+                return Some(path)
+            }
+
+            for item in self.edges(next) {
+                if !previous.contains_key(item) {
+                    previous.insert(*item, next);
+                    queue.push_back(*item);
+                }
+            }
+        }
+
+        None
+    }
+
+    // Number of edges to every node reachable from start, empty if start isn't in the graph
+    pub fn distances(&self, start: T) -> HashMap<T, usize> {
+        use std::collections::VecDeque;
+
+        let mut queue = VecDeque::new();
+        let mut distances = HashMap::new();
+        if !self.adjacency_map.contains_key(&start) {
+            return distances;
+        }
+
+        queue.push_back(start);
+        distances.insert(start, 0);
+        while let Some(next) = queue.pop_front() {
+            let distance = distances[&next];
+            for item in self.edges(next) {
+                if !distances.contains_key(item) {
+                    distances.insert(*item, distance + 1);
+                    queue.push_back(*item);
+                }
+            }
+        }
+
+        distances
+    }
 }
 
 #[cfg(test)]
@@ -173,4 +234,21 @@ mod tests {
         let path = graph.path_between(3, 6).unwrap();
         assert_eq!(path, vec![3, 4, 5, 6]);
     }
+
+    #[test]
+    fn test_shortest_path_and_distances() {
+        let mut graph: Graph<i32> = Graph::new();
+        graph.add_nodes(vec![1, 2, 3, 4, 5]);
+        graph.add_edges(vec![(1, 2), (2, 3), (3, 4), (1, 5), (5, 4), (4, 1)]);
+
+        assert_eq!(graph.shortest_path(1, 4).unwrap(), vec![1, 5, 4]);
+        assert_eq!(graph.shortest_path(1, 1).unwrap(), vec![1]);
+        assert_eq!(graph.shortest_path(4, 3).unwrap(), vec![4, 1, 2, 3]);
+
+        let distances = graph.distances(2);
+        assert_eq!((distances[&3], distances[&1], distances[&5]), (1, 3, 4));
+
+        assert_eq!(graph.shortest_path(6, 1), None);
+        assert!(graph.distances(6).is_empty());
+    }
 }
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+pub type Point = (i64, i64);
+
+// y grows downwards, so Up is (0, -1)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Direction {
+    pub fn turn_left(self) -> Self {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Left => Direction::Down,
+            Direction::Down => Direction::Right,
+            Direction::Right => Direction::Up,
+        }
+    }
+
+    pub fn turn_right(self) -> Self {
+        self.turn_left().turn_left().turn_left()
+    }
+
+    pub fn reverse(self) -> Self {
+        self.turn_left().turn_left()
+    }
+
+    pub fn offset(self) -> Point {
+        match self {
+            Direction::Up => (0, -1),
+            Direction::Right => (1, 0),
+            Direction::Down => (0, 1),
+            Direction::Left => (-1, 0),
+        }
+    }
+
+    pub fn step(self, (x, y): Point) -> Point {
+        let (dx, dy) = self.offset();
+        (x + dx, y + dy)
+    }
+}
+
+// Sparse grid that grows in every direction
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid<T> {
+    cells: HashMap<Point, T>,
+}
+
+impl<T> Default for Grid<T> {
+    fn default() -> Self {
+        Self { cells: HashMap::new() }
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn get(&self, point: Point) -> Option<&T> {
+        self.cells.get(&point)
+    }
+
+    pub fn set(&mut self, point: Point, value: T) {
+        self.cells.insert(point, value);
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=(&Point, &T)> {
+        self.cells.iter()
+    }
+
+    // Top left and bottom right corners, inclusive
+    pub fn bounds(&self) -> Option<(Point, Point)> {
+        let xs = self.cells.keys().map(|(x, _)| *x);
+        let ys = self.cells.keys().map(|(_, y)| *y);
+        Some(((xs.clone().min()?, ys.clone().min()?), (xs.max()?, ys.max()?)))
+    }
+
+    // One character per cell within the bounds, rows separated by newlines
+    pub fn render<F>(&self, mut cell: F) -> String
+    where F: FnMut(Point, Option<&T>) -> char
+    {
+        let ((min_x, min_y), (max_x, max_y)) = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return String::new(),
+        };
+
+        (min_y..=max_y)
+            .map(|y| (min_x..=max_x).map(|x| cell((x, y), self.get((x, y)))).collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_turning() {
+        assert_eq!(Direction::Up.turn_left(), Direction::Left);
+        assert_eq!(Direction::Up.turn_right(), Direction::Right);
+        assert_eq!(Direction::Left.turn_right().turn_right(), Direction::Right);
+        assert_eq!(Direction::Down.reverse(), Direction::Up);
+        assert_eq!(Direction::Down.step((2, 3)), (2, 4));
+    }
+
+    #[test]
+    fn test_render() {
+        let mut grid = Grid::new();
+        grid.set((-1, 0), 1);
+        grid.set((1, 1), 2);
+
+        assert_eq!(grid.bounds(), Some(((-1, 0), (1, 1))));
+        assert_eq!(grid.render(|_, cell| cell.map(|value| if *value == 1 { '#' } else { '+' }).unwrap_or('.')), "#..\n..+");
+        assert_eq!(Grid::<i64>::new().render(|_, _| '#'), "");
+    }
+}
@@ -10,7 +10,11 @@ pub fn input_file_name(day_number: u8) -> String {
 }
 
 pub fn input_file_reader(day_number: u8) -> Result<BufReader<File>> {
-    let file = File::open(input_file_name(day_number))?;
+    file_reader(&input_file_name(day_number))
+}
+
+pub fn file_reader(file_name: &str) -> Result<BufReader<File>> {
+    let file = File::open(file_name)?;
     Ok(BufReader::new(file))
 }
 
@@ -31,7 +35,10 @@ where T: FromStr
 }
 
 pub fn read_input_list(day_number: u8, delimiter: u8) -> Result<Vec<String>> {
-    let file = input_file_reader(day_number)?;
+    read_list(input_file_reader(day_number)?, delimiter)
+}
+
+fn read_list(file: BufReader<File>, delimiter: u8) -> Result<Vec<String>> {
     let result = file
         .split(delimiter)
         .flatten()
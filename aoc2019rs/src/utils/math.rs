@@ -1,3 +1,5 @@
+use std::ops::RangeInclusive;
+
 pub fn index_permutations(size: usize) -> Vec<Vec<usize>> {
     let mut result = Vec::new();
     let mut current_size = 0;
@@ -49,6 +51,43 @@ pub fn permutations_cloned<T: Clone>(input: &[T]) -> Vec<Vec<T>> {
         .collect()
 }
 
+// Every combination of one value from each range, with the last range changing fastest.
+// Combinations are made one at a time like an odometer, so nothing is built up front.
+#[derive(Clone, Debug)]
+pub struct RangeProduct {
+    ranges: Vec<RangeInclusive<i64>>,
+    next: Option<Vec<i64>>,
+}
+
+pub fn product(ranges: &[RangeInclusive<i64>]) -> RangeProduct {
+    let next = if ranges.iter().any(|range| range.is_empty()) {
+        None
+    } else {
+        Some(ranges.iter().map(|range| *range.start()).collect())
+    };
+    RangeProduct { ranges: ranges.to_vec(), next }
+}
+
+impl Iterator for RangeProduct {
+    type Item = Vec<i64>;
+
+    fn next(&mut self) -> Option<Vec<i64>> {
+        let current = self.next.take()?;
+
+        let mut following = current.clone();
+        for (value, range) in following.iter_mut().zip(&self.ranges).rev() {
+            if *value < *range.end() {
+                *value += 1;
+                self.next = Some(following);
+                break;
+            }
+            *value = *range.start();
+        }
+
+        Some(current)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -64,6 +103,15 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn test_product() {
+        assert_eq!(product(&[0..=1, 5..=6]).collect::<Vec<_>>(), vec![vec![0, 5], vec![0, 6], vec![1, 5], vec![1, 6]]);
+        assert_eq!(product(&[]).collect::<Vec<_>>(), vec![Vec::<i64>::new()]);
+        assert_eq!(product(&[0..=3, RangeInclusive::new(1, 0)]).next(), None);
+        assert_eq!(product(&[i64::MAX - 1..=i64::MAX]).collect::<Vec<_>>(), vec![vec![i64::MAX - 1], vec![i64::MAX]]);
+        assert_eq!(product(&[0..=i64::MAX, 0..=i64::MAX]).nth(3), Some(vec![0, 3]));
+    }
+
     #[test]
     fn test_index_permutations() {
         assert_eq!(index_permutations(0), Vec::<Vec<usize>>::new());